@@ -33,4 +33,5 @@ impl<const P: char, const N: u8> EventPin<P, N> {
     }
 }
 
+pub type EventPinCc1101Gdo0 = EventPin<'G', 3>;
 pub type EventPinCc1101Gdo2 = EventPin<'D', 2>;
@@ -5,6 +5,7 @@ pub mod button;
 pub mod event_pin;
 pub mod led;
 pub mod serial;
+pub mod serial_async;
 pub mod spi;
 pub mod spi_adapter;
 pub mod temp;
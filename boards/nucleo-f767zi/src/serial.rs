@@ -7,6 +7,34 @@ use stm32f7xx_hal::{
     serial::{self, Error, Instance, PinRx, PinTx, Rx, Serial, Tx},
 };
 
+#[derive(Debug)]
+pub enum SerialError {
+    Framing,
+    Noise,
+    Overrun,
+    Parity,
+    Other,
+}
+
+impl From<Error> for SerialError {
+    fn from(value: Error) -> Self {
+        match value {
+            Error::Framing => Self::Framing,
+            Error::Noise => Self::Noise,
+            Error::Overrun => Self::Overrun,
+            Error::Parity => Self::Parity,
+            #[allow(unreachable_patterns)]
+            _ => Self::Other,
+        }
+    }
+}
+
+impl embedded_io::Error for SerialError {
+    fn kind(&self) -> embedded_io::ErrorKind {
+        embedded_io::ErrorKind::Other
+    }
+}
+
 pub struct SerialParameters<'a, UART, const P: char, const N_TX: u8, const N_RX: u8, const A: u8> {
     pub uart: UART,
     pub clocks: &'a Clocks,
@@ -63,4 +91,26 @@ where
     }
 }
 
+impl<UART, const P: char, const N_TX: u8, const N_RX: u8, const A: u8> embedded_io::ErrorType
+    for SerialUart<UART, P, N_TX, N_RX, A>
+{
+    type Error = SerialError;
+}
+
+impl<UART: Instance, const P: char, const N_TX: u8, const N_RX: u8, const A: u8> embedded_io::Write
+    for SerialUart<UART, P, N_TX, N_RX, A>
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        for byte in buf {
+            nb::block!(self.tx.write(*byte))?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        nb::block!(self.tx.flush())?;
+        Ok(())
+    }
+}
+
 pub type SerialUartUsb = SerialUart<USART3, 'D', 8, 9, 7>;
@@ -0,0 +1,102 @@
+use crate::serial::{SerialError, SerialParameters};
+use async_serial::{AsyncSerial, BufferOverrun, InterruptUart};
+use cortex_m::peripheral::NVIC;
+use embedded_hal::serial::{Read, Write};
+use stm32f7xx_hal::{
+    gpio::{Alternate, Pin},
+    pac::{Interrupt, USART3},
+    serial::{self, Event, Instance, PinRx, PinTx, Serial},
+};
+
+pub use async_serial::SERIAL_BUFFER_LEN;
+
+/// Receive and transmit buffers of an async serial, to be placed in a `static`.
+pub type SerialBuffers = async_serial::SerialBuffers<SerialError>;
+
+/// Interrupt driven serial, implementing the `embedded-io-async` traits. The UART is owned
+/// by the `SerialInterrupt` half, whose `on_interrupt` shall be called from the UART
+/// interrupt handler.
+pub type AsyncSerialUart = AsyncSerial<SerialError, Interrupt>;
+
+impl From<BufferOverrun> for SerialError {
+    fn from(_: BufferOverrun) -> Self {
+        SerialError::Overrun
+    }
+}
+
+pub struct SerialInterrupt<UART, const P: char, const N_TX: u8, const N_RX: u8, const A: u8> {
+    serial: Serial<UART, (Pin<P, N_TX, Alternate<A>>, Pin<P, N_RX, Alternate<A>>)>,
+    buffers: &'static SerialBuffers,
+}
+
+impl<UART: Instance, const P: char, const N_TX: u8, const N_RX: u8, const A: u8>
+    SerialInterrupt<UART, P, N_TX, N_RX, A>
+where
+    Pin<P, N_TX, Alternate<A>>: PinTx<UART>,
+    Pin<P, N_RX, Alternate<A>>: PinRx<UART>,
+{
+    /// Create the async serial and its interrupt half. `interrupt` is the UART interrupt,
+    /// pended by the async serial to start a transmission.
+    pub fn new(
+        serial_parameters: SerialParameters<UART, P, N_TX, N_RX, A>,
+        buffers: &'static SerialBuffers,
+        interrupt: Interrupt,
+    ) -> (AsyncSerialUart, Self) {
+        // Init UART pins
+        let pin_uart_tx: Pin<P, N_TX, Alternate<A>> = serial_parameters.pin_tx.into_alternate();
+        let pin_uart_rx: Pin<P, N_RX, Alternate<A>> = serial_parameters.pin_rx.into_alternate();
+
+        // Init UART Serial - Default to 115_200 bauds
+        let mut serial = Serial::new(
+            serial_parameters.uart,
+            (pin_uart_tx, pin_uart_rx),
+            serial_parameters.clocks,
+            serial::Config {
+                ..Default::default()
+            },
+        );
+        serial.listen(Event::Rxne);
+
+        (
+            AsyncSerial::new(buffers, interrupt, NVIC::pend),
+            Self { serial, buffers },
+        )
+    }
+
+    /// Move the received bytes into the receive buffer and the bytes to send into the UART.
+    pub fn on_interrupt(&mut self) {
+        let buffers = self.buffers;
+        buffers.on_interrupt(self);
+    }
+}
+
+impl<UART: Instance, const P: char, const N_TX: u8, const N_RX: u8, const A: u8> InterruptUart
+    for SerialInterrupt<UART, P, N_TX, N_RX, A>
+where
+    Pin<P, N_TX, Alternate<A>>: PinTx<UART>,
+    Pin<P, N_RX, Alternate<A>>: PinRx<UART>,
+{
+    type Error = SerialError;
+
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        self.serial
+            .read()
+            .map_err(|error| error.map(SerialError::from))
+    }
+
+    fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
+        self.serial
+            .write(byte)
+            .map_err(|error| error.map(|_| SerialError::Other))
+    }
+
+    fn listen_tx(&mut self, enable: bool) {
+        if enable {
+            self.serial.listen(Event::Txe);
+        } else {
+            self.serial.unlisten(Event::Txe);
+        }
+    }
+}
+
+pub type SerialInterruptUsb = SerialInterrupt<USART3, 'D', 8, 9, 7>;
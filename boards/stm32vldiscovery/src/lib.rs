@@ -4,3 +4,4 @@
 pub mod button;
 pub mod led;
 pub mod serial;
+pub mod serial_async;
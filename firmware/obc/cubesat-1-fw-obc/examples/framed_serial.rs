@@ -0,0 +1,94 @@
+#![no_main]
+#![no_std]
+#![feature(type_alias_impl_trait)]
+
+use frame_processing::{
+    config::SfpConfig,
+    framed::{FramedEvent, FramedSerial},
+    framing::Framing,
+};
+use nucleo_f767zi::{
+    serial::SerialParameters,
+    serial_async::{AsyncSerialUart, SerialBuffers, SerialInterruptUsb},
+};
+use panic_halt as _;
+use rtic::app;
+use stm32f7xx_hal::{pac::Interrupt, prelude::*};
+
+static SERIAL_BUFFERS: SerialBuffers = SerialBuffers::new();
+
+#[app(device = stm32f7xx_hal::pac, dispatchers = [TIM2])]
+mod app {
+    use super::*;
+
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {
+        serial: Option<AsyncSerialUart>,
+        serial_interrupt: SerialInterruptUsb,
+    }
+
+    #[init]
+    fn init(ctx: init::Context) -> (Shared, Local) {
+        let dp = ctx.device;
+
+        // Set up the system clock. We want to run at 216MHz for this one.
+        let rcc = dp.RCC.constrain();
+        let clocks = rcc.cfgr.sysclk(216.MHz()).freeze();
+
+        // Initialize GPIO Ports
+        let gpiod = dp.GPIOD.split();
+
+        // Initialize interrupt driven UART for serial communication through USB
+        let (serial, serial_interrupt) = SerialInterruptUsb::new(
+            SerialParameters {
+                uart: dp.USART3,
+                clocks: &clocks,
+                pin_tx: gpiod.pd8,
+                pin_rx: gpiod.pd9,
+            },
+            &SERIAL_BUFFERS,
+            Interrupt::USART3,
+        );
+
+        // Spawn tasks
+        task_serial_com::spawn().ok();
+
+        (
+            Shared {},
+            Local {
+                serial: Some(serial),
+                serial_interrupt,
+            },
+        )
+    }
+
+    #[task(priority = 1, local = [serial])]
+    async fn task_serial_com(ctx: task_serial_com::Context) {
+        let config = SfpConfig::default();
+        let serial = ctx.local.serial.take().unwrap();
+        let mut framed = FramedSerial::new(
+            serial,
+            Framing::Sfp.encoder(config),
+            Framing::Sfp.decoder(config),
+        );
+
+        loop {
+            // Answer to each frame, as in the serial_stm32vldiscovery example
+            let answer: [u8; 2] = match framed.receive().await {
+                Ok(FramedEvent::Frame(_)) => [0xCA, 0xFE],
+                Ok(FramedEvent::Rejected(_)) => [0xFF, 0xFF],
+                _ => continue,
+            };
+
+            framed.send(&answer).await.ok();
+        }
+    }
+
+    #[task(binds = USART3, local = [serial_interrupt])]
+    fn serial_isr(ctx: serial_isr::Context) {
+        ctx.local.serial_interrupt.on_interrupt();
+    }
+}
@@ -8,7 +8,11 @@
 use cortex_m_semihosting::hprintln;
 
 use cortex_m_rt::entry;
-use frame_processing::{frame::pack_frame, frame::process_incoming_frame};
+use frame_processing::{
+    config::SfpConfig,
+    decoder::{SfpDecoder, SfpEvent},
+    encoder::write_frame,
+};
 use nb::block;
 use stm32f1xx_hal::{pac, prelude::*};
 use stm32vldiscovery::serial::{SerialParameters, SerialUartUsb};
@@ -38,37 +42,46 @@ fn main() -> ! {
     // Initialize UART for serial communication through USB
     let mut serial = SerialUartUsb::new(serial_parameters);
 
-    let mut buffer: [u8; 1024] = [0; 1024];
-    let mut answer: [u8; 1024] = [0; 1024];
-    let mut buffer_size = 0usize;
+    let config = SfpConfig::default();
+    let mut decoder = SfpDecoder::new(config);
+    let mut console: [u8; 64] = [0; 64];
+    let mut console_len = 0usize;
+    let mut console_line = false;
 
     loop {
         let received = block!(serial.read()).unwrap();
 
-        if buffer_size < buffer.len() {
-            buffer[buffer_size] = received;
-            buffer_size += 1;
-        }
-
-        // Process the frame and see if it's valid or not
-        let (complete_frame, frame_valid) = process_incoming_frame(&mut buffer, &mut buffer_size);
+        // Bytes outside of frames are collected as console text
+        let mut passthrough = |byte: u8| {
+            if byte == b'\n' {
+                console_line = true;
+            } else if console_len < console.len() {
+                console[console_len] = byte;
+                console_len += 1;
+            }
+        };
 
-        if complete_frame {
-            if frame_valid {
+        // Process the received byte and answer to each complete frame
+        decoder.push_routed(received, &mut passthrough, |event| {
+            let data = match event {
                 // Frame was valid - prepare a message
-                let data = [0xCA, 0xFE];
-                let frame_len = pack_frame(&data, &mut answer);
-                for i in 0..(frame_len) {
-                    serial.write(answer[i]).unwrap();
-                }
-            } else {
+                SfpEvent::Frame(_) => [0xCA, 0xFE],
                 // Frame was not valid - prepare a message
-                let data = [0xFF, 0xFF];
-                let frame_len = pack_frame(&data, &mut answer);
-                for i in 0..(frame_len) {
-                    serial.write(answer[i]).unwrap();
-                }
+                SfpEvent::CrcError => [0xFF, 0xFF],
+                _ => return,
+            };
+
+            // Stream the answer frame directly to the serial
+            write_frame(&config, &mut serial, &data).unwrap();
+        });
+
+        // Echo back each complete console line
+        if console_line {
+            if let Ok(line) = core::str::from_utf8(&console[..console_len]) {
+                serial.formatln(format_args!("console: {}", line));
             }
+            console_len = 0;
+            console_line = false;
         }
     }
 }
@@ -10,10 +10,13 @@ use rtic_monotonics::{systick::Systick, Monotonic};
 #[cfg(feature = "nucleo-f767zi-board")]
 mod nucleo_f767zi_board {
     use super::*;
-    use cc1101_wrapper::{Cc1101Wrapper, PACKET_LENGTH};
+    use cc1101_wrapper::{
+        Cc1101Wrapper, Gdo, GdoEvents, RadioPowerMode, SystickClock, BROADCAST_ADDRESS,
+        PACKET_LENGTH,
+    };
     use nucleo_f767zi::{
         button::{Button, ButtonParameters},
-        event_pin::{EventPinCc1101Gdo2, EventPinParameters},
+        event_pin::{EventPinCc1101Gdo0, EventPinCc1101Gdo2, EventPinParameters},
         led::{LedBlue, LedGreen, LedParameters, LedRed},
         serial::{SerialParameters, SerialUartUsb},
         spi::SpiMaster3,
@@ -21,6 +24,20 @@ mod nucleo_f767zi_board {
     };
     use stm32f7xx_hal::{gpio::Edge, pac, prelude::*};
 
+    // CC1101 GDO interrupt events, signalled by the ISR and awaited by the CC1101 Wrapper
+    static CC1101_EVENTS: GdoEvents = GdoEvents::new();
+
+    // CC1101 power mode commanded by a byte received on the serial: 'c' Continuous, 'w'
+    // Wake-on-Radio, 'p' Power Down
+    fn cc1101_power_mode_command(byte: u8) -> Option<RadioPowerMode> {
+        match byte {
+            b'c' => Some(RadioPowerMode::Continuous),
+            b'w' => Some(RadioPowerMode::WakeOnRadio),
+            b'p' => Some(RadioPowerMode::PowerDown),
+            _ => None,
+        }
+    }
+
     #[app(device = pac, dispatchers = [TIM2, TIM3])]
     mod app {
         use super::*;
@@ -41,7 +58,10 @@ mod nucleo_f767zi_board {
         struct Shared {
             serial: SerialUartUsb,
             button_int_signal: bool,
-            cc1101_int_signal: bool,
+            // CC1101 power mode between the RF operations. Wake-on-Radio saves power in the
+            // low power modes of the OBC, at the cost of a preamble longer than the wake-up
+            // period.
+            cc1101_power_mode: RadioPowerMode,
         }
 
         #[local]
@@ -51,7 +71,8 @@ mod nucleo_f767zi_board {
             led_blue: LedBlue,
             led_red: LedRed,
             cc1101_int: EventPinCc1101Gdo2,
-            cc1101_wrp: Cc1101Wrapper<Cc1101SpiAdapter>,
+            cc1101_fifo_int: EventPinCc1101Gdo0,
+            cc1101_wrp: Option<Cc1101Wrapper<Cc1101SpiAdapter, SystickClock>>,
         }
 
         #[init]
@@ -70,6 +91,7 @@ mod nucleo_f767zi_board {
             let gpiob = dp.GPIOB.split();
             let gpioc = dp.GPIOC.split();
             let gpiod = dp.GPIOD.split();
+            let gpiog = dp.GPIOG.split();
 
             // Initialize systick
             let sysclk = (216.MHz() as HertzU32).to_Hz();
@@ -120,8 +142,18 @@ mod nucleo_f767zi_board {
                 apb: &mut rcc.apb2,
             });
 
-            // Initialize CC1101 Wrapper - RF Transceiver
-            let cc1101_wrp = Cc1101Wrapper::new(SpiAdapter::new(spi_3.spi, spi_3.cs));
+            // Initialize CC1101 FIFO threshold interrupt
+            let cc1101_fifo_int = EventPinCc1101Gdo0::new(EventPinParameters {
+                pin: gpiog.pg3,
+                edge: Edge::Rising,
+                syscfg: &mut syscfg,
+                exti: &mut exti,
+                apb: &mut rcc.apb2,
+            });
+
+            // Initialize CC1101 Wrapper - RF Transceiver. Keep running without radio if missing
+            let cc1101_wrp =
+                Cc1101Wrapper::new(SpiAdapter::new(spi_3.spi, spi_3.cs), SystickClock).ok();
 
             // Spawn tasks
             task_10ms::spawn().ok();
@@ -132,7 +164,7 @@ mod nucleo_f767zi_board {
                 Shared {
                     serial,
                     button_int_signal: false,
-                    cc1101_int_signal: false,
+                    cc1101_power_mode: RadioPowerMode::Continuous,
                 },
                 Local {
                     button,
@@ -140,18 +172,31 @@ mod nucleo_f767zi_board {
                     led_blue,
                     led_red,
                     cc1101_int,
+                    cc1101_fifo_int,
                     cc1101_wrp,
                 },
             )
         }
 
-        #[allow(unused_variables, unused_mut)]
-        #[task(priority = 1, shared = [serial])]
+        #[task(priority = 1, shared = [serial, cc1101_power_mode])]
         async fn task_10ms(mut ctx: task_10ms::Context) {
             loop {
                 let mut instant = Systick::now();
                 instant += 10.millis();
 
+                // Lock shared "serial" resource. Use it in the critical section
+                let command = ctx
+                    .shared
+                    .serial
+                    .lock(|serial| serial.read().ok())
+                    .and_then(cc1101_power_mode_command);
+                if let Some(mode) = command {
+                    // Lock shared "cc1101_power_mode" resource. Use it in the critical section
+                    ctx.shared.cc1101_power_mode.lock(|power_mode| {
+                        *power_mode = mode;
+                    });
+                }
+
                 #[cfg(feature = "task_10ms")]
                 let _task_10ms = {
                     // Lock shared "serial" resource. Use it in the critical section
@@ -167,16 +212,29 @@ mod nucleo_f767zi_board {
             }
         }
 
-        #[task(priority = 2, local = [cc1101_wrp], shared = [button_int_signal, cc1101_int_signal, serial])]
+        #[task(priority = 2, local = [cc1101_wrp], shared = [button_int_signal, serial, cc1101_power_mode])]
         async fn task_rf_com(mut ctx: task_rf_com::Context) {
-            ctx.local.cc1101_wrp.init_config().unwrap();
+            let Some(cc1101_wrp) = ctx.local.cc1101_wrp.as_mut() else {
+                // Lock shared "serial" resource. Use it in the critical section
+                ctx.shared.serial.lock(|serial| {
+                    serial.formatln(format_args!("[task_rf_com] CC1101 not found"));
+                });
+                return;
+            };
+
+            cc1101_wrp.set_gdo_events(&CC1101_EVENTS);
+            if let Err(error) = cc1101_wrp.init_config() {
+                // Lock shared "serial" resource. Use it in the critical section
+                ctx.shared.serial.lock(|serial| {
+                    serial.formatln(format_args!("[task_rf_com] CC1101 degraded: {:?}", error));
+                });
+            }
 
             Systick::delay(100.millis().into()).await;
 
             loop {
                 let _task_rf_com = {
                     let mut button_int_flag = false;
-                    let mut cc1101_int_flag = false;
                     let mut data_rx: [u8; PACKET_LENGTH as usize] = [0; PACKET_LENGTH as usize];
                     let mut data_tx: [u8; PACKET_LENGTH as usize] = [0; PACKET_LENGTH as usize];
                     let mut rssi: i16 = 0;
@@ -195,31 +253,31 @@ mod nucleo_f767zi_board {
                         *signal = false;
                     });
 
-                    // Lock shared "cc1101_int_signal" resource. Use it in the critical section
-                    ctx.shared.cc1101_int_signal.lock(|signal| {
-                        cc1101_int_flag = *signal;
-                        *signal = false;
-                    });
+                    // Apply the commanded power mode, taking effect at the next RF processing
+                    let power_mode = ctx.shared.cc1101_power_mode.lock(|mode| *mode);
+                    if power_mode != cc1101_wrp.radio_power_mode() {
+                        cc1101_wrp.set_radio_power_mode(power_mode);
 
-                    // Test Code: Generate Tx data
-                    if button_int_flag {
-                        let _ = ctx
-                            .local
-                            .cc1101_wrp
-                            .write_data(&data_tx[0..(PACKET_LENGTH as usize)]);
+                        // Lock shared "serial" resource. Use it in the critical section
+                        ctx.shared.serial.lock(|serial| {
+                            serial.formatln(format_args!(
+                                "[task_rf_com] CC1101 power mode: {:?}",
+                                power_mode
+                            ));
+                        });
                     }
 
-                    // Handle Rx interrupt for CC1101
-                    if cc1101_int_flag {
-                        ctx.local.cc1101_wrp.signal_rx_int();
+                    // Test Code: Generate Tx data
+                    if button_int_flag {
+                        let _ = cc1101_wrp
+                            .write_data(BROADCAST_ADDRESS, &data_tx[0..(PACKET_LENGTH as usize)]);
                     }
 
                     // Process RF
-                    ctx.local.cc1101_wrp.main().await;
+                    cc1101_wrp.main().await;
 
-                    if ctx.local.cc1101_wrp.is_data_received() {
-                        ctx.local
-                            .cc1101_wrp
+                    if cc1101_wrp.is_data_received() {
+                        let (length, source) = cc1101_wrp
                             .read_data(&mut data_rx, &mut rssi, &mut lqi)
                             .unwrap();
 
@@ -227,17 +285,18 @@ mod nucleo_f767zi_board {
                         // Lock shared "serial" resource. Use it in the critical section
                         ctx.shared.serial.lock(|serial| {
                             serial.formatln(format_args!(
-                                "[task_rf_com] Rx (len: {}, rssi: {}, lqi: {}): {:02X?}",
-                                PACKET_LENGTH,
+                                "[task_rf_com] Rx (len: {}, src: {:02X}, rssi: {}, lqi: {}): {:02X?}",
+                                length,
+                                source,
                                 rssi,
                                 lqi,
-                                &data_rx[0..(PACKET_LENGTH as usize)]
+                                &data_rx[0..length]
                             ));
                         });
                     }
 
                     // Test Code: Consume last error
-                    let (error_option, error_count) = ctx.local.cc1101_wrp.read_last_error();
+                    let (error_option, error_count) = cc1101_wrp.read_last_error();
                     if let Some(error) = error_option {
                         // Lock shared "serial" resource. Use it in the critical section
                         ctx.shared.serial.lock(|serial| {
@@ -298,12 +357,10 @@ mod nucleo_f767zi_board {
             ctx.local.button.clear_interrupt_pending_bit();
         }
 
-        #[task(binds = EXTI2, local = [cc1101_int], shared=[cc1101_int_signal, serial])]
+        #[task(binds = EXTI2, local = [cc1101_int], shared=[serial])]
         fn cc1101_isr(mut ctx: cc1101_isr::Context) {
-            // Lock shared "cc1101_int_signal" resource. Use it in the critical section
-            ctx.shared.cc1101_int_signal.lock(|signal| {
-                *signal = true;
-            });
+            // Signal the end of packet (falling edge of GDO2) to the CC1101 Wrapper
+            CC1101_EVENTS.signal(Gdo::Gdo2);
 
             // Lock shared "serial" resource. Use it in the critical section
             ctx.shared.serial.lock(|serial| {
@@ -316,6 +373,16 @@ mod nucleo_f767zi_board {
             // Obtain access to CC1101 Interrupt Pin and Clear Interrupt Pending Flag
             ctx.local.cc1101_int.clear_interrupt_pending_bit();
         }
+
+        #[task(binds = EXTI3, local = [cc1101_fifo_int])]
+        fn cc1101_fifo_isr(ctx: cc1101_fifo_isr::Context) {
+            // Signal the FIFO threshold (rising edge of GDO0) to the CC1101 Wrapper. Raised
+            // several times per packet while streaming, hence not traced on the serial
+            CC1101_EVENTS.signal(Gdo::Gdo0);
+
+            // Obtain access to CC1101 Interrupt Pin and Clear Interrupt Pending Flag
+            ctx.local.cc1101_fifo_int.clear_interrupt_pending_bit();
+        }
     }
 }
 
@@ -0,0 +1,370 @@
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+
+//! Interrupt driven serial implementing the `embedded-io-async` traits.
+//!
+//! The bytes are exchanged with the UART interrupt through `SerialBuffers`, placed in a
+//! `static`. The boards only provide the UART access, by implementing `InterruptUart`, and
+//! call `SerialBuffers::on_interrupt` from the UART interrupt handler.
+
+use core::cell::RefCell;
+use core::future::poll_fn;
+use core::task::{Poll, Waker};
+use critical_section::Mutex;
+use heapless::Deque;
+
+/// Size of the receive and transmit buffers
+pub const SERIAL_BUFFER_LEN: usize = 256;
+
+/// A byte was received while the receive buffer was full, and was lost.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct BufferOverrun;
+
+/// UART access needed by the interrupt handler.
+pub trait InterruptUart {
+    type Error;
+
+    /// Read a received byte.
+    fn read(&mut self) -> nb::Result<u8, Self::Error>;
+
+    /// Hand a byte over to the UART for transmission.
+    fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error>;
+
+    /// Enable or disable the interrupt raised when the UART can accept a new byte.
+    fn listen_tx(&mut self, enable: bool);
+}
+
+struct BufferState<E> {
+    bytes: Deque<u8, SERIAL_BUFFER_LEN>,
+    waker: Option<Waker>,
+    error: Option<E>,
+}
+
+/// Byte buffer shared between the UART interrupt and the async serial.
+struct SerialBuffer<E> {
+    state: Mutex<RefCell<BufferState<E>>>,
+}
+
+impl<E> SerialBuffer<E> {
+    const fn new() -> Self {
+        Self {
+            state: Mutex::new(RefCell::new(BufferState {
+                bytes: Deque::new(),
+                waker: None,
+                error: None,
+            })),
+        }
+    }
+
+    fn with<R>(&self, f: impl FnOnce(&mut BufferState<E>) -> R) -> R {
+        critical_section::with(|cs| f(&mut self.state.borrow_ref_mut(cs)))
+    }
+
+    fn wake(state: &mut BufferState<E>) {
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Receive and transmit buffers of an async serial, to be placed in a `static`.
+pub struct SerialBuffers<E> {
+    rx: SerialBuffer<E>,
+    tx: SerialBuffer<E>,
+}
+
+impl<E> SerialBuffers<E> {
+    pub const fn new() -> Self {
+        Self {
+            rx: SerialBuffer::new(),
+            tx: SerialBuffer::new(),
+        }
+    }
+
+    /// Move the received bytes into the receive buffer and the bytes to send into the UART.
+    pub fn on_interrupt<U>(&self, uart: &mut U)
+    where
+        U: InterruptUart<Error = E>,
+        E: From<BufferOverrun>,
+    {
+        self.rx.with(|rx| {
+            loop {
+                match uart.read() {
+                    Ok(byte) => {
+                        if rx.bytes.push_back(byte).is_err() {
+                            rx.error = Some(BufferOverrun.into());
+                        }
+                    }
+                    Err(nb::Error::WouldBlock) => break,
+                    Err(nb::Error::Other(error)) => {
+                        rx.error = Some(error);
+                        break;
+                    }
+                }
+            }
+            if !rx.bytes.is_empty() || rx.error.is_some() {
+                SerialBuffer::wake(rx);
+            }
+        });
+
+        self.tx.with(|tx| {
+            while let Some(&byte) = tx.bytes.front() {
+                match uart.write(byte) {
+                    Ok(()) => {
+                        tx.bytes.pop_front();
+                    }
+                    Err(_) => break,
+                }
+            }
+
+            // Wait for the UART to accept more bytes, or stop once everything was sent
+            if tx.bytes.is_empty() {
+                uart.listen_tx(false);
+                SerialBuffer::wake(tx);
+            } else {
+                uart.listen_tx(true);
+            }
+        });
+    }
+}
+
+impl<E> Default for SerialBuffers<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Interrupt driven serial, implementing the `embedded-io-async` traits. Writing pends the
+/// UART interrupt `interrupt` through `pend`, so that its handler starts the transmission.
+pub struct AsyncSerial<E: 'static, I> {
+    buffers: &'static SerialBuffers<E>,
+    interrupt: I,
+    pend: fn(I),
+}
+
+impl<E, I: Copy> AsyncSerial<E, I> {
+    pub fn new(buffers: &'static SerialBuffers<E>, interrupt: I, pend: fn(I)) -> Self {
+        Self {
+            buffers,
+            interrupt,
+            pend,
+        }
+    }
+}
+
+impl<E: embedded_io_async::Error, I> embedded_io_async::ErrorType for AsyncSerial<E, I> {
+    type Error = E;
+}
+
+impl<E: embedded_io_async::Error, I: Copy> embedded_io_async::Read for AsyncSerial<E, I> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        poll_fn(|cx| {
+            self.buffers.rx.with(|rx| {
+                if let Some(error) = rx.error.take() {
+                    return Poll::Ready(Err(error));
+                }
+                if rx.bytes.is_empty() && !buf.is_empty() {
+                    rx.waker = Some(cx.waker().clone());
+                    return Poll::Pending;
+                }
+
+                let mut len = 0;
+                while len < buf.len() {
+                    match rx.bytes.pop_front() {
+                        Some(byte) => buf[len] = byte,
+                        None => break,
+                    }
+                    len += 1;
+                }
+                Poll::Ready(Ok(len))
+            })
+        })
+        .await
+    }
+}
+
+impl<E: embedded_io_async::Error, I: Copy> embedded_io_async::Write for AsyncSerial<E, I> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let len = poll_fn(|cx| {
+            self.buffers.tx.with(|tx| {
+                let mut len = 0;
+                for &byte in buf {
+                    if tx.bytes.push_back(byte).is_err() {
+                        break;
+                    }
+                    len += 1;
+                }
+
+                if len == 0 && !buf.is_empty() {
+                    tx.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                } else {
+                    Poll::Ready(len)
+                }
+            })
+        })
+        .await;
+
+        // Let the interrupt handler start the transmission
+        (self.pend)(self.interrupt);
+
+        Ok(len)
+    }
+
+    /// Wait until all the buffered bytes were handed over to the UART.
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        poll_fn(|cx| {
+            self.buffers.tx.with(|tx| {
+                if tx.bytes.is_empty() {
+                    Poll::Ready(Ok(()))
+                } else {
+                    tx.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            })
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_io_async::{ErrorKind, Read, Write};
+    use futures_executor::block_on;
+    use std::collections::VecDeque;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+    enum TestError {
+        Framing,
+        Overrun,
+    }
+
+    impl From<BufferOverrun> for TestError {
+        fn from(_: BufferOverrun) -> Self {
+            TestError::Overrun
+        }
+    }
+
+    impl embedded_io_async::Error for TestError {
+        fn kind(&self) -> ErrorKind {
+            ErrorKind::Other
+        }
+    }
+
+    /// UART receiving `input` and accepting at most `tx_room` bytes per interrupt.
+    #[derive(Default)]
+    struct MockUart {
+        input: VecDeque<Result<u8, TestError>>,
+        output: Vec<u8>,
+        tx_room: usize,
+        tx_listening: bool,
+    }
+
+    impl InterruptUart for MockUart {
+        type Error = TestError;
+
+        fn read(&mut self) -> nb::Result<u8, Self::Error> {
+            match self.input.pop_front() {
+                Some(result) => result.map_err(nb::Error::Other),
+                None => Err(nb::Error::WouldBlock),
+            }
+        }
+
+        fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
+            if self.tx_room == 0 {
+                return Err(nb::Error::WouldBlock);
+            }
+            self.tx_room -= 1;
+            self.output.push(byte);
+            Ok(())
+        }
+
+        fn listen_tx(&mut self, enable: bool) {
+            self.tx_listening = enable;
+        }
+    }
+
+    // Number of times the interrupt was pended, only written to by one test
+    static PENDED: AtomicUsize = AtomicUsize::new(0);
+
+    fn serial(buffers: &'static SerialBuffers<TestError>) -> AsyncSerial<TestError, ()> {
+        AsyncSerial::new(buffers, (), |()| {
+            PENDED.fetch_add(1, Ordering::Relaxed);
+        })
+    }
+
+    #[test]
+    fn received_bytes_are_read() {
+        static BUFFERS: SerialBuffers<TestError> = SerialBuffers::new();
+        let mut serial = serial(&BUFFERS);
+        let mut uart = MockUart {
+            input: [1, 2, 3].into_iter().map(Ok).collect(),
+            ..Default::default()
+        };
+
+        BUFFERS.on_interrupt(&mut uart);
+
+        let mut buf = [0; 2];
+        assert_eq!(block_on(serial.read(&mut buf)), Ok(2));
+        assert_eq!(buf, [1, 2]);
+        assert_eq!(block_on(serial.read(&mut buf)), Ok(1));
+        assert_eq!(buf[0], 3);
+    }
+
+    #[test]
+    fn receive_errors_are_reported_once() {
+        static BUFFERS: SerialBuffers<TestError> = SerialBuffers::new();
+        let mut serial = serial(&BUFFERS);
+        let mut uart = MockUart {
+            input: [Ok(1), Err(TestError::Framing)].into_iter().collect(),
+            ..Default::default()
+        };
+
+        BUFFERS.on_interrupt(&mut uart);
+
+        let mut buf = [0; 4];
+        assert_eq!(block_on(serial.read(&mut buf)), Err(TestError::Framing));
+        assert_eq!(block_on(serial.read(&mut buf)), Ok(1));
+    }
+
+    #[test]
+    fn full_receive_buffer_reports_an_overrun() {
+        static BUFFERS: SerialBuffers<TestError> = SerialBuffers::new();
+        let mut serial = serial(&BUFFERS);
+        let mut uart = MockUart {
+            input: (0..=SERIAL_BUFFER_LEN).map(|i| Ok(i as u8)).collect(),
+            ..Default::default()
+        };
+
+        BUFFERS.on_interrupt(&mut uart);
+
+        let mut buf = [0; SERIAL_BUFFER_LEN];
+        assert_eq!(block_on(serial.read(&mut buf)), Err(TestError::Overrun));
+        assert_eq!(block_on(serial.read(&mut buf)), Ok(SERIAL_BUFFER_LEN));
+    }
+
+    #[test]
+    fn written_bytes_are_sent_from_the_interrupt() {
+        static BUFFERS: SerialBuffers<TestError> = SerialBuffers::new();
+        let mut serial = serial(&BUFFERS);
+        let mut uart = MockUart {
+            tx_room: 2,
+            ..Default::default()
+        };
+
+        assert_eq!(block_on(serial.write(&[1, 2, 3])), Ok(3));
+        assert_eq!(PENDED.load(Ordering::Relaxed), 1);
+
+        // The UART takes two bytes, the interrupt waits for room for the last one
+        BUFFERS.on_interrupt(&mut uart);
+        assert_eq!(uart.output, [1, 2]);
+        assert!(uart.tx_listening);
+
+        uart.tx_room = 2;
+        BUFFERS.on_interrupt(&mut uart);
+        assert_eq!(uart.output, [1, 2, 3]);
+        assert!(!uart.tx_listening);
+        assert_eq!(block_on(serial.flush()), Ok(()));
+    }
+}
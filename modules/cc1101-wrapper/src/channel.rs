@@ -0,0 +1,156 @@
+use crate::clock::Timestamp;
+
+/// Shortest time spent on a channel while hopping [ms]
+pub const MIN_DWELL_MS: u32 = 10;
+
+// Mixed with the seed for each cycle of the hopping sequence
+const CYCLE_KEY: u32 = 0x9E37_79B9;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ChannelError {
+    /// Channel is not part of the channel plan of the radio profile
+    OutOfPlan,
+    /// Dwell time is shorter than `MIN_DWELL_MS`
+    DwellTooShort,
+}
+
+/// Channel hopping configuration, shared by both ends of the link
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct HoppingConfig {
+    /// Seed of the hopping sequence
+    pub seed: u32,
+    /// Time spent on each channel [ms]
+    pub dwell_ms: u32,
+    /// Start of the first slot of the sequence, on the clock of the wrapper
+    pub epoch: Timestamp,
+}
+
+impl HoppingConfig {
+    /// Check the configuration against the wrapper capabilities.
+    pub fn validate(&self) -> Result<(), ChannelError> {
+        if self.dwell_ms < MIN_DWELL_MS {
+            return Err(ChannelError::DwellTooShort);
+        }
+
+        Ok(())
+    }
+
+    /// Slot of the sequence at `now`, none before the epoch
+    pub(crate) fn slot_at(&self, now: Timestamp) -> Option<u32> {
+        if now < self.epoch {
+            return None;
+        }
+
+        Some(((now - self.epoch).to_millis() / self.dwell_ms as u64) as u32)
+    }
+}
+
+fn xorshift32(mut x: u32) -> u32 {
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    x
+}
+
+/// Channel of a slot of the hopping sequence over `channels` channels. The sequence is made
+/// of cycles visiting every channel once, in an order shuffled by Fisher-Yates with a
+/// xorshift32 generator seeded by `seed ^ (cycle * 0x9E3779B9)` (1 instead of 0), so that
+/// the other end of the link can compute the same sequence.
+pub fn hop_channel(seed: u32, slot: u32, channels: u8) -> u8 {
+    if channels <= 1 {
+        return 0;
+    }
+
+    let count = channels as usize;
+    let cycle = slot / channels as u32;
+    let mut order = [0u8; 256];
+    for (index, channel) in order.iter_mut().enumerate().take(count) {
+        *channel = index as u8;
+    }
+
+    let mut x = (seed ^ cycle.wrapping_mul(CYCLE_KEY)).max(1);
+    for last in (1..count).rev() {
+        x = xorshift32(x);
+        order.swap(last, x as usize % (last + 1));
+    }
+
+    order[(slot % channels as u32) as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hopping(epoch: u64) -> HoppingConfig {
+        HoppingConfig {
+            seed: 0x1234_5678,
+            dwell_ms: 100,
+            epoch: Timestamp::from_ticks(epoch),
+        }
+    }
+
+    #[test]
+    fn dwell_time_is_checked() {
+        assert_eq!(hopping(0).validate(), Ok(()));
+        let config = HoppingConfig {
+            dwell_ms: MIN_DWELL_MS - 1,
+            ..hopping(0)
+        };
+        assert_eq!(config.validate(), Err(ChannelError::DwellTooShort));
+    }
+
+    #[test]
+    fn slots_start_at_the_epoch() {
+        let config = hopping(1_000);
+        let slot_at = |time| config.slot_at(Timestamp::from_ticks(time));
+        assert_eq!(slot_at(999), None);
+        assert_eq!(slot_at(1_000), Some(0));
+        assert_eq!(slot_at(1_099), Some(0));
+        assert_eq!(slot_at(1_100), Some(1));
+    }
+
+    #[test]
+    fn single_channel_does_not_hop() {
+        for slot in 0..10 {
+            assert_eq!(hop_channel(1, slot, 0), 0);
+            assert_eq!(hop_channel(1, slot, 1), 0);
+        }
+    }
+
+    #[test]
+    fn each_cycle_visits_every_channel_once() {
+        for channels in [2, 5, 16, 255] {
+            for cycle in 0..4 {
+                let mut visited = [false; 256];
+                for slot in cycle * channels as u32..(cycle + 1) * channels as u32 {
+                    let channel = hop_channel(0xCAFE, slot, channels);
+                    assert!(channel < channels);
+                    assert!(!visited[channel as usize]);
+                    visited[channel as usize] = true;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sequence_depends_on_the_seed_and_the_cycle() {
+        let cycle = |seed, cycle: u32| -> [u8; 16] {
+            core::array::from_fn(|slot| hop_channel(seed, cycle * 16 + slot as u32, 16))
+        };
+
+        // Computed the same way at both ends
+        assert_eq!(cycle(1, 0), cycle(1, 0));
+        assert_ne!(cycle(1, 0), cycle(2, 0));
+        assert_ne!(cycle(1, 0), cycle(1, 1));
+
+        // Zero seed of the generator replaced
+        assert_eq!(cycle(0, 0), cycle(1, 0));
+    }
+
+    #[test]
+    fn sequence_is_stable() {
+        // Reference sequence of the other end of the link
+        let sequence: [u8; 16] = core::array::from_fn(|slot| hop_channel(0xCAFE, slot as u32, 8));
+        assert_eq!(sequence, [4, 6, 7, 3, 0, 5, 2, 1, 5, 4, 1, 6, 2, 3, 7, 0]);
+    }
+}
@@ -0,0 +1,38 @@
+use embedded_hal_async::delay::DelayNs;
+use fugit::Instant;
+#[cfg(feature = "systick")]
+use rtic_monotonics::{systick::Systick, Monotonic};
+
+/// Millisecond timestamp of the wrapper clock
+pub type Timestamp = Instant<u64, 1, 1000>;
+
+/// Time source of the CC1101 Wrapper: a millisecond clock and an async delay.
+pub trait Clock: DelayNs {
+    /// Current time
+    fn now(&mut self) -> Timestamp;
+}
+
+/// Clock based on the RTIC Systick monotonic, which shall be started by the application.
+#[cfg(feature = "systick")]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SystickClock;
+
+#[cfg(feature = "systick")]
+impl DelayNs for SystickClock {
+    async fn delay_ns(&mut self, ns: u32) {
+        // Round up to the Systick resolution
+        let ms = (ns as u64).div_ceil(1_000_000);
+        Systick::delay(fugit::ExtU64::millis(ms)).await;
+    }
+
+    async fn delay_ms(&mut self, ms: u32) {
+        Systick::delay(fugit::ExtU64::millis(ms as u64)).await;
+    }
+}
+
+#[cfg(feature = "systick")]
+impl Clock for SystickClock {
+    fn now(&mut self) -> Timestamp {
+        Systick::now()
+    }
+}
@@ -0,0 +1,80 @@
+use atomic_waker::AtomicWaker;
+use core::future::{poll_fn, Future};
+use core::pin::pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::Poll;
+
+/// GDO pins of the CC1101 wired to interrupts
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Gdo {
+    /// RX FIFO above or TX FIFO below the threshold, or end of packet, rising edge
+    Gdo0,
+    /// End of the received or transmitted packet, falling edge
+    Gdo2,
+}
+
+/// Events of the GDO pins, signalled by the interrupt handlers and awaited by the wrapper.
+/// Intended to be a `static`, shared between the interrupt handlers and the RF task.
+pub struct GdoEvents {
+    pending: [AtomicBool; 2],
+    waker: AtomicWaker,
+}
+
+impl GdoEvents {
+    pub const fn new() -> Self {
+        Self {
+            pending: [AtomicBool::new(false), AtomicBool::new(false)],
+            waker: AtomicWaker::new(),
+        }
+    }
+
+    /// Signal an edge of the `gdo` pin. To be called from its interrupt handler.
+    pub fn signal(&self, gdo: Gdo) {
+        self.pending[gdo as usize].store(true, Ordering::Release);
+        self.waker.wake();
+    }
+
+    /// Take the pending event of the `gdo` pin, if any.
+    pub fn take(&self, gdo: Gdo) -> bool {
+        self.pending[gdo as usize].swap(false, Ordering::Acquire)
+    }
+
+    /// Wait for the next event of the `gdo` pin.
+    pub async fn wait(&self, gdo: Gdo) {
+        poll_fn(|cx| {
+            self.waker.register(cx.waker());
+            if self.take(gdo) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+impl Default for GdoEvents {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Wait for an event of the `gdos` pins until `timeout` completes. Returns false on timeout.
+pub(crate) async fn wait_until(events: &GdoEvents, gdos: &[Gdo], timeout: impl Future) -> bool {
+    let mut timeout = pin!(timeout);
+    // Take the events of all the pins
+    let take = || gdos.iter().filter(|&&gdo| events.take(gdo)).count() > 0;
+
+    poll_fn(|cx| {
+        events.waker.register(cx.waker());
+        if take() {
+            Poll::Ready(true)
+        } else if timeout.as_mut().poll(cx).is_ready() {
+            // The event may have been signalled while the timeout completed
+            Poll::Ready(take())
+        } else {
+            Poll::Pending
+        }
+    })
+    .await
+}
@@ -0,0 +1,303 @@
+use crate::clock::Timestamp;
+use heapless::Vec;
+
+// Crystal oscillator frequency [Hz]
+const FXOSC: i64 = 26_000_000;
+
+/// Number of points of the Doppler correction table
+pub const DOPPLER_TABLE_SIZE: usize = 32;
+
+/// Largest frequency offset compensated by FSCTRL0.FREQOFF, 127 steps of fXOSC/2^14 [Hz]
+pub const MAX_FREQ_OFFSET_HZ: u32 = 201_538;
+
+/// Largest Doppler shift of the correction table [Hz]
+pub const MAX_DOPPLER_SHIFT_HZ: u32 = 100_000;
+
+/// Smallest change of the Doppler correction retuning the carrier, each retuning
+/// recalibrates the frequency synthesizer [Hz]
+pub const DOPPLER_STEP_HZ: u32 = 1_000;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FrequencyError {
+    /// Largest offset is above `MAX_FREQ_OFFSET_HZ`
+    OffsetOutOfRange,
+    /// Tracking gain shift is above 7
+    InvalidGain,
+    /// Doppler correction table is longer than `DOPPLER_TABLE_SIZE`
+    TableTooLong,
+    /// Doppler correction table is not in chronological order
+    TableNotSorted,
+    /// Doppler shift is above `MAX_DOPPLER_SHIFT_HZ`
+    ShiftOutOfRange,
+}
+
+/// Frequency offset tracking configuration. After each received packet the frequency offset
+/// estimated by the demodulator (FREQEST) corrects the frequency offset of the transceiver
+/// (FSCTRL0), used both to receive and to transmit, compensating the crystal drift of both
+/// ends and the residual Doppler shift.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct AfcConfig {
+    /// Track the frequency offset of the received packets
+    pub enabled: bool,
+    /// Largest frequency offset compensated [Hz]
+    pub max_offset_hz: u32,
+    /// Each packet corrects 1/2^`gain_shift` of its estimated offset, from 0 to 7. Higher
+    /// values smooth the estimation noise out, at the cost of a slower tracking.
+    pub gain_shift: u8,
+}
+
+impl AfcConfig {
+    /// Keep the frequency offset of the transceiver at zero
+    pub const DISABLED: Self = Self {
+        enabled: false,
+        max_offset_hz: 0,
+        gain_shift: 0,
+    };
+
+    /// Project default: track up to 50 kHz, half of the offset at each packet. This covers
+    /// the Doppler shift at 433 MHz in LEO (+/-10 kHz) and the crystal tolerances.
+    pub const PROJECT: Self = Self {
+        enabled: true,
+        max_offset_hz: 50_000,
+        gain_shift: 1,
+    };
+
+    /// Check the configuration against the CC1101 capabilities.
+    pub fn validate(&self) -> Result<(), FrequencyError> {
+        if self.max_offset_hz > MAX_FREQ_OFFSET_HZ {
+            return Err(FrequencyError::OffsetOutOfRange);
+        }
+        if self.gain_shift > 7 {
+            return Err(FrequencyError::InvalidGain);
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for AfcConfig {
+    fn default() -> Self {
+        Self::PROJECT
+    }
+}
+
+/// Point of the Doppler correction table
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct DopplerPoint {
+    /// Time of the point, on the clock of the wrapper
+    pub time: Timestamp,
+    /// Doppler shift of the link [Hz], positive while the distance decreases
+    pub shift_hz: i32,
+}
+
+/// Check a Doppler correction table: chronological points with a supported shift.
+pub fn validate_doppler_table(table: &[DopplerPoint]) -> Result<(), FrequencyError> {
+    if table.len() > DOPPLER_TABLE_SIZE {
+        return Err(FrequencyError::TableTooLong);
+    }
+    if table.windows(2).any(|pair| pair[0].time >= pair[1].time) {
+        return Err(FrequencyError::TableNotSorted);
+    }
+    if table
+        .iter()
+        .any(|point| point.shift_hz.unsigned_abs() > MAX_DOPPLER_SHIFT_HZ)
+    {
+        return Err(FrequencyError::ShiftOutOfRange);
+    }
+
+    Ok(())
+}
+
+/// Tracks the frequency offset of the wrapper and interpolates the Doppler correction
+#[derive(Default)]
+pub(crate) struct FrequencyTracker {
+    pub config: AfcConfig,
+    offset_hz: i32,
+    table: Vec<DopplerPoint, DOPPLER_TABLE_SIZE>,
+}
+
+impl FrequencyTracker {
+    /// Frequency offset currently compensated [Hz]
+    pub fn offset_hz(&self) -> i32 {
+        self.offset_hz
+    }
+
+    /// FSCTRL0.FREQOFF value of the offset currently compensated
+    pub fn freqoff(&self) -> i8 {
+        let steps = ((self.offset_hz as i64) << 14) / FXOSC;
+        steps.clamp(-127, 127) as i8
+    }
+
+    /// Correct the offset with the estimation of a received packet (FREQEST). Returns
+    /// whether FREQOFF changed.
+    pub fn update(&mut self, freqest: i8) -> bool {
+        if !self.config.enabled {
+            return false;
+        }
+
+        let previous = self.freqoff();
+        let estimate_hz = ((freqest as i64 * FXOSC) >> 14) as i32;
+        let limit = self.config.max_offset_hz as i32;
+        self.offset_hz =
+            (self.offset_hz + (estimate_hz >> self.config.gain_shift)).clamp(-limit, limit);
+
+        self.freqoff() != previous
+    }
+
+    /// Forget the offset, e.g. on a new configuration.
+    pub fn reset(&mut self) {
+        self.offset_hz = 0;
+    }
+
+    pub fn set_table(&mut self, table: &[DopplerPoint]) {
+        self.table.clear();
+        let _ = self.table.extend_from_slice(table);
+    }
+
+    /// Doppler shift at `now` [Hz], interpolated between the points of the table. Outside of
+    /// the table there is no correction.
+    pub fn doppler_at(&self, now: Timestamp) -> i32 {
+        let Some(next) = self.table.iter().position(|point| point.time > now) else {
+            // Past the last point, or no table
+            return match self.table.last() {
+                Some(last) if last.time == now => last.shift_hz,
+                _ => 0,
+            };
+        };
+        if next == 0 {
+            return 0;
+        }
+
+        let (start, end) = (self.table[next - 1], self.table[next]);
+        let elapsed = (now - start.time).to_millis() as i64;
+        let span = (end.time - start.time).to_millis() as i64;
+        let delta = (end.shift_hz - start.shift_hz) as i64;
+
+        start.shift_hz + (delta * elapsed / span) as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(time: u64, shift_hz: i32) -> DopplerPoint {
+        DopplerPoint {
+            time: Timestamp::from_ticks(time),
+            shift_hz,
+        }
+    }
+
+    fn tracker(config: AfcConfig) -> FrequencyTracker {
+        FrequencyTracker {
+            config,
+            ..FrequencyTracker::default()
+        }
+    }
+
+    #[test]
+    fn afc_configuration_is_checked() {
+        assert_eq!(AfcConfig::PROJECT.validate(), Ok(()));
+        assert_eq!(AfcConfig::DISABLED.validate(), Ok(()));
+
+        let config = AfcConfig {
+            max_offset_hz: MAX_FREQ_OFFSET_HZ + 1,
+            ..AfcConfig::PROJECT
+        };
+        assert_eq!(config.validate(), Err(FrequencyError::OffsetOutOfRange));
+
+        let config = AfcConfig {
+            gain_shift: 8,
+            ..AfcConfig::PROJECT
+        };
+        assert_eq!(config.validate(), Err(FrequencyError::InvalidGain));
+    }
+
+    #[test]
+    fn offset_follows_a_part_of_the_estimation() {
+        let mut tracker = tracker(AfcConfig::PROJECT);
+
+        // 10 steps of 1586.9 Hz, half of it corrected
+        assert!(tracker.update(10));
+        assert_eq!(tracker.offset_hz(), 7_934);
+        assert_eq!(tracker.freqoff(), 4);
+
+        // Too small to change FREQOFF
+        assert!(!tracker.update(-1));
+        assert_eq!(tracker.offset_hz(), 7_140);
+        assert_eq!(tracker.freqoff(), 4);
+
+        tracker.reset();
+        assert_eq!(tracker.offset_hz(), 0);
+        assert_eq!(tracker.freqoff(), 0);
+    }
+
+    #[test]
+    fn offset_is_limited() {
+        let config = AfcConfig {
+            gain_shift: 0,
+            ..AfcConfig::PROJECT
+        };
+        let mut tracker = tracker(config);
+
+        assert!(tracker.update(i8::MAX));
+        assert_eq!(tracker.offset_hz(), 50_000);
+        assert!(tracker.update(-128));
+        assert!(!tracker.update(-128));
+        assert_eq!(tracker.offset_hz(), -50_000);
+        assert_eq!(tracker.freqoff(), -31);
+    }
+
+    #[test]
+    fn disabled_tracking_keeps_the_offset() {
+        let mut tracker = tracker(AfcConfig::DISABLED);
+        assert!(!tracker.update(50));
+        assert_eq!(tracker.offset_hz(), 0);
+    }
+
+    #[test]
+    fn doppler_table_is_checked() {
+        assert_eq!(validate_doppler_table(&[]), Ok(()));
+        assert_eq!(
+            validate_doppler_table(&[point(0, -100_000), point(1, 100_000)]),
+            Ok(())
+        );
+
+        let table = [point(0, 0); DOPPLER_TABLE_SIZE + 1];
+        assert_eq!(
+            validate_doppler_table(&table),
+            Err(FrequencyError::TableTooLong)
+        );
+        assert_eq!(
+            validate_doppler_table(&[point(1, 0), point(1, 0)]),
+            Err(FrequencyError::TableNotSorted)
+        );
+        assert_eq!(
+            validate_doppler_table(&[point(2, 0), point(1, 0)]),
+            Err(FrequencyError::TableNotSorted)
+        );
+        assert_eq!(
+            validate_doppler_table(&[point(0, 100_001)]),
+            Err(FrequencyError::ShiftOutOfRange)
+        );
+    }
+
+    #[test]
+    fn doppler_is_interpolated_between_the_points() {
+        let mut tracker = tracker(AfcConfig::PROJECT);
+        let at = |tracker: &FrequencyTracker, time| tracker.doppler_at(Timestamp::from_ticks(time));
+        assert_eq!(at(&tracker, 0), 0);
+
+        tracker.set_table(&[point(1_000, 1_000), point(3_000, -3_000), point(4_000, 0)]);
+        assert_eq!(at(&tracker, 1_000), 1_000);
+        assert_eq!(at(&tracker, 2_000), -1_000);
+        assert_eq!(at(&tracker, 2_999), -2_998);
+        assert_eq!(at(&tracker, 3_000), -3_000);
+        assert_eq!(at(&tracker, 3_500), -1_500);
+
+        // No correction outside of the table
+        assert_eq!(at(&tracker, 999), 0);
+        assert_eq!(at(&tracker, 4_000), 0);
+        assert_eq!(at(&tracker, 4_001), 0);
+    }
+}
@@ -0,0 +1,70 @@
+use cc1101::CcaMode;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum LbtError {
+    /// Carrier sense threshold is outside of -7..=7 dB
+    ThresholdOutOfRange,
+    /// Minimum back-off is longer than the maximum back-off
+    InvalidBackoff,
+}
+
+/// Listen Before Talk configuration. Before each transmission the transceiver listens in
+/// Rx state and only starts transmitting if the channel is clear, as assessed by the
+/// `cca_mode`; otherwise the transmission is retried after a random back-off.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct LbtConfig {
+    /// Clear channel assessment. `CcaMode::CciAlways` disables Listen Before Talk.
+    pub cca_mode: CcaMode,
+    /// Carrier sense threshold [dB], relative to the AGC target amplitude
+    pub carrier_sense_threshold: i8,
+    /// Number of retries after the first attempt found the channel busy
+    pub max_retries: u8,
+    /// Shortest back-off between two attempts [ms]
+    pub backoff_min_ms: u32,
+    /// Longest back-off between two attempts [ms]
+    pub backoff_max_ms: u32,
+}
+
+impl LbtConfig {
+    /// Transmit regardless of the channel occupancy
+    pub const DISABLED: Self = Self {
+        cca_mode: CcaMode::CciAlways,
+        carrier_sense_threshold: 0,
+        max_retries: 0,
+        backoff_min_ms: 0,
+        backoff_max_ms: 0,
+    };
+
+    /// Project default: transmit if the RSSI is below the threshold and no packet is
+    /// being received, retrying up to 4 times within 2-20 ms.
+    pub const PROJECT: Self = Self {
+        cca_mode: CcaMode::RssiBelowThresholdUnlessReceiving,
+        carrier_sense_threshold: 0,
+        max_retries: 4,
+        backoff_min_ms: 2,
+        backoff_max_ms: 20,
+    };
+
+    /// Check the configuration against the CC1101 capabilities.
+    pub fn validate(&self) -> Result<(), LbtError> {
+        if !(-7..=7).contains(&self.carrier_sense_threshold) {
+            return Err(LbtError::ThresholdOutOfRange);
+        }
+        if self.backoff_min_ms > self.backoff_max_ms {
+            return Err(LbtError::InvalidBackoff);
+        }
+
+        Ok(())
+    }
+
+    /// Whether the channel is assessed before transmitting
+    pub fn enabled(&self) -> bool {
+        self.cca_mode != CcaMode::CciAlways
+    }
+}
+
+impl Default for LbtConfig {
+    fn default() -> Self {
+        Self::PROJECT
+    }
+}
@@ -1,20 +1,113 @@
 #![no_std]
 
+#[cfg(feature = "sim")]
+extern crate std;
+
 pub use cc1101::{
     AddressFilter, AutoCalibration, Cc1101, CcaMode, Error, GdoCfg, MachineState, ModulationFormat,
     NumPreamble, PacketLength, RadioMode, SyncMode, UserError, FIFO_SIZE_MAX,
 };
+use core::ops::Range;
 use embedded_hal::{digital::PinState, spi::SpiDevice};
-use fugit::{Duration, Instant};
-use rtic_monotonics::{systick::Systick, Monotonic};
+use fugit::Duration;
+use heapless::{binary_heap::Max, BinaryHeap, Deque};
+
+/// Time Source
+pub mod clock;
+#[cfg(feature = "systick")]
+pub use clock::SystickClock;
+pub use clock::{Clock, Timestamp};
+
+/// GDO Interrupt Events
+pub mod events;
+use events::wait_until;
+pub use events::{Gdo, GdoEvents};
+
+/// Listen Before Talk
+pub mod lbt;
+pub use lbt::{LbtConfig, LbtError};
+
+/// TX Output Power
+pub mod power;
+pub use power::{PowerConfig, PowerError, POWER_LEVELS_DBM};
+
+/// Frequency Offset Compensation
+pub mod frequency;
+use frequency::{validate_doppler_table, FrequencyTracker};
+pub use frequency::{
+    AfcConfig, DopplerPoint, FrequencyError, DOPPLER_STEP_HZ, DOPPLER_TABLE_SIZE,
+    MAX_DOPPLER_SHIFT_HZ, MAX_FREQ_OFFSET_HZ,
+};
+
+/// Channels and Hopping
+pub mod channel;
+pub use channel::{hop_channel, ChannelError, HoppingConfig, MIN_DWELL_MS};
+
+/// Chip Presence Detection
+pub mod probe;
+use probe::check_hw_info;
+pub use probe::probe;
+
+/// Radio Profiles
+pub mod profile;
+pub use profile::{ProfileError, RadioProfile};
+
+/// Fault Recovery
+pub mod recovery;
+use recovery::Escalation;
+pub use recovery::{
+    RecoveryAction, RecoveryConfig, RecoveryError, RecoveryRecord, RECOVERY_HISTORY_DEPTH,
+};
+
+/// Transmit and Receive Queues
+pub mod queue;
+use queue::{RxPacket, TxPacket};
+pub use queue::{
+    TxCompletion, TxId, TxPriority, TxResult, MAX_PACKET_LENGTH, RX_QUEUE_DEPTH, TX_QUEUE_DEPTH,
+};
+
+/// Host-side CC1101 Simulator
+#[cfg(feature = "sim")]
+pub mod sim;
+
+/// Link Statistics
+pub mod stats;
+use stats::LinkMonitor;
+pub use stats::{LinkStats, SampleSummary, STATS_WINDOW};
+
+/// Wake-on-Radio and Sleep
+pub mod wor;
+use wor::TEST_REGISTERS;
+pub use wor::{RadioPowerMode, WorConfig, WorError};
 
 pub const PACKET_LENGTH: u8 = FIFO_SIZE_MAX;
 
-enum RxState {
-    Waiting,
-    Receiving,
-    Received,
-    Error,
+// Initial state of the back-off generator, mixed with the start-up time
+const BACKOFF_SEED: u32 = 0x9E37_79B9;
+
+// FIFOTHR value: GDO0 signals at least 32 bytes in the RX FIFO, or at most 32 bytes in the
+// TX FIFO
+const FIFO_THRESHOLD: u8 = 7;
+const FIFO_THRESHOLD_BYTES: usize = 32;
+
+// Longest preamble and sync word, and the CRC bytes, sent along the packet
+const PACKET_OVERHEAD: usize = 32;
+
+// PKTLEN register address
+const PKTLEN: u8 = 0x06;
+
+/// Broadcast address, accepted by the AddressFilter::DeviceLowBroadcast and
+/// AddressFilter::DeviceHighLowBroadcast filters
+pub const BROADCAST_ADDRESS: u8 = 0x00;
+/// Second broadcast address, accepted by the AddressFilter::DeviceHighLowBroadcast filter
+pub const BROADCAST_ADDRESS_HIGH: u8 = 0xFF;
+
+/// Packet read from the RX FIFO
+struct RxFrame {
+    length: usize,
+    source: u8,
+    rssi: i16,
+    lqi: u8,
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -23,10 +116,14 @@ pub enum Cc1101WrapperError {
     MonitoringError,
     /// Receive buffer is empty
     RxBufferEmpty,
-    /// Transmit buffer has unsent data
+    /// Transmit queue is full
     TxBufferBusy,
+    /// Receive queue is full, the received packet was dropped
+    RxQueueFull,
     /// Operation timeout
     TimeoutError,
+    /// Data length does not fit the configured packet length, or the provided buffer
+    InvalidLength,
     /// The TX FIFO buffer underflowed, too large packet for configured packet length.
     TxUnderflow,
     /// The RX FIFO buffer overflowed, too small buffer for configured packet length.
@@ -37,6 +134,28 @@ pub enum Cc1101WrapperError {
     InvalidState(u8),
     /// User Input Error
     UserInputError(UserError),
+    /// Radio profile is not supported by the transceiver
+    InvalidProfile(ProfileError),
+    /// Listen Before Talk configuration is not supported by the transceiver
+    InvalidLbtConfig(LbtError),
+    /// Output power configuration is not supported by the transceiver
+    InvalidPowerConfig(PowerError),
+    /// Wake-on-Radio configuration is not supported by the transceiver
+    InvalidWorConfig(WorError),
+    /// Fault recovery configuration is inconsistent
+    InvalidRecoveryConfig(RecoveryError),
+    /// Frequency offset tracking or Doppler correction is not supported by the transceiver
+    InvalidFrequencyConfig(FrequencyError),
+    /// Channel or hopping configuration does not fit the channel plan
+    InvalidChannel(ChannelError),
+    /// The channel was busy at every transmission attempt
+    ChannelBusy,
+    /// PARTNUM and VERSION do not identify a CC1101, the transceiver is missing or dead
+    UnknownChip(u8, u8),
+    /// Register readback differs from the written value
+    SpiIntegrity,
+    /// The transceiver is not operational, the wrapper runs in degraded mode
+    Degraded,
     /// Platform-dependent SPI-errors, such as IO errors.
     Spi,
 }
@@ -54,96 +173,419 @@ impl<SpiE> From<Error<SpiE>> for Cc1101WrapperError {
     }
 }
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
-enum Cc1101RxMode {
-    Polling,
-    Interrupt,
+impl From<ProfileError> for Cc1101WrapperError {
+    fn from(e: ProfileError) -> Self {
+        Cc1101WrapperError::InvalidProfile(e)
+    }
 }
 
-struct DataBuffer {
-    data: [u8; FIFO_SIZE_MAX as usize],
-    length: u8,
-    address: u8,
-    ready: bool,
+impl From<LbtError> for Cc1101WrapperError {
+    fn from(e: LbtError) -> Self {
+        Cc1101WrapperError::InvalidLbtConfig(e)
+    }
 }
 
-impl Default for DataBuffer {
-    fn default() -> Self {
-        Self {
-            data: [0; FIFO_SIZE_MAX as usize],
-            length: 0,
-            address: 0,
-            ready: false,
-        }
+impl From<PowerError> for Cc1101WrapperError {
+    fn from(e: PowerError) -> Self {
+        Cc1101WrapperError::InvalidPowerConfig(e)
+    }
+}
+
+impl From<WorError> for Cc1101WrapperError {
+    fn from(e: WorError) -> Self {
+        Cc1101WrapperError::InvalidWorConfig(e)
+    }
+}
+
+impl From<RecoveryError> for Cc1101WrapperError {
+    fn from(e: RecoveryError) -> Self {
+        Cc1101WrapperError::InvalidRecoveryConfig(e)
+    }
+}
+
+impl From<FrequencyError> for Cc1101WrapperError {
+    fn from(e: FrequencyError) -> Self {
+        Cc1101WrapperError::InvalidFrequencyConfig(e)
+    }
+}
+
+impl From<ChannelError> for Cc1101WrapperError {
+    fn from(e: ChannelError) -> Self {
+        Cc1101WrapperError::InvalidChannel(e)
     }
 }
 
-pub struct Cc1101Wrapper<SPI> {
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Cc1101RxMode {
+    Polling,
+    Interrupt,
+}
+
+/// CC1101 Wrapper, holding up to `TXQ` packets to be transmitted and `RXQ` received packets.
+pub struct Cc1101Wrapper<
+    SPI,
+    CLK,
+    const TXQ: usize = TX_QUEUE_DEPTH,
+    const RXQ: usize = RX_QUEUE_DEPTH,
+> {
     cc1101: Cc1101<SPI>,
+    clock: CLK,
+    profile: RadioProfile,
+    lbt: LbtConfig,
+    power: PowerConfig,
+    frequency: FrequencyTracker,
+    doppler_hz: i32,
+    tuned_hz: u64,
+    channel: u8,
+    hopping: Option<HoppingConfig>,
+    wor: WorConfig,
+    power_mode: RadioPowerMode,
+    asleep: bool,
+    sleep_backup: [u8; TEST_REGISTERS.len()],
+    backoff_seed: u32,
+    events: Option<&'static GdoEvents>,
     rx_mode: Cc1101RxMode,
     rx_init: bool,
     rx_int_pending: bool,
-    rx_data: DataBuffer,
-    tx_data: DataBuffer,
-    last_rx_rssi: i16,
-    last_rx_lqi: u8,
-    timestamp_monitor: Instant<u64, 1, 1000>,
+    rx_queue: Deque<RxPacket, RXQ>,
+    tx_queue: BinaryHeap<TxPacket, Max, TXQ>,
+    tx_completions: Deque<TxCompletion, TXQ>,
+    tx_next_id: TxId,
+    timestamp_monitor: Timestamp,
+    link: LinkMonitor,
+    recovery: Escalation,
+    degraded: bool,
     last_error: Option<Cc1101WrapperError>,
     error_count: u32,
 }
 
-impl<SPI, SpiE> Cc1101Wrapper<SPI>
+impl<SPI, SpiE, CLK, const TXQ: usize, const RXQ: usize> Cc1101Wrapper<SPI, CLK, TXQ, RXQ>
 where
     SPI: SpiDevice<u8, Error = SpiE>,
+    CLK: Clock,
 {
-    /// Instantiate the CC1101 Wrapper module and the underlying CC1101 driver.
-    pub fn new(spi: SPI) -> Self {
-        let cc1101 = Cc1101::new(spi);
-
-        match cc1101 {
-            Ok(cc1101) => Cc1101Wrapper {
-                cc1101,
-                rx_mode: Cc1101RxMode::Polling,
-                rx_init: false,
-                rx_int_pending: false,
-                rx_data: DataBuffer::default(),
-                tx_data: DataBuffer::default(),
-                last_rx_rssi: 0,
-                last_rx_lqi: 0,
-                timestamp_monitor: Systick::now(),
-                last_error: None,
-                error_count: 0,
-            },
-            Err(_error) => panic!("Error initializing CC1101"),
-        }
-    }
-
-    /// Initialize RF Transceiver's configuration specific to the project.
+    /// Instantiate the CC1101 Wrapper module and the underlying CC1101 driver. The `clock`
+    /// provides the timestamps, delays and timeouts of the RF operations.
+    ///
+    /// The transceiver is probed first, an error is returned if it is missing or if the
+    /// SPI communication is faulty.
+    pub fn new(mut spi: SPI, mut clock: CLK) -> Result<Self, Cc1101WrapperError> {
+        probe(&mut spi)?;
+        let cc1101 = Cc1101::new(spi)?;
+
+        let timestamp = clock.now();
+
+        Ok(Cc1101Wrapper {
+            cc1101,
+            timestamp_monitor: timestamp,
+            clock,
+            profile: RadioProfile::default(),
+            lbt: LbtConfig::default(),
+            power: PowerConfig::default(),
+            frequency: FrequencyTracker::default(),
+            doppler_hz: 0,
+            tuned_hz: 0,
+            channel: 0,
+            hopping: None,
+            wor: WorConfig::default(),
+            power_mode: RadioPowerMode::Continuous,
+            asleep: false,
+            sleep_backup: [0; TEST_REGISTERS.len()],
+            backoff_seed: (timestamp.ticks() as u32) ^ BACKOFF_SEED,
+            events: None,
+            rx_mode: Cc1101RxMode::Polling,
+            rx_init: false,
+            rx_int_pending: false,
+            rx_queue: Deque::new(),
+            tx_queue: BinaryHeap::new(),
+            tx_completions: Deque::new(),
+            tx_next_id: 0,
+            link: LinkMonitor::default(),
+            recovery: Escalation::default(),
+            degraded: false,
+            last_error: None,
+            error_count: 0,
+        })
+    }
+
+    /// Initialize RF Transceiver's configuration specific to the project, with the radio
+    /// profile last applied (the default profile at first). On failure the wrapper enters the
+    /// degraded mode, until the configuration succeeds.
     pub fn init_config(&mut self) -> Result<(), Cc1101WrapperError> {
+        let result = self.configure();
+        self.degraded = result.is_err();
+        self.recovery.restart();
+        result
+    }
+
+    /// Await the GDO interrupts signalled to `events` instead of polling the transceiver
+    /// state, taking effect at the next `init_config`. GDO2 is asserted from the sync word to
+    /// the end of packet, its falling edge shall be signalled as `Gdo::Gdo2`; GDO0 is asserted
+    /// when the RX FIFO fills above the threshold, or the TX FIFO drains below it, its rising
+    /// edge shall be signalled as `Gdo::Gdo0`.
+    pub fn set_gdo_events(&mut self, events: &'static GdoEvents) {
+        self.events = Some(events);
+    }
+
+    /// Whether the transceiver is not operational. In degraded mode `main` does not perform
+    /// any RF operation and `write_data` is rejected.
+    pub fn is_degraded(&self) -> bool {
+        self.degraded
+    }
+
+    /// Validate and apply a radio profile. The transceiver is put in Idle state while being
+    /// configured and the Rx state is restarted by the next `main` call. The output power is
+    /// mapped again to the PATABLE of the new frequency band and modulation, the frequency
+    /// offset and the Doppler correction are kept. The current channel is kept if it is part
+    /// of the new channel plan, otherwise channel 0 is selected.
+    pub fn apply_profile(&mut self, profile: &RadioProfile) -> Result<(), Cc1101WrapperError> {
+        profile.validate()?;
+
+        self.wake_up()?;
+        self.cc1101.exit_rx_tx()?;
+
+        self.tuned_hz = profile
+            .frequency
+            .saturating_add_signed(self.doppler_hz as i64);
+        self.cc1101.set_frequency(self.tuned_hz)?;
+        self.cc1101.set_freq_offset(self.frequency.freqoff())?;
+        self.cc1101.set_channel_spacing(profile.channel_spacing)?;
+        if self.channel >= profile.channels {
+            self.channel = 0;
+        }
+        self.cc1101.set_channel(self.channel)?;
+        self.cc1101.set_freq_if(profile.freq_if)?;
+        self.cc1101.set_chanbw(profile.chanbw)?;
+        self.cc1101.set_deviation(profile.deviation)?;
+        self.cc1101.set_data_rate(profile.data_rate)?;
+        self.cc1101.set_modulation_format(profile.modulation)?;
+        self.cc1101.set_num_preamble(profile.num_preamble)?;
+        self.cc1101.set_sync_mode(profile.sync_mode)?;
+        self.cc1101.set_packet_length(profile.packet_length)?;
+        self.cc1101.set_address_filter(profile.address_filter)?;
+        self.cc1101.crc_enable(profile.crc)?;
+        self.cc1101.crc_autoflush_enable(profile.crc_autoflush)?;
+        self.cc1101.white_data_enable(profile.whitening)?;
+        self.cc1101.fec_enable(profile.fec)?;
+        let power = self.power;
+        self.write_patable(&power, profile)?;
+
+        self.profile = *profile;
+        self.rx_init = false;
+
+        Ok(())
+    }
+
+    /// Get the radio profile currently applied
+    pub fn current_profile(&self) -> &RadioProfile {
+        &self.profile
+    }
+
+    /// Validate and apply a Listen Before Talk configuration, used by the next transmissions.
+    pub fn set_lbt_config(&mut self, config: &LbtConfig) -> Result<(), Cc1101WrapperError> {
+        config.validate()?;
+
+        self.wake_up()?;
+        self.cc1101.set_cca_mode(config.cca_mode)?;
+        self.cc1101
+            .set_carrier_sense_threshold(config.carrier_sense_threshold)?;
+
+        self.lbt = *config;
+
+        Ok(())
+    }
+
+    /// Get the Listen Before Talk configuration currently applied
+    pub fn current_lbt_config(&self) -> &LbtConfig {
+        &self.lbt
+    }
+
+    /// Validate and apply an output power configuration, used by the next transmissions.
+    pub fn set_power_config(&mut self, config: &PowerConfig) -> Result<(), Cc1101WrapperError> {
+        config.validate()?;
+
+        self.wake_up()?;
+        let profile = self.profile;
+        self.write_patable(config, &profile)?;
+
+        self.power = *config;
+
+        Ok(())
+    }
+
+    /// Get the output power configuration currently applied
+    pub fn current_power_config(&self) -> &PowerConfig {
+        &self.power
+    }
+
+    /// Set the output power [dBm], keeping the power ramp. The power level used is returned,
+    /// the highest one of `POWER_LEVELS_DBM` not above `dbm`.
+    pub fn set_tx_power(&mut self, dbm: i8) -> Result<i8, Cc1101WrapperError> {
+        let config = PowerConfig { dbm, ..self.power };
+        self.set_power_config(&config)?;
+
+        Ok(config.level_dbm())
+    }
+
+    /// Get the output power level of the transmissions [dBm]
+    pub fn tx_power(&self) -> i8 {
+        self.power.level_dbm()
+    }
+
+    /// Switch to a channel of the channel plan and calibrate the frequency synthesizer. The
+    /// channel hopping is stopped and the Rx state is restarted if it was running.
+    pub async fn switch_channel(&mut self, channel: u8) -> Result<(), Cc1101WrapperError> {
+        if channel >= self.profile.channels {
+            return Err(ChannelError::OutOfPlan.into());
+        }
+
+        self.wake_up()?;
+        self.hopping = None;
+
+        let timeout = fugit::ExtU64::millis(10);
+        self.set_radio_mode(RadioMode::Idle, timeout).await?;
+        self.cc1101.set_channel(channel)?;
+        self.channel = channel;
+        self.set_radio_mode(RadioMode::Calibrate, timeout).await?;
+        self.await_machine_state(MachineState::IDLE, timeout)
+            .await?;
+
+        if self.rx_init {
+            self.set_radio_mode(RadioMode::Receive, timeout).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the channel currently used
+    pub fn current_channel(&self) -> u8 {
+        self.channel
+    }
+
+    /// Validate a channel hopping configuration and start hopping: each `main` call in
+    /// `RadioPowerMode::Continuous` follows the channel of `hop_channel` for the current slot.
+    pub fn start_hopping(&mut self, config: &HoppingConfig) -> Result<(), Cc1101WrapperError> {
+        config.validate()?;
+
+        self.hopping = Some(*config);
+
+        Ok(())
+    }
+
+    /// Stop hopping, staying on the current channel
+    pub fn stop_hopping(&mut self) {
+        self.hopping = None;
+    }
+
+    /// Get the channel hopping configuration, none when not hopping
+    pub fn current_hopping_config(&self) -> Option<&HoppingConfig> {
+        self.hopping.as_ref()
+    }
+
+    /// Validate and apply a frequency offset tracking configuration. The offset compensated
+    /// so far is kept, unless the tracking is disabled.
+    pub fn set_afc_config(&mut self, config: &AfcConfig) -> Result<(), Cc1101WrapperError> {
+        config.validate()?;
+
+        if !config.enabled {
+            self.wake_up()?;
+            self.frequency.reset();
+            self.cc1101.set_freq_offset(0)?;
+        }
+
+        self.frequency.config = *config;
+
+        Ok(())
+    }
+
+    /// Get the frequency offset tracking configuration currently applied
+    pub fn current_afc_config(&self) -> &AfcConfig {
+        &self.frequency.config
+    }
+
+    /// Get the frequency offset currently compensated [Hz]
+    pub fn frequency_offset(&self) -> i32 {
+        self.frequency.offset_hz()
+    }
+
+    /// Validate and load a time-tagged Doppler correction table, replacing the previous one.
+    /// While `main` runs in `RadioPowerMode::Continuous` within the time span of the table,
+    /// the carrier is pre-corrected by the Doppler shift interpolated between its points:
+    /// the receiver listens the shift above the profile frequency, and the transmitter sends
+    /// the shift below it, so that the other end receives the nominal frequency. An empty
+    /// table stops the correction.
+    pub fn set_doppler_table(&mut self, table: &[DopplerPoint]) -> Result<(), Cc1101WrapperError> {
+        validate_doppler_table(table)?;
+
+        self.frequency.set_table(table);
+
+        Ok(())
+    }
+
+    /// Get the Doppler shift the carrier is currently corrected by [Hz]
+    pub fn doppler_correction(&self) -> i32 {
+        self.doppler_hz
+    }
+
+    /// Select the power mode of the transceiver between the RF operations, taking effect at
+    /// the next `main` call. While asleep, `main` does not access the transceiver unless a
+    /// packet woke the receiver up, signalled as for the Rx state, or a packet is queued for
+    /// transmission; the transceiver goes back to sleep afterwards.
+    pub fn set_radio_power_mode(&mut self, mode: RadioPowerMode) {
+        self.power_mode = mode;
+    }
+
+    /// Get the power mode of the transceiver between the RF operations
+    pub fn radio_power_mode(&self) -> RadioPowerMode {
+        self.power_mode
+    }
+
+    /// Validate a Wake-on-Radio configuration, used the next time the transceiver goes to
+    /// sleep in `RadioPowerMode::WakeOnRadio`.
+    pub fn set_wor_config(&mut self, config: &WorConfig) -> Result<(), Cc1101WrapperError> {
+        config.validate()?;
+
+        self.wor = *config;
+
+        Ok(())
+    }
+
+    /// Get the Wake-on-Radio configuration currently applied
+    pub fn current_wor_config(&self) -> &WorConfig {
+        &self.wor
+    }
+
+    fn write_patable(
+        &mut self,
+        config: &PowerConfig,
+        profile: &RadioProfile,
+    ) -> Result<(), Cc1101WrapperError> {
+        let (patable, pa_power) = config.patable(profile.frequency, profile.modulation);
+        self.cc1101.set_patable(&patable[..=pa_power as usize])?;
+        self.cc1101.set_pa_power(pa_power)?;
+
+        Ok(())
+    }
+
+    fn configure(&mut self) -> Result<(), Cc1101WrapperError> {
         // Reset CC1101
         self.cc1101.reset_chip()?;
+        self.asleep = false;
+        self.frequency.reset();
 
-        // Set project specific radio configuration
-        self.cc1101.set_frequency(433_000_000)?; // 433 MHz
-        self.cc1101.set_freq_if(203_125)?;
-        self.cc1101.set_chanbw(101_562)?;
-        self.cc1101.set_deviation(20_629)?;
-        self.cc1101.set_data_rate(38_383)?;
-        self.cc1101
-            .set_modulation_format(ModulationFormat::BinaryFrequencyShiftKeying)?;
-        self.cc1101.set_num_preamble(NumPreamble::Eight)?;
-        self.cc1101.set_sync_mode(SyncMode::MatchFull(0xCAFE))?;
-        self.cc1101
-            .set_packet_length(PacketLength::Fixed(PACKET_LENGTH))?;
-        self.cc1101.set_address_filter(AddressFilter::Disabled)?;
-        self.cc1101.crc_enable(true)?;
-        self.cc1101.crc_autoflush_enable(true)?;
+        // Check the chip identity
+        let (partnum, version) = self.cc1101.get_hw_info()?;
+        check_hw_info(partnum, version)?;
+
+        // Set project specific radio configuration, with the current radio profile
+        let profile = self.profile;
+        self.apply_profile(&profile)?;
         self.cc1101.append_status_enable(false)?;
-        self.cc1101.white_data_enable(false)?;
-        self.cc1101.set_cca_mode(CcaMode::CciAlways)?;
+        let lbt = self.lbt;
+        self.set_lbt_config(&lbt)?;
         self.cc1101.set_autocalibration(AutoCalibration::FromIdle)?;
-        self.cc1101.set_gdo2_active_state(PinState::Low)?;
-        self.cc1101.set_gdo2_config(GdoCfg::CRC_OK)?;
+        self.cc1101.set_fifo_threshold(FIFO_THRESHOLD)?;
+        self.configure_gdo()?;
 
         // Set Rx mode
         self.rx_mode = Cc1101RxMode::Interrupt;
@@ -151,6 +593,38 @@ where
         Ok(())
     }
 
+    fn configure_gdo(&mut self) -> Result<(), Cc1101WrapperError> {
+        if self.events.is_some() {
+            // Sync word sent or received, until the end of packet
+            self.cc1101.set_gdo2_active_state(PinState::High)?;
+            self.cc1101.set_gdo2_config(GdoCfg::SYNC_WORD)?;
+
+            self.configure_gdo0(false)?;
+        } else {
+            // Packet received with valid CRC
+            self.cc1101.set_gdo2_active_state(PinState::Low)?;
+            self.cc1101.set_gdo2_config(GdoCfg::CRC_OK)?;
+        }
+
+        Ok(())
+    }
+
+    /// Configure GDO0 to signal the TX FIFO draining below the threshold while transmitting,
+    /// or the RX FIFO filling above the threshold otherwise.
+    fn configure_gdo0(&mut self, transmit: bool) -> Result<(), Cc1101WrapperError> {
+        if transmit {
+            // TX FIFO drained below the threshold
+            self.cc1101.set_gdo0_active_state(PinState::Low)?;
+            self.cc1101.set_gdo0_config(GdoCfg::TX_FIFO_THR)?;
+        } else {
+            // RX FIFO filled above the threshold, or end of packet
+            self.cc1101.set_gdo0_active_state(PinState::High)?;
+            self.cc1101.set_gdo0_config(GdoCfg::RX_FIFO_THR_OR_EOP)?;
+        }
+
+        Ok(())
+    }
+
     /// Get HW partnum and version info
     pub fn get_hw_info(&mut self) -> Result<(u8, u8), Cc1101WrapperError> {
         Ok(self.cc1101.get_hw_info()?)
@@ -158,6 +632,25 @@ where
 
     /// CC1101 main function which processes the RF operations and shall be called cyclically.
     pub async fn main(&mut self) {
+        if self.degraded {
+            return;
+        }
+
+        if self.power_mode != RadioPowerMode::Continuous {
+            self.process_low_power().await;
+            return;
+        }
+
+        // Wake up from a low power mode
+        let result = self.wake_up();
+        self.process_native_result(result);
+
+        // Follow the Doppler correction table
+        self.track_doppler().await;
+
+        // Follow the hopping sequence
+        self.hop().await;
+
         // Initialization activity
         if !self.rx_init {
             self.rx_init = true;
@@ -188,45 +681,254 @@ where
     }
 
     pub fn is_data_received(&mut self) -> bool {
-        self.rx_data.ready
+        !self.rx_queue.is_empty()
     }
 
+    /// Read the oldest received packet into `data`. Returns the length of the data and the
+    /// source address, which is BROADCAST_ADDRESS in fixed packet length mode.
     pub fn read_data(
         &mut self,
         data: &mut [u8],
         rssi: &mut i16,
         lqi: &mut u8,
-    ) -> Result<(), Cc1101WrapperError> {
-        if self.rx_data.ready {
-            // Copy data from internal Rx Buffer
-            *rssi = self.last_rx_rssi;
-            *lqi = self.last_rx_lqi;
-            data[..(self.rx_data.length as usize)]
-                .copy_from_slice(&self.rx_data.data[..(self.rx_data.length as usize)]);
-
-            self.rx_data.ready = false;
-        } else {
-            // Rx buffer is empty. No data was received on RF
+    ) -> Result<(usize, u8), Cc1101WrapperError> {
+        let Some(packet) = self.rx_queue.front() else {
+            // Rx queue is empty. No data was received on RF
             return Err(Cc1101WrapperError::RxBufferEmpty);
+        };
+
+        let length = packet.length as usize;
+        if data.len() < length {
+            return Err(Cc1101WrapperError::InvalidLength);
         }
 
-        Ok(())
+        // Copy data from internal Rx Queue
+        *rssi = packet.rssi;
+        *lqi = packet.lqi;
+        data[..length].copy_from_slice(&packet.data[..length]);
+        let source = packet.source;
+
+        self.rx_queue.pop_front();
+
+        Ok((length, source))
+    }
+
+    /// Queue `data` for transmission to the `destination` address, with normal priority and
+    /// without completion notification. See `write_packet`.
+    pub fn write_data(&mut self, destination: u8, data: &[u8]) -> Result<(), Cc1101WrapperError> {
+        self.write_packet(destination, data, TxPriority::Normal, false)
+            .map(|_| ())
+    }
+
+    /// Queue `data` for transmission to the `destination` address. In variable packet length
+    /// mode exactly the given bytes are sent, preceded by the destination and source
+    /// addresses; in fixed packet length mode `data` shall have the configured length and
+    /// the addresses are not sent; in infinite packet length mode the data is preceded by its
+    /// length. Up to `MAX_PACKET_LENGTH` bytes are queued.
+    ///
+    /// Queued packets are sent by `main`, highest `priority` first. If `notify` is set, the
+    /// outcome is reported by `tx_completion` under the returned id.
+    pub fn write_packet(
+        &mut self,
+        destination: u8,
+        data: &[u8],
+        priority: TxPriority,
+        notify: bool,
+    ) -> Result<TxId, Cc1101WrapperError> {
+        if self.degraded {
+            return Err(Cc1101WrapperError::Degraded);
+        }
+        self.check_length(data.len(), MAX_PACKET_LENGTH)?;
+
+        let mut packet = TxPacket {
+            id: self.tx_next_id,
+            priority,
+            notify,
+            destination,
+            source: self.profile.device_address(),
+            length: data.len() as u8,
+            data: [0; MAX_PACKET_LENGTH],
+        };
+        packet.data[..data.len()].copy_from_slice(data);
+
+        // Tx queue may be full of unsent packets
+        let id = packet.id;
+        self.tx_queue
+            .push(packet)
+            .map_err(|_| Cc1101WrapperError::TxBufferBusy)?;
+        self.tx_next_id = self.tx_next_id.wrapping_add(1);
+
+        Ok(id)
+    }
+
+    /// Transmit `data` to the `destination` address right away, ahead of the queued packets,
+    /// and wait for the end of the transmission. The data is given as for `write_packet`, up
+    /// to 65535 bytes in infinite packet length mode. Errors are stored as for `main`, see
+    /// `read_last_error`, and fault the next transceiver check.
+    pub async fn transmit(
+        &mut self,
+        destination: u8,
+        data: &[u8],
+    ) -> Result<(), Cc1101WrapperError> {
+        if self.degraded {
+            return Err(Cc1101WrapperError::Degraded);
+        }
+        self.check_length(data.len(), u16::MAX as usize)?;
+
+        let result = match self.wake_up() {
+            Ok(()) => {
+                let source = self.profile.device_address();
+
+                self.begin_transmit().await;
+                let result = self.send_packet(destination, source, data).await;
+                self.end_transmit().await;
+                result
+            }
+            Err(error) => Err(error),
+        };
+
+        if let Err(error) = result {
+            self.store_error(error);
+        }
+        result
+    }
+
+    /// Wait up to `timeout` for a packet and read it into `data`, as `read_data` does. The
+    /// packets already queued are returned first. Packets larger than `MAX_PACKET_LENGTH`
+    /// are only received this way. Errors are stored as for `main`, except the timeout
+    /// without any packet.
+    pub async fn receive(
+        &mut self,
+        data: &mut [u8],
+        rssi: &mut i16,
+        lqi: &mut u8,
+        timeout: Duration<u64, 1, 1000>,
+    ) -> Result<(usize, u8), Cc1101WrapperError> {
+        if self.degraded {
+            return Err(Cc1101WrapperError::Degraded);
+        }
+        if !self.rx_queue.is_empty() {
+            return self.read_data(data, rssi, lqi);
+        }
+        if let Err(error) = self.wake_up() {
+            self.store_error(error);
+            return Err(error);
+        }
+
+        if !self.rx_init {
+            self.rx_init = true;
+            self.start_rx_state().await;
+        }
+
+        let deadline = self.clock.now() + timeout;
+        loop {
+            match self.await_rx_start(deadline).await {
+                Ok(()) => {}
+                // No packet before the deadline, not a fault of the transceiver
+                Err(Cc1101WrapperError::TimeoutError) => {
+                    return Err(Cc1101WrapperError::TimeoutError)
+                }
+                Err(error) => {
+                    self.store_error(error);
+                    return Err(error);
+                }
+            }
+
+            // Receive data
+            let result = self.receive_frame(data).await;
+            if let Err(error) = result {
+                self.store_error(error);
+            }
+
+            // Restart Rx state
+            self.start_rx_state().await;
+
+            if let Some(frame) = result? {
+                *rssi = frame.rssi;
+                *lqi = frame.lqi;
+                return Ok((frame.length, frame.source));
+            }
+        }
     }
 
-    pub fn write_data(&mut self, data: &[u8]) -> Result<(), Cc1101WrapperError> {
-        if !self.tx_data.ready {
-            // Copy data into internal Tx Buffer
-            self.tx_data.length = data.len() as u8;
-            self.tx_data.data[..(self.tx_data.length as usize)].copy_from_slice(data);
-            self.tx_data.ready = true;
+    /// Check that `length` data bytes fit the configured packet length, or `max_length` in
+    /// infinite packet length mode. Infinite packets of a multiple of 256 bytes, data length
+    /// included, cannot be ended with a packet length and are rejected.
+    fn check_length(&self, length: usize, max_length: usize) -> Result<(), Cc1101WrapperError> {
+        let valid = match self.profile.packet_length {
+            // Length field counts the destination and source address bytes
+            PacketLength::Variable(max) => length > 0 && length + 2 <= max as usize,
+            PacketLength::Fixed(fixed) => length == fixed as usize,
+            PacketLength::Infinite => length > 0 && length <= max_length && (length + 2) as u8 != 0,
+        };
+
+        if valid {
+            Ok(())
         } else {
-            // Tx buffer is busy holding previous data
-            return Err(Cc1101WrapperError::TxBufferBusy);
+            Err(Cc1101WrapperError::InvalidLength)
+        }
+    }
+
+    /// Number of packets waiting in the transmit queue
+    pub fn tx_pending(&self) -> usize {
+        self.tx_queue.len()
+    }
+
+    /// Get the oldest completion notification of the packets queued with `notify` set. Only
+    /// the latest `TXQ` notifications are kept.
+    pub fn tx_completion(&mut self) -> Option<TxCompletion> {
+        self.tx_completions.pop_front()
+    }
+
+    /// Set the address filter of the received packets, keeping the rest of the radio profile.
+    pub fn set_address_filter(&mut self, filter: AddressFilter) -> Result<(), Cc1101WrapperError> {
+        let profile = RadioProfile {
+            address_filter: filter,
+            ..self.profile
+        };
+        self.apply_profile(&profile)
+    }
+
+    /// Get the link quality statistics, since the start or the last `reset_link_stats`.
+    pub fn link_stats(&mut self) -> LinkStats {
+        let now = self.clock.now();
+        LinkStats {
+            tx_power_dbm: self.power.level_dbm(),
+            freq_offset_hz: self.frequency.offset_hz(),
+            channel: self.channel,
+            ..self.link.stats(now)
         }
+    }
+
+    /// Restart the link quality statistics. The time since the last valid packet is kept.
+    pub fn reset_link_stats(&mut self) {
+        self.link.reset();
+    }
+
+    /// Validate and apply a fault recovery configuration, restarting the escalation.
+    pub fn set_recovery_config(
+        &mut self,
+        config: &RecoveryConfig,
+    ) -> Result<(), Cc1101WrapperError> {
+        config.validate()?;
+
+        self.recovery.config = *config;
+        self.recovery.restart();
 
         Ok(())
     }
 
+    /// Get the fault recovery configuration currently applied
+    pub fn current_recovery_config(&self) -> &RecoveryConfig {
+        &self.recovery.config
+    }
+
+    /// Get the oldest recovery action taken by the transceiver monitoring. Only the latest
+    /// `RECOVERY_HISTORY_DEPTH` records are kept.
+    pub fn recovery_record(&mut self) -> Option<RecoveryRecord> {
+        self.recovery.pop_record()
+    }
+
     pub fn read_last_error(&mut self) -> (Option<Cc1101WrapperError>, u32) {
         let last_error = self.last_error;
         let error_count = self.error_count;
@@ -260,19 +962,12 @@ where
         // Start Rx
         self.start_rx_state().await;
 
-        match Systick::timeout_after(fugit::ExtU64::millis(100), self.receive_polling()).await {
-            Ok(result) => match result {
-                Ok(state) => match state {
-                    RxState::Received => {
-                        self.rx_data.ready = true;
-                    }
-                    _ => { /* Unreachable statement */ }
-                },
-                Err(error) => {
-                    self.store_error(error);
-                }
-            },
-            Err(_) => { /* Timeout Error */ }
+        match self.receive_polling(fugit::ExtU64::millis(100)).await {
+            Ok(()) => { /* Received */ }
+            Err(Cc1101WrapperError::TimeoutError) => { /* Nothing received */ }
+            Err(error) => {
+                self.store_error(error);
+            }
         };
 
         // Start Idle state
@@ -280,15 +975,15 @@ where
     }
 
     async fn process_receive_interrupt(&mut self) {
-        // Check if Rx interrupt is pending
-        if self.rx_int_pending {
+        // Check if Rx interrupt is pending: end of packet, or RX FIFO filled above the threshold
+        let signalled = self
+            .events
+            .is_some_and(|events| events.take(Gdo::Gdo2) | events.take(Gdo::Gdo0));
+        if self.rx_int_pending || signalled {
             self.rx_int_pending = false;
 
-            // Start Idle state
-            self.start_idle_state().await;
-
             // Receive data
-            let result = self.receive_interrupt();
+            let result = self.receive_queued().await;
             self.process_native_result(result);
 
             // Restart Rx state
@@ -297,189 +992,815 @@ where
     }
 
     async fn process_transmit(&mut self) {
-        let timeout = fugit::ExtU64::millis(10);
-
         // Check if data is available for write
-        if self.tx_data.ready {
-            if self.rx_mode == Cc1101RxMode::Interrupt {
-                // Start Idle state
-                self.start_idle_state().await;
+        if self.tx_queue.is_empty() {
+            return;
+        }
 
-                // Flush FIFO RX
-                let result = self.cc1101.flush_rx_fifo_buffer();
-                self.process_result(result);
-                let result = self.cc1101.get_machine_state();
-                let _ = self.process_result(result);
+        self.begin_transmit().await;
+
+        // Send the queued packets, highest priority first
+        while let Some(packet) = self.tx_queue.pop() {
+            let data = &packet.data[..packet.length as usize];
+            let result = match self
+                .send_packet(packet.destination, packet.source, data)
+                .await
+            {
+                Ok(()) => TxResult::Sent,
+                Err(Cc1101WrapperError::ChannelBusy) => {
+                    self.store_error(Cc1101WrapperError::ChannelBusy);
+                    TxResult::ChannelBusy
+                }
+                Err(error) => {
+                    self.store_error(error);
+                    TxResult::Failed(error)
+                }
+            };
+
+            if packet.notify {
+                let completion = TxCompletion {
+                    id: packet.id,
+                    result,
+                };
+                if let Err(completion) = self.tx_completions.push_back(completion) {
+                    // Drop the oldest notification
+                    self.tx_completions.pop_front();
+                    let _ = self.tx_completions.push_back(completion);
+                }
             }
+        }
 
-            // Flush FIFO Tx
-            let result = self.cc1101.flush_tx_fifo_buffer();
+        self.end_transmit().await;
+    }
+
+    async fn begin_transmit(&mut self) {
+        if self.rx_mode == Cc1101RxMode::Interrupt {
+            // Start Idle state
+            self.start_idle_state().await;
+
+            // Flush FIFO RX
+            let result = self.cc1101.flush_rx_fifo_buffer();
             self.process_result(result);
             let result = self.cc1101.get_machine_state();
             let _ = self.process_result(result);
+        }
 
-            // let mut length: Option<u8> = Some(self.tx_data.length + 1); // Plus address byte
-            let mut length: Option<u8> = None;
-            // let mut address: Option<u8> = Some(self.tx_data.address);
-            let mut address: Option<u8> = None;
-
-            // Write data
-            let result = self.cc1101.write_data(
-                &mut length,
-                &mut address,
-                &mut self.tx_data.data[..(self.tx_data.length as usize)],
-            );
-            self.process_result(result);
-            let result = self.cc1101.get_tx_bytes();
-            let _ = self.process_result(result);
+        // Doppler pre-correction of the transmitted carrier
+        let result = self.tune(-self.doppler_hz);
+        self.process_native_result(result);
+    }
 
-            // Start Tx
-            let result = self.set_radio_mode(RadioMode::Transmit, timeout).await;
-            self.process_native_result(result);
-            Systick::delay(fugit::ExtU64::millis(5)).await;
+    async fn end_transmit(&mut self) {
+        let result = self.tune(self.doppler_hz);
+        self.process_native_result(result);
+
+        if self.rx_mode == Cc1101RxMode::Interrupt {
+            // Restart Rx state
+            self.start_rx_state().await;
+        }
+    }
+
+    /// Transmit a single packet and wait for the transceiver to return to Idle state.
+    async fn send_packet(
+        &mut self,
+        destination: u8,
+        source: u8,
+        data: &[u8],
+    ) -> Result<(), Cc1101WrapperError> {
+        let (header, header_len) = self.packet_header(destination, source, data.len());
+        let total = header_len + data.len();
+        let refill = total > FIFO_SIZE_MAX as usize;
+
+        if refill && self.events.is_some() {
+            self.configure_gdo0(true)?;
+        }
+
+        let result = self.stream_packet(&header[..header_len], data).await;
+        if result.is_ok() {
+            self.link.packet_sent();
+        }
+
+        // Recover from a TX FIFO underflow
+        if result == Err(Cc1101WrapperError::TxUnderflow) {
+            self.cc1101.flush_tx_fifo_buffer()?;
+        }
+        if self.profile.packet_length == PacketLength::Infinite {
+            self.cc1101.set_packet_length(PacketLength::Infinite)?;
+        }
+        if refill && self.events.is_some() {
+            self.configure_gdo0(false)?;
+            self.discard_event(Gdo::Gdo0);
+        }
+
+        result
+    }
+
+    /// Write the packet made of `header` and `data` into the TX FIFO and transmit it. Packets
+    /// larger than the TX FIFO are written as it drains. In infinite packet length mode the
+    /// transceiver is switched to fixed packet length mode within the last 255 bytes, so that
+    /// the packet ends after the data.
+    async fn stream_packet(
+        &mut self,
+        header: &[u8],
+        data: &[u8],
+    ) -> Result<(), Cc1101WrapperError> {
+        let timeout = fugit::ExtU64::millis(10);
+        let fifo_size = FIFO_SIZE_MAX as usize;
+        let total = header.len() + data.len();
+
+        // Flush FIFO Tx
+        self.cc1101.flush_tx_fifo_buffer()?;
+        self.cc1101.get_machine_state()?;
+
+        let mut fixed_length = self.profile.packet_length != PacketLength::Infinite;
+        if !fixed_length && total <= 255 {
+            self.cc1101
+                .set_packet_length(PacketLength::Fixed(total as u8))?;
+            fixed_length = true;
+        }
+
+        // Write data
+        let mut written = total.min(fifo_size);
+        self.write_fifo_bytes(header, data, 0..written)?;
+
+        // Start Tx
+        self.discard_event(Gdo::Gdo0);
+        self.discard_event(Gdo::Gdo2);
+        if self.lbt.enabled() {
+            self.start_tx_when_clear().await?;
+        } else {
+            self.set_radio_mode(RadioMode::Transmit, timeout).await?;
+        }
+        let deadline = self.clock.now() + self.packet_timeout(total);
+
+        // Refill FIFO Tx
+        while written < total || !fixed_length {
+            self.await_fifo(deadline).await?;
+
+            let txbytes = self.cc1101.get_tx_bytes()?;
+            if txbytes > FIFO_SIZE_MAX {
+                return Err(Cc1101WrapperError::TxUnderflow);
+            }
+            let txbytes = txbytes as usize;
+
+            // The packet ends when the byte counter modulo 256 reaches the packet length. It is
+            // written first, the packet would otherwise end at the previous packet length.
+            let sent = written - txbytes;
+            if !fixed_length && total - sent <= 255 {
+                self.cc1101.write_register(PKTLEN, total as u8)?;
+                self.cc1101
+                    .set_packet_length(PacketLength::Fixed(total as u8))?;
+                fixed_length = true;
+            }
+
+            let length = (fifo_size - txbytes).min(total - written);
+            self.write_fifo_bytes(header, data, written..written + length)?;
+            written += length;
+        }
+
+        // Wait for Tx to finish
+        match self.events {
+            Some(events) => {
+                let timeout = self.millis_until(deadline);
+                if !wait_until(events, &[Gdo::Gdo2], self.clock.delay_ms(timeout)).await {
+                    return Err(Cc1101WrapperError::TimeoutError);
+                }
+            }
+            None => self.clock.delay_ms(5).await,
+        }
+        self.await_machine_state(MachineState::IDLE, timeout).await
+    }
+
+    /// Header preceding the data on the air: length, destination and source address bytes in
+    /// variable packet length mode, data length in infinite packet length mode.
+    fn packet_header(&self, destination: u8, source: u8, length: usize) -> ([u8; 3], usize) {
+        match self.profile.packet_length {
+            PacketLength::Variable(_) => ([length as u8 + 2, destination, source], 3),
+            PacketLength::Fixed(_) => ([0; 3], 0),
+            PacketLength::Infinite => {
+                let [high, low] = (length as u16).to_be_bytes();
+                ([high, low, 0], 2)
+            }
+        }
+    }
+
+    /// Write the `range` bytes of the packet made of `header` and `data` into the TX FIFO.
+    fn write_fifo_bytes(
+        &mut self,
+        header: &[u8],
+        data: &[u8],
+        range: Range<usize>,
+    ) -> Result<(), Cc1101WrapperError> {
+        let split = header.len();
+        if range.start < split {
+            self.cc1101
+                .write_fifo_data(&header[range.start..range.end.min(split)])?;
+        }
+        if range.end > split {
+            self.cc1101
+                .write_fifo_data(&data[range.start.max(split) - split..range.end - split])?;
+        }
+
+        Ok(())
+    }
 
-            // Wait for Tx to finish and get the result
-            let result = self.await_machine_state(MachineState::IDLE, timeout).await;
+    /// Read the `range` bytes of the packet made of `header` and `data` from the RX FIFO.
+    fn read_fifo_bytes(
+        &mut self,
+        header: &mut [u8],
+        data: &mut [u8],
+        range: Range<usize>,
+    ) -> Result<(), Cc1101WrapperError> {
+        let split = header.len();
+        if range.start < split {
+            self.cc1101
+                .read_fifo_data(&mut header[range.start..range.end.min(split)])?;
+        }
+        if range.end > split {
+            self.cc1101
+                .read_fifo_data(&mut data[range.start.max(split) - split..range.end - split])?;
+        }
+
+        Ok(())
+    }
+
+    /// Wait for the FIFO threshold event of GDO0 before accessing the FIFO again, or for half
+    /// the time to fill or drain the threshold without GDO events.
+    async fn await_fifo(&mut self, deadline: Timestamp) -> Result<(), Cc1101WrapperError> {
+        if self.clock.now() >= deadline {
+            return Err(Cc1101WrapperError::TimeoutError);
+        }
+
+        let airtime = self.airtime_ms(FIFO_THRESHOLD_BYTES);
+        match self.events {
+            Some(events) => {
+                // The FIFO is checked anyway on timeout, e.g. after the last bytes of a packet
+                wait_until(events, &[Gdo::Gdo0], self.clock.delay_ms(airtime)).await;
+            }
+            None => self.clock.delay_ms((airtime / 2).max(1)).await,
+        }
+
+        Ok(())
+    }
+
+    /// Discard the pending event of the `gdo` pin, e.g. raised by a packet received in Rx.
+    fn discard_event(&self, gdo: Gdo) {
+        if let Some(events) = self.events {
+            events.take(gdo);
+        }
+    }
+
+    /// Time on air of `bytes` bytes [ms], rounded up
+    fn airtime_ms(&self, bytes: usize) -> u32 {
+        let bits = bytes as u64 * 8;
+        let coding = if self.profile.fec { 2 } else { 1 };
+        (bits * coding * 1000).div_ceil(self.profile.data_rate.max(1)) as u32
+    }
+
+    /// Upper bound of the time on air of a packet of `length` bytes, header included
+    fn packet_timeout(&self, length: usize) -> Duration<u64, 1, 1000> {
+        fugit::ExtU64::millis(self.airtime_ms(length + PACKET_OVERHEAD) as u64 + 10)
+    }
+
+    /// Milliseconds left until `deadline`
+    fn millis_until(&mut self, deadline: Timestamp) -> u32 {
+        let now = self.clock.now();
+        if now < deadline {
+            (deadline - now).to_millis() as u32
+        } else {
+            0
+        }
+    }
+
+    /// Start Tx from Rx state, so that the transceiver only transmits if the channel is clear.
+    /// Busy attempts are retried after a random back-off.
+    async fn start_tx_when_clear(&mut self) -> Result<(), Cc1101WrapperError> {
+        let timeout = fugit::ExtU64::millis(10);
+
+        for attempt in 0..=self.lbt.max_retries {
+            if attempt > 0 {
+                let backoff = self.next_backoff_ms();
+                self.clock.delay_ms(backoff).await;
+            }
+
+            self.set_radio_mode(RadioMode::Receive, timeout).await?;
+
+            // Wait for a valid RSSI before the channel assessment
+            self.clock.delay_ms(1).await;
+
+            // Strobe is ignored by the transceiver if the channel is busy
+            self.discard_event(Gdo::Gdo2);
+            self.cc1101.enable_tx()?;
+            match self
+                .await_machine_state(MachineState::TX, fugit::ExtU64::millis(2))
+                .await
+            {
+                Ok(()) => return Ok(()),
+                Err(Cc1101WrapperError::TimeoutError) => {
+                    // Feed the RSSI noise to the back-off generator
+                    let rssi = self.cc1101.get_rssi_dbm()?;
+                    self.backoff_seed ^= rssi as u16 as u32;
+                }
+                Err(error) => return Err(error),
+            }
+        }
+
+        self.cc1101.exit_rx_tx()?;
+
+        Err(Cc1101WrapperError::ChannelBusy)
+    }
+
+    /// Random back-off within the configured range, from a xorshift generator.
+    fn next_backoff_ms(&mut self) -> u32 {
+        let mut x = self.backoff_seed.max(1);
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.backoff_seed = x;
+
+        let span = self.lbt.backoff_max_ms - self.lbt.backoff_min_ms;
+        self.lbt.backoff_min_ms + x % span.saturating_add(1)
+    }
+
+    async fn process_low_power(&mut self) {
+        // Packet received by the woken up receiver
+        let signalled = self.power_mode == RadioPowerMode::WakeOnRadio
+            && (self.rx_int_pending
+                || self
+                    .events
+                    .is_some_and(|events| events.take(Gdo::Gdo2) | events.take(Gdo::Gdo0)));
+
+        // Any access wakes the transceiver up, let it sleep until there is something to do
+        if self.asleep && !signalled && self.tx_queue.is_empty() {
+            return;
+        }
+
+        if signalled {
+            self.rx_int_pending = false;
+
+            // Receive data
+            let result = self.receive_queued().await;
             self.process_native_result(result);
+        }
 
-            self.tx_data.ready = false;
+        let result = self.wake_up();
+        self.process_native_result(result);
 
-            if self.rx_mode == Cc1101RxMode::Interrupt {
-                // Restart Rx state
-                self.start_rx_state().await;
+        // Process RF transmitting
+        self.process_transmit().await;
+
+        // Go back to sleep
+        let result = self.enter_low_power().await;
+        self.process_native_result(result);
+    }
+
+    /// Put the transceiver to sleep in the selected power mode, saving the registers lost in
+    /// SLEEP state.
+    async fn enter_low_power(&mut self) -> Result<(), Cc1101WrapperError> {
+        self.set_radio_mode(RadioMode::Idle, fugit::ExtU64::millis(10))
+            .await?;
+
+        for (value, &addr) in self.sleep_backup.iter_mut().zip(TEST_REGISTERS.iter()) {
+            *value = self.cc1101.read_register(addr)?;
+        }
+
+        if self.power_mode == RadioPowerMode::WakeOnRadio {
+            for (addr, value) in self.wor.registers() {
+                self.cc1101.write_register(addr, value)?;
             }
+            self.cc1101.flush_rx_fifo_buffer()?;
+            self.cc1101.enter_wake_on_radio()?;
+        } else {
+            self.cc1101.enter_power_down_mode()?;
+        }
+
+        self.asleep = true;
+        self.rx_init = false;
+
+        Ok(())
+    }
+
+    /// Wake the transceiver up if asleep, restoring the TEST registers and the PATABLE lost
+    /// in SLEEP state.
+    fn wake_up(&mut self) -> Result<(), Cc1101WrapperError> {
+        if !self.asleep {
+            return Ok(());
+        }
+
+        // The first access wakes the transceiver up
+        self.cc1101.get_machine_state()?;
+        self.asleep = false;
+
+        for (&addr, &value) in TEST_REGISTERS.iter().zip(self.sleep_backup.iter()) {
+            self.cc1101.write_register(addr, value)?;
+        }
+        let (power, profile) = (self.power, self.profile);
+        self.write_patable(&power, &profile)
+    }
+
+    /// Program the carrier frequency of the profile shifted by `shift_hz`, in Idle state.
+    fn tune(&mut self, shift_hz: i32) -> Result<(), Cc1101WrapperError> {
+        let frequency = self
+            .profile
+            .frequency
+            .saturating_add_signed(shift_hz as i64);
+        if frequency != self.tuned_hz {
+            self.cc1101.set_frequency(frequency)?;
+            self.tuned_hz = frequency;
+        }
+
+        Ok(())
+    }
+
+    /// Retune the receiver when the Doppler shift of the table moved by `DOPPLER_STEP_HZ`,
+    /// or at the end of the table.
+    async fn track_doppler(&mut self) {
+        let now = self.clock.now();
+        let shift = self.frequency.doppler_at(now);
+        let step = shift.abs_diff(self.doppler_hz);
+        if step < DOPPLER_STEP_HZ && (shift != 0 || self.doppler_hz == 0) {
+            return;
+        }
+
+        self.doppler_hz = shift;
+        if self.rx_init {
+            self.start_idle_state().await;
+        }
+        let result = self.tune(shift);
+        self.process_native_result(result);
+        if self.rx_init {
+            self.start_rx_state().await;
+        }
+    }
+
+    /// Change the channel at the start of each slot of the hopping sequence.
+    async fn hop(&mut self) {
+        let Some(config) = self.hopping else {
+            return;
+        };
+        let Some(slot) = config.slot_at(self.clock.now()) else {
+            return;
+        };
+        let channel = hop_channel(config.seed, slot, self.profile.channels);
+        if channel == self.channel {
+            return;
+        }
+
+        if self.rx_init {
+            self.start_idle_state().await;
+        }
+        let result = self.cc1101.set_channel(channel);
+        if self.process_result(result).is_some() {
+            self.channel = channel;
+        }
+        if self.rx_init {
+            self.start_rx_state().await;
         }
     }
 
     async fn monitor(&mut self) {
-        let period: Duration<u64, 1, 1000> = fugit::ExtU64::millis(1000);
-        let timestamp_now = Systick::now();
+        let period: Duration<u64, 1, 1000> =
+            fugit::ExtU64::millis(self.recovery.config.check_period_ms as u64);
+        let timestamp_now = self.clock.now();
 
         if (timestamp_now - self.timestamp_monitor) > period {
             self.timestamp_monitor = timestamp_now;
 
             let result = self.cc1101.get_machine_state();
+            let state = self.process_result(result);
+
+            // Errors of the RF operations since the last check, the state read included
+            let pending = self.recovery.take_pending();
 
-            match self.process_result(result) {
+            let fault = match state {
                 Some(state) => {
                     if state != MachineState::RX {
                         self.store_error(Cc1101WrapperError::MonitoringError);
-
-                        // Restart Rx state
-                        self.start_rx_state().await;
+                        pending.or(Some(Cc1101WrapperError::MonitoringError))
+                    } else {
+                        let result = self.sample_noise_floor();
+                        self.process_native_result(result);
+                        pending
                     }
                 }
-                None => {
-                    // Start Idle state
-                    self.start_idle_state().await;
+                None => pending,
+            };
 
-                    // Restart Rx state
-                    self.start_rx_state().await;
-                }
+            match fault {
+                Some(cause) => self.recover(cause).await,
+                None => self.recovery.healthy(),
             }
         }
     }
 
-    async fn receive_polling(&mut self) -> Result<RxState, Cc1101WrapperError> {
-        let mut rx_state = RxState::Waiting;
-        let mut last_rxbytes = 0;
+    /// Take the recovery action of the escalation level reached by a faulty check.
+    async fn recover(&mut self, cause: Cc1101WrapperError) {
+        let Some(action) = self.recovery.escalate() else {
+            return;
+        };
+
+        let result = match action {
+            RecoveryAction::FlushFifos => self.flush_fifos().await,
+            RecoveryAction::Restrobe => self.restrobe().await,
+            RecoveryAction::Recalibrate => self.recalibrate().await,
+            RecoveryAction::Reset => self.reset_transceiver().await,
+            RecoveryAction::MarkFailed => {
+                self.degraded = true;
+                Ok(())
+            }
+        };
+
+        let timestamp = self.clock.now();
+        self.recovery.record(timestamp, cause, action, result);
 
+        // A failed action faults the next check
+        if let Err(error) = result {
+            self.store_error(error);
+        }
+    }
+
+    async fn flush_fifos(&mut self) -> Result<(), Cc1101WrapperError> {
+        self.cc1101.exit_rx_tx()?;
+        self.cc1101.flush_rx_fifo_buffer()?;
+        self.cc1101.flush_tx_fifo_buffer()?;
+
+        self.set_radio_mode(RadioMode::Receive, fugit::ExtU64::millis(10))
+            .await
+    }
+
+    async fn restrobe(&mut self) -> Result<(), Cc1101WrapperError> {
+        let timeout = fugit::ExtU64::millis(10);
+        self.set_radio_mode(RadioMode::Idle, timeout).await?;
+        self.set_radio_mode(RadioMode::Receive, timeout).await
+    }
+
+    async fn recalibrate(&mut self) -> Result<(), Cc1101WrapperError> {
+        let timeout = fugit::ExtU64::millis(10);
+        self.set_radio_mode(RadioMode::Calibrate, timeout).await?;
+        self.await_machine_state(MachineState::IDLE, timeout)
+            .await?;
+        self.set_radio_mode(RadioMode::Receive, timeout).await
+    }
+
+    /// Reset the transceiver, then apply the project configuration and the current radio
+    /// profile again.
+    async fn reset_transceiver(&mut self) -> Result<(), Cc1101WrapperError> {
+        self.configure()?;
+
+        self.rx_init = true;
+        self.set_radio_mode(RadioMode::Receive, fugit::ExtU64::millis(10))
+            .await
+    }
+
+    /// Sample the RSSI as noise floor, unless a packet is being received.
+    fn sample_noise_floor(&mut self) -> Result<(), Cc1101WrapperError> {
+        if !self.cc1101.get_packet_status()?.sof_delimiter {
+            let rssi = self.cc1101.get_rssi_dbm()?;
+            self.link.noise_sampled(rssi);
+        }
+
+        Ok(())
+    }
+
+    async fn receive_polling(
+        &mut self,
+        timeout: Duration<u64, 1, 1000>,
+    ) -> Result<(), Cc1101WrapperError> {
+        let deadline = self.clock.now() + timeout;
+        self.poll_rx_start(deadline).await?;
+        self.receive_queued().await
+    }
+
+    /// Wait until `deadline` for the transceiver to receive a packet.
+    async fn await_rx_start(&mut self, deadline: Timestamp) -> Result<(), Cc1101WrapperError> {
+        let Some(events) = self.events else {
+            return self.poll_rx_start(deadline).await;
+        };
+
+        // End of a packet, or RX FIFO filled above the threshold by a larger one. Packets
+        // shorter than the threshold do not end in infinite packet length mode, they are polled.
+        let infinite = self.profile.packet_length == PacketLength::Infinite;
         loop {
-            match rx_state {
-                RxState::Waiting => {
-                    Systick::delay(fugit::ExtU64::millis(5)).await;
+            let mut timeout = self.millis_until(deadline);
+            if infinite {
+                timeout = timeout.min(self.airtime_ms(FIFO_THRESHOLD_BYTES));
+            }
 
-                    let packet_status = self.cc1101.get_packet_status()?;
-                    if packet_status.sof_delimiter {
-                        rx_state = RxState::Receiving;
-                    }
+            if wait_until(
+                events,
+                &[Gdo::Gdo0, Gdo::Gdo2],
+                self.clock.delay_ms(timeout),
+            )
+            .await
+            {
+                return Ok(());
+            }
+            if infinite && self.cc1101.get_rx_bytes()? > 0 {
+                return Ok(());
+            }
+            if self.clock.now() >= deadline {
+                return Err(Cc1101WrapperError::TimeoutError);
+            }
+        }
+    }
+
+    async fn poll_rx_start(&mut self, deadline: Timestamp) -> Result<(), Cc1101WrapperError> {
+        loop {
+            if self.clock.now() >= deadline {
+                return Err(Cc1101WrapperError::TimeoutError);
+            }
+
+            self.clock.delay_ms(5).await;
+
+            let packet_status = self.cc1101.get_packet_status()?;
+            if packet_status.sof_delimiter || self.cc1101.get_rx_bytes()? > 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Receive the packet signalled by the transceiver into the internal Rx Queue.
+    async fn receive_queued(&mut self) -> Result<(), Cc1101WrapperError> {
+        let mut packet = RxPacket::default();
+        let Some(frame) = self.receive_frame(&mut packet.data).await? else {
+            return Ok(());
+        };
+
+        // Store received data
+        packet.source = frame.source;
+        packet.length = frame.length as u8;
+        packet.rssi = frame.rssi;
+        packet.lqi = frame.lqi;
+
+        self.rx_queue
+            .push_back(packet)
+            .map_err(|_| Cc1101WrapperError::RxQueueFull)
+    }
+
+    /// Read the packet being received into `data`. Returns None if the transceiver holds no
+    /// packet, e.g. after it was discarded by the address filter or flushed on CRC mismatch.
+    /// On error the rest of the packet is dropped.
+    async fn receive_frame(
+        &mut self,
+        data: &mut [u8],
+    ) -> Result<Option<RxFrame>, Cc1101WrapperError> {
+        let result = self.drain_frame(data).await;
+
+        if result.is_err() {
+            // Start Idle state, recovering from a RX FIFO overflow
+            self.start_idle_state().await;
+
+            // Flush FIFO RX
+            let result = self.cc1101.flush_rx_fifo_buffer();
+            self.process_result(result);
+        }
+
+        // The end of an infinite packet is received in fixed packet length mode
+        if self.profile.packet_length == PacketLength::Infinite {
+            let result = self.cc1101.set_packet_length(PacketLength::Infinite);
+            self.process_result(result);
+        }
+
+        result
+    }
+
+    /// Read the packet being received into `data`, draining the RX FIFO as it fills for
+    /// packets larger than the FIFO.
+    async fn drain_frame(
+        &mut self,
+        data: &mut [u8],
+    ) -> Result<Option<RxFrame>, Cc1101WrapperError> {
+        let mut header = [0; 3];
+        let (header_len, mut total) = match self.profile.packet_length {
+            PacketLength::Variable(_) => (3, None),
+            PacketLength::Fixed(length) => (0, Some(length as usize)),
+            PacketLength::Infinite => (2, None),
+        };
+        if total.is_some_and(|total| total > data.len()) {
+            return Err(Cc1101WrapperError::InvalidLength);
+        }
+        let mut deadline = self.clock.now() + self.packet_timeout(FIFO_SIZE_MAX as usize);
+        let mut read = 0;
+        let mut fixed_length = self.profile.packet_length != PacketLength::Infinite;
+
+        loop {
+            // Packet status first, so that the RX FIFO holds the whole packet once received
+            let receiving = self.cc1101.get_packet_status()?.sof_delimiter;
+            let rxbytes = self.cc1101.get_rx_bytes()?;
+            if rxbytes > FIFO_SIZE_MAX {
+                return Err(Cc1101WrapperError::RxOverflow);
+            }
+            let available = rxbytes as usize;
+
+            if !receiving && available == 0 {
+                if read == 0 {
+                    return Ok(None);
                 }
-                RxState::Receiving => {
-                    Systick::delay(fugit::ExtU64::millis(1)).await;
-
-                    let num_rxbytes = self.cc1101.get_rx_bytes()?;
-                    if (num_rxbytes > 0) && (num_rxbytes == last_rxbytes) {
-                        let packet_status = self.cc1101.get_packet_status()?;
-                        if packet_status.crc_ok {
-                            rx_state = RxState::Received;
-                        } else {
-                            rx_state = RxState::Error;
-                        }
-                    }
-                    last_rxbytes = num_rxbytes;
-                    if last_rxbytes > FIFO_SIZE_MAX {
-                        // TODO: Check how to treat this case in a reliable way
-                        last_rxbytes = FIFO_SIZE_MAX;
-                        // rx_state = RxState::Error;
-                    }
+                // Rest of the packet flushed on CRC mismatch
+                return Err(Cc1101WrapperError::CrcMismatch);
+            }
+
+            // The packet ends when the byte counter modulo 256 reaches the packet length, then
+            // the CRC is checked. Switch to fixed packet length once less than 256 bytes are
+            // left, written first as when transmitting.
+            if let (false, Some(total)) = (fixed_length, total) {
+                if total - (read + available).min(total) <= 255 {
+                    self.cc1101.write_register(PKTLEN, total as u8)?;
+                    self.cc1101
+                        .set_packet_length(PacketLength::Fixed(total as u8))?;
+                    fixed_length = true;
                 }
-                RxState::Received => {
-                    let mut length: Option<u8> = None;
-                    let mut address: Option<u8> = None;
-                    let mut rssi: Option<i16> = None;
-                    let mut lqi: Option<u8> = None;
-
-                    self.cc1101.read_data(
-                        &mut length,
-                        &mut address,
-                        &mut rssi,
-                        &mut lqi,
-                        &mut self.rx_data.data[0..(last_rxbytes as usize)],
-                    )?;
-
-                    // Store received data
-                    self.rx_data.length = last_rxbytes; // Fixed Length?
-
-                    // self.rx_data.length = length.unwrap() - 1; // Minus address byte
-                    // self.rx_data.address = address.unwrap();
-                    self.rx_data.address = 0;
-                    self.last_rx_rssi = self.cc1101.get_rssi_dbm()?;
-                    self.last_rx_lqi = self.cc1101.get_lqi()?;
-                    break;
+            }
+
+            // Header bytes first, giving the length of the packet
+            let end = total.unwrap_or(header_len);
+            let mut length = available.min(end - read);
+            if receiving && length == available && read + length < end {
+                // The last byte in the RX FIFO shall not be read while it is being received
+                length = length.saturating_sub(1);
+            }
+            self.read_fifo_bytes(&mut header[..header_len], data, read..read + length)?;
+            read += length;
+
+            if total.is_none() && read == header_len {
+                let length = match self.profile.packet_length {
+                    // Length field counts the destination and source address bytes
+                    PacketLength::Variable(_) => (header[0] as usize)
+                        .checked_sub(2)
+                        .ok_or(Cc1101WrapperError::InvalidLength)?,
+                    _ => u16::from_be_bytes([header[0], header[1]]) as usize,
+                };
+                if length > data.len() {
+                    return Err(Cc1101WrapperError::InvalidLength);
                 }
-                RxState::Error => {
-                    return Err(Cc1101WrapperError::CrcMismatch);
+                // No packet length to end an infinite packet of a multiple of 256 bytes
+                if !fixed_length && (header_len + length) as u8 == 0 {
+                    return Err(Cc1101WrapperError::InvalidLength);
                 }
+
+                total = Some(header_len + length);
+                deadline = self.clock.now() + self.packet_timeout(length);
+                continue;
             }
-        }
 
-        Ok(rx_state)
-    }
+            if total == Some(read) {
+                break;
+            }
+            if length + 1 < available {
+                continue;
+            }
 
-    fn receive_interrupt(&mut self) -> Result<(), Cc1101WrapperError> {
-        let mut length: Option<u8> = None;
-        let mut address: Option<u8> = None;
-        let mut rssi: Option<i16> = None;
-        let mut lqi: Option<u8> = None;
+            self.await_fifo(deadline).await?;
+        }
 
-        let rxbytes = self.cc1101.get_rx_bytes()?;
-        let packet_status = self.cc1101.get_packet_status()?;
+        if self.profile.crc {
+            // CRC is checked at the end of packet
+            loop {
+                let packet_status = self.cc1101.get_packet_status()?;
+                if !packet_status.sof_delimiter {
+                    if !packet_status.crc_ok {
+                        return Err(Cc1101WrapperError::CrcMismatch);
+                    }
+                    break;
+                }
+                if self.clock.now() >= deadline {
+                    return Err(Cc1101WrapperError::TimeoutError);
+                }
+                self.clock.delay_ms(1).await;
+            }
+        }
 
-        if packet_status.crc_ok {
-            self.cc1101.read_data(
-                &mut length,
-                &mut address,
-                &mut rssi,
-                &mut lqi,
-                &mut self.rx_data.data[0..(rxbytes as usize)],
-            )?;
+        // End of packet already handled
+        self.discard_event(Gdo::Gdo2);
 
-            // Store received data
-            self.rx_data.ready = true;
-            self.rx_data.length = rxbytes;
-            self.rx_data.address = 0;
+        let source = match self.profile.packet_length {
+            PacketLength::Variable(_) => header[2],
+            _ => BROADCAST_ADDRESS,
+        };
 
-            self.last_rx_rssi = self.cc1101.get_rssi_dbm()?;
-            self.last_rx_lqi = self.cc1101.get_lqi()?;
-        } else {
-            return Err(Cc1101WrapperError::CrcMismatch);
+        let rssi = self.cc1101.get_rssi_dbm()?;
+        let lqi = self.cc1101.get_lqi()?;
+        let now = self.clock.now();
+        self.link.packet_received(rssi, lqi, now);
+
+        // Follow the carrier of the received packets
+        let freqest = self.cc1101.get_freq_estimate()?;
+        if self.frequency.update(freqest) {
+            self.cc1101.set_freq_offset(self.frequency.freqoff())?;
         }
 
-        Ok(())
+        Ok(Some(RxFrame {
+            length: read - header_len,
+            source,
+            rssi,
+            lqi,
+        }))
     }
 
     /// Store error
     fn store_error(&mut self, error: Cc1101WrapperError) {
+        self.link.error(error);
+        self.recovery.error(error);
         self.last_error = Some(error);
         self.error_count += 1;
     }
@@ -515,8 +1836,8 @@ where
     async fn check_machine_state(
         &mut self,
         target_state: MachineState,
+        deadline: Timestamp,
     ) -> Result<(), Cc1101WrapperError> {
-        let delay = fugit::ExtU64::micros(1000);
         loop {
             let machine_state = self.cc1101.get_machine_state()?;
 
@@ -534,7 +1855,11 @@ where
                 /* Ignore other states */
             }
 
-            Systick::delay(delay).await;
+            if self.clock.now() >= deadline {
+                return Err(Cc1101WrapperError::TimeoutError);
+            }
+
+            self.clock.delay_ms(1).await;
         }
     }
 
@@ -543,10 +1868,8 @@ where
         target_state: MachineState,
         timeout: Duration<u64, 1, 1000>,
     ) -> Result<(), Cc1101WrapperError> {
-        match Systick::timeout_after(timeout, self.check_machine_state(target_state)).await {
-            Ok(result) => result,
-            Err(_) => Err(Cc1101WrapperError::TimeoutError),
-        }
+        let deadline = self.clock.now() + timeout;
+        self.check_machine_state(target_state, deadline).await
     }
 
     /// Set Radio Mode.
@@ -557,8 +1880,20 @@ where
     ) -> Result<(), Cc1101WrapperError> {
         // Set "Idle" mode before going into any other mode
         self.cc1101.exit_rx_tx()?;
-        self.await_machine_state(MachineState::IDLE, timeout)
-            .await?;
+        match self.await_machine_state(MachineState::IDLE, timeout).await {
+            // FIFO errors are only left by flushing the FIFO
+            Err(Cc1101WrapperError::RxOverflow) => {
+                self.cc1101.flush_rx_fifo_buffer()?;
+                self.await_machine_state(MachineState::IDLE, timeout)
+                    .await?;
+            }
+            Err(Cc1101WrapperError::TxUnderflow) => {
+                self.cc1101.flush_tx_fifo_buffer()?;
+                self.await_machine_state(MachineState::IDLE, timeout)
+                    .await?;
+            }
+            result => result?,
+        }
 
         match radio_mode {
             RadioMode::Idle => {
@@ -0,0 +1,172 @@
+use cc1101::ModulationFormat;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PowerError {
+    /// Output power is outside of -30..=10 dBm
+    OutOfRange,
+    /// Power ramp is longer than the 7 steps of the PATABLE
+    RampTooLong,
+}
+
+/// Output power levels of the transceiver [dBm]
+pub const POWER_LEVELS_DBM: [i8; 8] = [-30, -20, -15, -10, 0, 5, 7, 10];
+
+// PATABLE values of the power levels per frequency band, from TI Design Note DN013
+const PATABLE_315: [u8; 8] = [0x12, 0x0D, 0x1C, 0x34, 0x51, 0x85, 0xCB, 0xC2];
+const PATABLE_433: [u8; 8] = [0x12, 0x0E, 0x1D, 0x34, 0x60, 0x84, 0xC8, 0xC0];
+const PATABLE_868: [u8; 8] = [0x03, 0x0F, 0x1E, 0x27, 0x50, 0x81, 0xCB, 0xC2];
+const PATABLE_915: [u8; 8] = [0x03, 0x0E, 0x1E, 0x27, 0x8E, 0xCD, 0xC7, 0xC0];
+
+/// Size of the PATABLE
+pub const PATABLE_SIZE: usize = 8;
+
+/// TX output power configuration
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PowerConfig {
+    /// Output power [dBm]. The highest level of `POWER_LEVELS_DBM` not above it is used.
+    pub dbm: i8,
+    /// Number of steps ramping the power up and down at each ASK/OOK symbol, up to 7. Without
+    /// ramping the carrier is switched on and off. Not used by the other modulations.
+    pub ramp_steps: u8,
+}
+
+impl PowerConfig {
+    /// Project default: highest output power, without ramping
+    pub const PROJECT: Self = Self {
+        dbm: 10,
+        ramp_steps: 0,
+    };
+
+    /// Check the configuration against the CC1101 capabilities.
+    pub fn validate(&self) -> Result<(), PowerError> {
+        if !(-30..=10).contains(&self.dbm) {
+            return Err(PowerError::OutOfRange);
+        }
+        if self.ramp_steps as usize >= PATABLE_SIZE {
+            return Err(PowerError::RampTooLong);
+        }
+
+        Ok(())
+    }
+
+    /// Output power level used [dBm]
+    pub fn level_dbm(&self) -> i8 {
+        POWER_LEVELS_DBM[self.level()]
+    }
+
+    fn level(&self) -> usize {
+        POWER_LEVELS_DBM
+            .iter()
+            .rposition(|&level| level <= self.dbm)
+            .unwrap_or(0)
+    }
+
+    /// PATABLE content and index of its last entry used (FREND0.PA_POWER), for the carrier
+    /// `frequency` [Hz] and the `modulation`.
+    pub(crate) fn patable(
+        &self,
+        frequency: u64,
+        modulation: ModulationFormat,
+    ) -> ([u8; PATABLE_SIZE], u8) {
+        let values = match frequency {
+            0..=348_000_000 => &PATABLE_315,
+            348_000_001..=464_000_000 => &PATABLE_433,
+            464_000_001..=899_999_999 => &PATABLE_868,
+            _ => &PATABLE_915,
+        };
+        let level = self.level();
+        let mut patable = [0; PATABLE_SIZE];
+
+        if modulation != ModulationFormat::OnOffKeying {
+            patable[0] = values[level];
+            return (patable, 0);
+        }
+
+        // First entry is the carrier off, the last one the output power, the ramp climbs the
+        // power levels in between
+        let steps = self.ramp_steps.max(1) as usize;
+        for (step, entry) in patable.iter_mut().enumerate().take(steps + 1).skip(1) {
+            *entry = values[level * step / steps];
+        }
+
+        (patable, steps as u8)
+    }
+}
+
+impl Default for PowerConfig {
+    fn default() -> Self {
+        Self::PROJECT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn power_outside_of_the_range_is_rejected() {
+        let config = |dbm, ramp_steps| PowerConfig { dbm, ramp_steps };
+        assert_eq!(PowerConfig::PROJECT.validate(), Ok(()));
+        assert_eq!(config(-30, 7).validate(), Ok(()));
+        assert_eq!(config(-31, 0).validate(), Err(PowerError::OutOfRange));
+        assert_eq!(config(11, 0).validate(), Err(PowerError::OutOfRange));
+        assert_eq!(config(0, 8).validate(), Err(PowerError::RampTooLong));
+    }
+
+    #[test]
+    fn highest_level_not_above_the_power_is_used() {
+        let level = |dbm| PowerConfig { dbm, ramp_steps: 0 }.level_dbm();
+        assert_eq!(level(-30), -30);
+        assert_eq!(level(-21), -30);
+        assert_eq!(level(6), 5);
+        assert_eq!(level(10), 10);
+    }
+
+    #[test]
+    fn patable_follows_the_frequency_band() {
+        let fsk = ModulationFormat::BinaryFrequencyShiftKeying;
+        let config = PowerConfig {
+            dbm: 0,
+            ramp_steps: 0,
+        };
+        for (frequency, value) in [
+            (315_000_000, 0x51),
+            (433_000_000, 0x60),
+            (868_000_000, 0x50),
+            (915_000_000, 0x8E),
+        ] {
+            let (patable, pa_power) = config.patable(frequency, fsk);
+            assert_eq!(patable, [value, 0, 0, 0, 0, 0, 0, 0]);
+            assert_eq!(pa_power, 0);
+        }
+    }
+
+    #[test]
+    fn ook_switches_between_off_and_the_power() {
+        let (patable, pa_power) =
+            PowerConfig::PROJECT.patable(433_000_000, ModulationFormat::OnOffKeying);
+        assert_eq!(patable, [0, 0xC0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(pa_power, 1);
+    }
+
+    #[test]
+    fn ook_ramp_climbs_the_power_levels() {
+        let ook = ModulationFormat::OnOffKeying;
+        let config = PowerConfig {
+            dbm: 10,
+            ramp_steps: 7,
+        };
+        let (patable, pa_power) = config.patable(433_000_000, ook);
+        assert_eq!(patable, [0, 0x0E, 0x1D, 0x34, 0x60, 0x84, 0xC8, 0xC0]);
+        assert_eq!(pa_power, 7);
+
+        // Steps spread over the levels up to 0 dBm
+        let config = PowerConfig {
+            dbm: 0,
+            ramp_steps: 2,
+        };
+        let (patable, pa_power) = config.patable(433_000_000, ook);
+        assert_eq!(patable, [0, 0x1D, 0x60, 0, 0, 0, 0, 0]);
+        assert_eq!(pa_power, 2);
+    }
+}
@@ -0,0 +1,61 @@
+use crate::Cc1101WrapperError;
+use embedded_hal::spi::SpiDevice;
+
+/// PARTNUM of the CC1101
+pub const CC1101_PARTNUM: u8 = 0x00;
+/// VERSION of the known CC1101 silicon revisions
+pub const CC1101_VERSIONS: [u8; 3] = [0x04, 0x14, 0x17];
+
+// SPI header bits and register addresses
+const HEADER_READ: u8 = 0x80;
+const HEADER_BURST: u8 = 0x40;
+const REG_ADDR: u8 = 0x09;
+const REG_PARTNUM: u8 = 0x30;
+const REG_VERSION: u8 = 0x31;
+
+// Readback patterns, toggling every bit of the register
+const PROBE_PATTERNS: [u8; 2] = [0x55, 0xAA];
+
+/// Check the PARTNUM and VERSION read from the transceiver.
+pub fn check_hw_info(partnum: u8, version: u8) -> Result<(), Cc1101WrapperError> {
+    if partnum == CC1101_PARTNUM && CC1101_VERSIONS.contains(&version) {
+        Ok(())
+    } else {
+        Err(Cc1101WrapperError::UnknownChip(partnum, version))
+    }
+}
+
+/// Verify the presence of a CC1101 on `spi` before handing it over to the driver: read the
+/// chip identity, then write and read back a register. Returns the PARTNUM and VERSION.
+pub fn probe<SPI: SpiDevice<u8>>(spi: &mut SPI) -> Result<(u8, u8), Cc1101WrapperError> {
+    let partnum = read_register(spi, REG_PARTNUM | HEADER_BURST)?;
+    let version = read_register(spi, REG_VERSION | HEADER_BURST)?;
+    check_hw_info(partnum, version)?;
+
+    let address = read_register(spi, REG_ADDR)?;
+    for pattern in PROBE_PATTERNS {
+        write_register(spi, REG_ADDR, pattern)?;
+        if read_register(spi, REG_ADDR)? != pattern {
+            return Err(Cc1101WrapperError::SpiIntegrity);
+        }
+    }
+    write_register(spi, REG_ADDR, address)?;
+
+    Ok((partnum, version))
+}
+
+fn read_register<SPI: SpiDevice<u8>>(spi: &mut SPI, header: u8) -> Result<u8, Cc1101WrapperError> {
+    let mut buffer = [header | HEADER_READ, 0];
+    spi.transfer_in_place(&mut buffer)
+        .map_err(|_| Cc1101WrapperError::Spi)?;
+    Ok(buffer[1])
+}
+
+fn write_register<SPI: SpiDevice<u8>>(
+    spi: &mut SPI,
+    addr: u8,
+    value: u8,
+) -> Result<(), Cc1101WrapperError> {
+    spi.write(&[addr, value])
+        .map_err(|_| Cc1101WrapperError::Spi)
+}
@@ -0,0 +1,374 @@
+use crate::BROADCAST_ADDRESS;
+use cc1101::{AddressFilter, ModulationFormat, NumPreamble, PacketLength, SyncMode, FIFO_SIZE_MAX};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ProfileError {
+    /// Carrier frequency is outside of the CC1101 bands (300-348, 387-464, 779-928 MHz)
+    FrequencyOutOfBand,
+    /// Data rate is not supported by the selected modulation
+    DataRateOutOfRange,
+    /// Frequency deviation is outside of 1.587-380.859 kHz
+    DeviationOutOfRange,
+    /// Channel bandwidth is outside of 58-812 kHz
+    ChannelBandwidthOutOfRange,
+    /// Channel bandwidth is narrower than the signal bandwidth (data rate + 2 * deviation)
+    BandwidthTooNarrow,
+    /// Packet length is zero, or leaves no room for the address bytes
+    PacketLengthOutOfRange,
+    /// Forward Error Correction is only supported with fixed packet length
+    FecRequiresFixedLength,
+    /// Address filtering needs the address bytes of the variable packet length mode
+    AddressFilterRequiresVariableLength,
+    /// Channel spacing is outside of 25.391-405.456 kHz
+    ChannelSpacingOutOfRange,
+    /// Channel plan is empty, or its last channel is outside of the band of the carrier
+    ChannelPlanOutOfBand,
+}
+
+/// Radio configuration of the CC1101 transceiver.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RadioProfile {
+    /// Carrier frequency of channel 0 [Hz]
+    pub frequency: u64,
+    /// Spacing between the channels [Hz]
+    pub channel_spacing: u64,
+    /// Number of channels of the channel plan, from the carrier frequency upwards
+    pub channels: u8,
+    /// Intermediate frequency [Hz]
+    pub freq_if: u64,
+    /// Data rate [Baud]
+    pub data_rate: u64,
+    /// Frequency deviation [Hz], for the frequency shift keying modulations
+    pub deviation: u64,
+    /// Channel filter bandwidth [Hz]
+    pub chanbw: u64,
+    pub modulation: ModulationFormat,
+    pub num_preamble: NumPreamble,
+    pub sync_mode: SyncMode,
+    /// Fixed or variable packet length up to 255 bytes, or infinite packet length with the
+    /// data length sent in the first two bytes
+    pub packet_length: PacketLength,
+    pub address_filter: AddressFilter,
+    /// Append and check a CRC on each packet
+    pub crc: bool,
+    /// Flush the RX FIFO on CRC mismatch
+    pub crc_autoflush: bool,
+    /// Data whitening
+    pub whitening: bool,
+    /// Forward Error Correction with interleaving
+    pub fec: bool,
+}
+
+impl RadioProfile {
+    /// Project default: 433 MHz, 38.383 kBaud 2-FSK
+    pub const UHF_38K4: Self = Self {
+        frequency: 433_000_000,
+        channel_spacing: 200_000,
+        channels: 1,
+        freq_if: 203_125,
+        data_rate: 38_383,
+        deviation: 20_629,
+        chanbw: 101_562,
+        modulation: ModulationFormat::BinaryFrequencyShiftKeying,
+        num_preamble: NumPreamble::Eight,
+        sync_mode: SyncMode::MatchFull(0xCAFE),
+        packet_length: PacketLength::Fixed(FIFO_SIZE_MAX),
+        address_filter: AddressFilter::Disabled,
+        crc: true,
+        crc_autoflush: true,
+        whitening: false,
+        fec: false,
+    };
+
+    /// Long range link: 433 MHz, 1.2 kBaud GFSK with FEC and whitening
+    pub const UHF_1K2_ROBUST: Self = Self {
+        frequency: 433_000_000,
+        channel_spacing: 200_000,
+        channels: 1,
+        freq_if: 152_343,
+        data_rate: 1_199,
+        deviation: 5_157,
+        chanbw: 58_035,
+        modulation: ModulationFormat::GaussianFrequencyShiftKeying,
+        num_preamble: NumPreamble::Eight,
+        sync_mode: SyncMode::MatchFull(0xCAFE),
+        packet_length: PacketLength::Fixed(FIFO_SIZE_MAX),
+        address_filter: AddressFilter::Disabled,
+        crc: true,
+        crc_autoflush: true,
+        whitening: true,
+        fec: true,
+    };
+
+    /// Short range, high throughput link for test benches: 433 MHz, 250 kBaud GFSK
+    pub const UHF_250K: Self = Self {
+        frequency: 433_000_000,
+        channel_spacing: 200_000,
+        channels: 1,
+        freq_if: 304_687,
+        data_rate: 249_939,
+        deviation: 126_953,
+        chanbw: 541_666,
+        modulation: ModulationFormat::GaussianFrequencyShiftKeying,
+        num_preamble: NumPreamble::Eight,
+        sync_mode: SyncMode::MatchFull(0xCAFE),
+        packet_length: PacketLength::Fixed(FIFO_SIZE_MAX),
+        address_filter: AddressFilter::Disabled,
+        crc: true,
+        crc_autoflush: true,
+        whitening: true,
+        fec: false,
+    };
+
+    /// Check the profile against the CC1101 capabilities.
+    pub fn validate(&self) -> Result<(), ProfileError> {
+        let Some(profile_band) = band(self.frequency) else {
+            return Err(ProfileError::FrequencyOutOfBand);
+        };
+
+        if !(25_391..=405_456).contains(&self.channel_spacing) {
+            return Err(ProfileError::ChannelSpacingOutOfRange);
+        }
+        let last_channel = self.channel_frequency(self.channels.saturating_sub(1));
+        if self.channels == 0 || band(last_channel) != Some(profile_band) {
+            return Err(ProfileError::ChannelPlanOutOfBand);
+        }
+
+        let data_rate_range = match self.modulation {
+            ModulationFormat::BinaryFrequencyShiftKeying => 600..=500_000,
+            ModulationFormat::MinimumShiftKeying => 26_000..=500_000,
+            ModulationFormat::FourFrequencyShiftKeying => 600..=300_000,
+            _ => 600..=250_000,
+        };
+        if !data_rate_range.contains(&self.data_rate) {
+            return Err(ProfileError::DataRateOutOfRange);
+        }
+
+        let fsk = !matches!(self.modulation, ModulationFormat::OnOffKeying);
+        if fsk && !(1_587..=380_859).contains(&self.deviation) {
+            return Err(ProfileError::DeviationOutOfRange);
+        }
+
+        if !(58_035..=812_500).contains(&self.chanbw) {
+            return Err(ProfileError::ChannelBandwidthOutOfRange);
+        }
+        let signal_bandwidth = self.data_rate + if fsk { 2 * self.deviation } else { 0 };
+        if self.chanbw < signal_bandwidth {
+            return Err(ProfileError::BandwidthTooNarrow);
+        }
+
+        let variable = matches!(self.packet_length, PacketLength::Variable(_));
+        if !variable && !matches!(self.address_filter, AddressFilter::Disabled) {
+            return Err(ProfileError::AddressFilterRequiresVariableLength);
+        }
+
+        match self.packet_length {
+            PacketLength::Fixed(0) => return Err(ProfileError::PacketLengthOutOfRange),
+            PacketLength::Fixed(_) => {}
+            // Room for the destination and source address bytes
+            PacketLength::Variable(max_length) if max_length < 3 => {
+                return Err(ProfileError::PacketLengthOutOfRange);
+            }
+            PacketLength::Variable(_) | PacketLength::Infinite if self.fec => {
+                return Err(ProfileError::FecRequiresFixedLength);
+            }
+            PacketLength::Variable(_) | PacketLength::Infinite => {}
+        }
+
+        Ok(())
+    }
+
+    /// Carrier frequency of a channel [Hz]
+    pub fn channel_frequency(&self, channel: u8) -> u64 {
+        self.frequency + channel as u64 * self.channel_spacing
+    }
+
+    /// Address of this node given by the address filter, sent as source address.
+    pub fn device_address(&self) -> u8 {
+        match self.address_filter {
+            AddressFilter::Device(address)
+            | AddressFilter::DeviceLowBroadcast(address)
+            | AddressFilter::DeviceHighLowBroadcast(address) => address,
+            _ => BROADCAST_ADDRESS,
+        }
+    }
+}
+
+/// Index of the CC1101 band of a frequency
+fn band(frequency: u64) -> Option<usize> {
+    match frequency {
+        300_000_000..=348_000_000 => Some(0),
+        387_000_000..=464_000_000 => Some(1),
+        779_000_000..=928_000_000 => Some(2),
+        _ => None,
+    }
+}
+
+impl Default for RadioProfile {
+    fn default() -> Self {
+        Self::UHF_38K4
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile_with(f: impl FnOnce(&mut RadioProfile)) -> RadioProfile {
+        let mut profile = RadioProfile::UHF_38K4;
+        f(&mut profile);
+        profile
+    }
+
+    #[test]
+    fn presets_are_valid() {
+        assert_eq!(RadioProfile::UHF_38K4.validate(), Ok(()));
+        assert_eq!(RadioProfile::UHF_1K2_ROBUST.validate(), Ok(()));
+        assert_eq!(RadioProfile::UHF_250K.validate(), Ok(()));
+    }
+
+    #[test]
+    fn frequency_outside_of_the_bands_is_rejected() {
+        let profile = profile_with(|p| p.frequency = 370_000_000);
+        assert_eq!(profile.validate(), Err(ProfileError::FrequencyOutOfBand));
+    }
+
+    #[test]
+    fn data_rate_outside_of_the_modulation_range_is_rejected() {
+        let profile = profile_with(|p| p.data_rate = 599);
+        assert_eq!(profile.validate(), Err(ProfileError::DataRateOutOfRange));
+
+        // Supported by 2-FSK, not by MSK
+        let profile = profile_with(|p| p.data_rate = 10_000);
+        assert_eq!(profile.validate(), Ok(()));
+        let profile = profile_with(|p| {
+            p.data_rate = 10_000;
+            p.modulation = ModulationFormat::MinimumShiftKeying;
+        });
+        assert_eq!(profile.validate(), Err(ProfileError::DataRateOutOfRange));
+    }
+
+    #[test]
+    fn deviation_outside_of_the_range_is_rejected() {
+        let profile = profile_with(|p| p.deviation = 1_000);
+        assert_eq!(profile.validate(), Err(ProfileError::DeviationOutOfRange));
+
+        // Not used by OOK
+        let profile = profile_with(|p| {
+            p.modulation = ModulationFormat::OnOffKeying;
+            p.deviation = 0;
+        });
+        assert_eq!(profile.validate(), Ok(()));
+    }
+
+    #[test]
+    fn channel_bandwidth_outside_of_the_range_is_rejected() {
+        let profile = profile_with(|p| p.chanbw = 900_000);
+        assert_eq!(
+            profile.validate(),
+            Err(ProfileError::ChannelBandwidthOutOfRange)
+        );
+    }
+
+    #[test]
+    fn channel_bandwidth_narrower_than_the_signal_is_rejected() {
+        // 38.4 kBaud + 2 * 20.6 kHz deviation does not fit in 60 kHz
+        let profile = profile_with(|p| p.chanbw = 60_000);
+        assert_eq!(profile.validate(), Err(ProfileError::BandwidthTooNarrow));
+    }
+
+    #[test]
+    fn packet_length_without_room_is_rejected() {
+        let profile = profile_with(|p| p.packet_length = PacketLength::Fixed(0));
+        assert_eq!(
+            profile.validate(),
+            Err(ProfileError::PacketLengthOutOfRange)
+        );
+
+        let profile = profile_with(|p| p.packet_length = PacketLength::Variable(2));
+        assert_eq!(
+            profile.validate(),
+            Err(ProfileError::PacketLengthOutOfRange)
+        );
+
+        let profile = profile_with(|p| p.packet_length = PacketLength::Variable(3));
+        assert_eq!(profile.validate(), Ok(()));
+    }
+
+    #[test]
+    fn fec_without_fixed_length_is_rejected() {
+        let profile = profile_with(|p| {
+            p.fec = true;
+            p.packet_length = PacketLength::Variable(FIFO_SIZE_MAX);
+        });
+        assert_eq!(
+            profile.validate(),
+            Err(ProfileError::FecRequiresFixedLength)
+        );
+
+        let profile = profile_with(|p| {
+            p.fec = true;
+            p.packet_length = PacketLength::Infinite;
+        });
+        assert_eq!(
+            profile.validate(),
+            Err(ProfileError::FecRequiresFixedLength)
+        );
+    }
+
+    #[test]
+    fn address_filter_without_variable_length_is_rejected() {
+        let profile = profile_with(|p| p.address_filter = AddressFilter::Device(0x12));
+        assert_eq!(
+            profile.validate(),
+            Err(ProfileError::AddressFilterRequiresVariableLength)
+        );
+
+        let profile = profile_with(|p| {
+            p.address_filter = AddressFilter::Device(0x12);
+            p.packet_length = PacketLength::Variable(FIFO_SIZE_MAX);
+        });
+        assert_eq!(profile.validate(), Ok(()));
+    }
+
+    #[test]
+    fn channel_spacing_outside_of_the_range_is_rejected() {
+        let profile = profile_with(|p| p.channel_spacing = 25_000);
+        assert_eq!(
+            profile.validate(),
+            Err(ProfileError::ChannelSpacingOutOfRange)
+        );
+    }
+
+    #[test]
+    fn channel_plan_leaving_the_band_is_rejected() {
+        let profile = profile_with(|p| p.channels = 0);
+        assert_eq!(profile.validate(), Err(ProfileError::ChannelPlanOutOfBand));
+
+        // Channel 155 is at 464.0 MHz, the upper edge of the band
+        let profile = profile_with(|p| p.channels = 156);
+        assert_eq!(profile.validate(), Ok(()));
+        let profile = profile_with(|p| p.channels = 157);
+        assert_eq!(profile.validate(), Err(ProfileError::ChannelPlanOutOfBand));
+    }
+
+    #[test]
+    fn channel_frequency_is_spaced_from_the_carrier() {
+        let profile = profile_with(|p| p.channels = 10);
+        assert_eq!(profile.channel_frequency(0), 433_000_000);
+        assert_eq!(profile.channel_frequency(9), 434_800_000);
+    }
+
+    #[test]
+    fn device_address_is_taken_from_the_address_filter() {
+        assert_eq!(RadioProfile::UHF_38K4.device_address(), BROADCAST_ADDRESS);
+        for filter in [
+            AddressFilter::Device(0x12),
+            AddressFilter::DeviceLowBroadcast(0x12),
+            AddressFilter::DeviceHighLowBroadcast(0x12),
+        ] {
+            let profile = profile_with(|p| p.address_filter = filter);
+            assert_eq!(profile.device_address(), 0x12);
+        }
+    }
+}
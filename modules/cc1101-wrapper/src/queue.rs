@@ -0,0 +1,95 @@
+use crate::Cc1101WrapperError;
+use core::cmp::Ordering;
+
+/// Default depth of the transmit queue
+pub const TX_QUEUE_DEPTH: usize = 4;
+/// Default depth of the receive queue
+pub const RX_QUEUE_DEPTH: usize = 4;
+/// Largest data length of the queued packets
+pub const MAX_PACKET_LENGTH: usize = 255;
+
+/// Identifier of a queued packet, reported in its completion notification
+pub type TxId = u32;
+
+/// Priority of a queued packet. Higher priorities are sent first, packets of the same
+/// priority are sent in order.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd)]
+pub enum TxPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+/// Outcome of a transmission
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TxResult {
+    /// The packet was sent
+    Sent,
+    /// The channel was busy at every attempt, the packet was not sent
+    ChannelBusy,
+    /// The transmission failed
+    Failed(Cc1101WrapperError),
+}
+
+/// Completion notification of a queued packet
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TxCompletion {
+    pub id: TxId,
+    pub result: TxResult,
+}
+
+/// Packet waiting in the transmit queue
+pub(crate) struct TxPacket {
+    pub id: TxId,
+    pub priority: TxPriority,
+    pub notify: bool,
+    pub destination: u8,
+    pub source: u8,
+    pub length: u8,
+    pub data: [u8; MAX_PACKET_LENGTH],
+}
+
+impl Ord for TxPacket {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Max heap: highest priority first, then lowest id
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.id.cmp(&self.id))
+    }
+}
+
+impl PartialOrd for TxPacket {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for TxPacket {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for TxPacket {}
+
+/// Packet waiting in the receive queue
+pub(crate) struct RxPacket {
+    pub source: u8,
+    pub length: u8,
+    pub rssi: i16,
+    pub lqi: u8,
+    pub data: [u8; MAX_PACKET_LENGTH],
+}
+
+impl Default for RxPacket {
+    fn default() -> Self {
+        Self {
+            source: 0,
+            length: 0,
+            rssi: 0,
+            lqi: 0,
+            data: [0; MAX_PACKET_LENGTH],
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use crate::clock::Timestamp;
+use crate::Cc1101WrapperError;
+use heapless::Deque;
+
+/// Number of recovery records kept in the history
+pub const RECOVERY_HISTORY_DEPTH: usize = 8;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RecoveryError {
+    /// Check period is zero
+    InvalidPeriod,
+    /// Thresholds are zero, or lower than the threshold of the previous action
+    InvalidThresholds,
+}
+
+/// Recovery actions, in escalation order
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum RecoveryAction {
+    /// Flush the RX and TX FIFOs and restart the Rx state
+    FlushFifos,
+    /// Strobe the Idle state, then the Rx state again
+    Restrobe,
+    /// Calibrate the frequency synthesizer and restart the Rx state
+    Recalibrate,
+    /// Reset the transceiver and apply the configuration again
+    Reset,
+    /// Give up, the wrapper enters the degraded mode until the next `init_config`
+    MarkFailed,
+}
+
+/// Recovery action taken by the transceiver monitoring
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct RecoveryRecord {
+    /// Time of the action
+    pub timestamp: Timestamp,
+    /// Fault detected by the check
+    pub cause: Cc1101WrapperError,
+    /// Action taken
+    pub action: RecoveryAction,
+    /// Number of consecutive faulty checks
+    pub faults: u8,
+    /// Outcome of the action, the fault may persist nonetheless
+    pub result: Result<(), Cc1101WrapperError>,
+}
+
+/// Fault recovery configuration. The transceiver is checked periodically in Rx state; each
+/// consecutive faulty check escalates to the last action whose threshold it reached.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct RecoveryConfig {
+    /// Period of the transceiver checks [ms]
+    pub check_period_ms: u32,
+    /// Consecutive faulty checks from which the FIFOs are flushed
+    pub flush_fifos: u8,
+    /// Consecutive faulty checks from which the Idle and Rx states are strobed again
+    pub restrobe: u8,
+    /// Consecutive faulty checks from which the frequency synthesizer is calibrated
+    pub recalibrate: u8,
+    /// Consecutive faulty checks from which the transceiver is reset
+    pub reset: u8,
+    /// Consecutive faulty checks from which the transceiver is declared failed
+    pub mark_failed: u8,
+}
+
+impl RecoveryConfig {
+    /// Project default: check every second, reset at the 4th faulty check in a row and give
+    /// up after a second reset.
+    pub const PROJECT: Self = Self {
+        check_period_ms: 1000,
+        flush_fifos: 1,
+        restrobe: 2,
+        recalibrate: 3,
+        reset: 4,
+        mark_failed: 6,
+    };
+
+    /// Check the configuration consistency.
+    pub fn validate(&self) -> Result<(), RecoveryError> {
+        if self.check_period_ms == 0 {
+            return Err(RecoveryError::InvalidPeriod);
+        }
+
+        let thresholds = self.thresholds();
+        if thresholds[0].0 == 0 || thresholds.windows(2).any(|pair| pair[0].0 > pair[1].0) {
+            return Err(RecoveryError::InvalidThresholds);
+        }
+
+        Ok(())
+    }
+
+    fn thresholds(&self) -> [(u8, RecoveryAction); 5] {
+        [
+            (self.flush_fifos, RecoveryAction::FlushFifos),
+            (self.restrobe, RecoveryAction::Restrobe),
+            (self.recalibrate, RecoveryAction::Recalibrate),
+            (self.reset, RecoveryAction::Reset),
+            (self.mark_failed, RecoveryAction::MarkFailed),
+        ]
+    }
+
+    /// Action for the given number of consecutive faulty checks
+    fn action(&self, faults: u8) -> Option<RecoveryAction> {
+        self.thresholds()
+            .iter()
+            .rev()
+            .find(|(threshold, _)| faults >= *threshold)
+            .map(|&(_, action)| action)
+    }
+}
+
+impl Default for RecoveryConfig {
+    fn default() -> Self {
+        Self::PROJECT
+    }
+}
+
+/// Escalates the recovery actions of the wrapper and keeps their history
+#[derive(Default)]
+pub(crate) struct Escalation {
+    pub config: RecoveryConfig,
+    faults: u8,
+    pending: Option<Cc1101WrapperError>,
+    history: Deque<RecoveryRecord, RECOVERY_HISTORY_DEPTH>,
+}
+
+impl Escalation {
+    /// Note an error of the RF operations, faulting the next check. The first error since the
+    /// last check is its cause.
+    pub fn error(&mut self, error: Cc1101WrapperError) {
+        if self.pending.is_none()
+            && matches!(
+                error,
+                Cc1101WrapperError::Spi
+                    | Cc1101WrapperError::TimeoutError
+                    | Cc1101WrapperError::InvalidState(_)
+                    | Cc1101WrapperError::SpiIntegrity
+                    | Cc1101WrapperError::UnknownChip(_, _)
+            )
+        {
+            self.pending = Some(error);
+        }
+    }
+
+    /// Take the error noted since the last check.
+    pub fn take_pending(&mut self) -> Option<Cc1101WrapperError> {
+        self.pending.take()
+    }
+
+    pub fn healthy(&mut self) {
+        self.faults = 0;
+    }
+
+    /// Count a faulty check and get the action to take.
+    pub fn escalate(&mut self) -> Option<RecoveryAction> {
+        self.faults = self.faults.saturating_add(1);
+        self.config.action(self.faults)
+    }
+
+    pub fn record(
+        &mut self,
+        timestamp: Timestamp,
+        cause: Cc1101WrapperError,
+        action: RecoveryAction,
+        result: Result<(), Cc1101WrapperError>,
+    ) {
+        let record = RecoveryRecord {
+            timestamp,
+            cause,
+            action,
+            faults: self.faults,
+            result,
+        };
+        if let Err(record) = self.history.push_back(record) {
+            // Drop the oldest record
+            self.history.pop_front();
+            let _ = self.history.push_back(record);
+        }
+    }
+
+    pub fn pop_record(&mut self) -> Option<RecoveryRecord> {
+        self.history.pop_front()
+    }
+
+    /// Restart the escalation, e.g. after a new configuration.
+    pub fn restart(&mut self) {
+        self.faults = 0;
+        self.pending = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn escalation(config: RecoveryConfig) -> Escalation {
+        Escalation {
+            config,
+            ..Escalation::default()
+        }
+    }
+
+    #[test]
+    fn inconsistent_configuration_is_rejected() {
+        assert_eq!(RecoveryConfig::PROJECT.validate(), Ok(()));
+
+        let config = RecoveryConfig {
+            check_period_ms: 0,
+            ..RecoveryConfig::PROJECT
+        };
+        assert_eq!(config.validate(), Err(RecoveryError::InvalidPeriod));
+
+        let config = RecoveryConfig {
+            flush_fifos: 0,
+            ..RecoveryConfig::PROJECT
+        };
+        assert_eq!(config.validate(), Err(RecoveryError::InvalidThresholds));
+
+        let config = RecoveryConfig {
+            recalibrate: 5,
+            ..RecoveryConfig::PROJECT
+        };
+        assert_eq!(config.validate(), Err(RecoveryError::InvalidThresholds));
+
+        // Actions sharing a threshold, the last one is taken
+        let config = RecoveryConfig {
+            restrobe: 1,
+            ..RecoveryConfig::PROJECT
+        };
+        assert_eq!(config.validate(), Ok(()));
+        assert_eq!(config.action(1), Some(RecoveryAction::Restrobe));
+    }
+
+    #[test]
+    fn consecutive_faults_escalate_the_actions() {
+        let mut escalation = escalation(RecoveryConfig::PROJECT);
+        let actions = [
+            RecoveryAction::FlushFifos,
+            RecoveryAction::Restrobe,
+            RecoveryAction::Recalibrate,
+            RecoveryAction::Reset,
+            RecoveryAction::Reset,
+            RecoveryAction::MarkFailed,
+        ];
+        for action in actions {
+            assert_eq!(escalation.escalate(), Some(action));
+        }
+
+        // The fault counter saturates instead of wrapping back to the first action
+        for _ in 0..300 {
+            assert_eq!(escalation.escalate(), Some(RecoveryAction::MarkFailed));
+        }
+
+        // Healthy check restarts the escalation
+        escalation.healthy();
+        assert_eq!(escalation.escalate(), Some(RecoveryAction::FlushFifos));
+    }
+
+    #[test]
+    fn no_action_below_the_first_threshold() {
+        let config = RecoveryConfig {
+            flush_fifos: 2,
+            restrobe: 2,
+            ..RecoveryConfig::PROJECT
+        };
+        let mut escalation = escalation(config);
+        assert_eq!(escalation.escalate(), None);
+        assert_eq!(escalation.escalate(), Some(RecoveryAction::Restrobe));
+    }
+
+    #[test]
+    fn first_rf_error_is_pending() {
+        let mut escalation = escalation(RecoveryConfig::PROJECT);
+
+        // Not a fault of the transceiver
+        escalation.error(Cc1101WrapperError::CrcMismatch);
+        assert_eq!(escalation.take_pending(), None);
+
+        escalation.error(Cc1101WrapperError::TimeoutError);
+        escalation.error(Cc1101WrapperError::Spi);
+        assert_eq!(
+            escalation.take_pending(),
+            Some(Cc1101WrapperError::TimeoutError)
+        );
+        assert_eq!(escalation.take_pending(), None);
+
+        escalation.error(Cc1101WrapperError::Spi);
+        escalation.restart();
+        assert_eq!(escalation.take_pending(), None);
+    }
+
+    #[test]
+    fn oldest_record_is_dropped_from_the_history() {
+        let mut escalation = escalation(RecoveryConfig::PROJECT);
+        for time in 0..RECOVERY_HISTORY_DEPTH as u64 + 2 {
+            escalation.record(
+                Timestamp::from_ticks(time),
+                Cc1101WrapperError::MonitoringError,
+                RecoveryAction::FlushFifos,
+                Ok(()),
+            );
+        }
+
+        for time in 2..RECOVERY_HISTORY_DEPTH as u64 + 2 {
+            let record = escalation.pop_record().unwrap();
+            assert_eq!(record.timestamp, Timestamp::from_ticks(time));
+        }
+        assert_eq!(escalation.pop_record(), None);
+    }
+}
@@ -0,0 +1,1434 @@
+//! Register level simulator of the CC1101 transceiver, implementing `SpiDevice` so that
+//! the driver and the wrapper can run on the host.
+//!
+//! The simulator models the configuration and status registers, the command strobes, the
+//! FIFOs and the main radio control state machine (MARCSTATE). Time is counted in SPI
+//! transactions: at the start of each transaction the pending radio activity advances
+//! by one step, moving up to `bytes_per_transaction` bytes from the TX FIFO to the air, or
+//! from the air to the RX FIFO. `Cc1101Sim::advance` lets the radio activity go on
+//! without SPI traffic, e.g. while the application waits for a GDO interrupt; transceivers
+//! attached to a `SimClock` advance by one step per millisecond of delay, the delay yielding
+//! to the executor in between. Changes of the GDO pins are reported to the handlers
+//! registered with `Cc1101Sim::on_gdo_change`.
+//!
+//! Radios attached to the same `SimChannel` exchange packets, as long as they use the
+//! same data rate, modulation, sync word and coding, and the carrier falls within the
+//! channel filter of the receiver. The carrier frequency follows the base frequency, the
+//! channel, the frequency offset (FSCTRL0) and the frequency error of each radio, see
+//! `Cc1101Sim::set_frequency_error`; FREQEST reports the offset of the received carrier.
+
+use crate::clock::{Clock, Timestamp};
+use core::future::poll_fn;
+use core::task::Poll;
+use embedded_hal::spi::{Error, ErrorKind, ErrorType, Operation, SpiDevice};
+use embedded_hal_async::delay::DelayNs;
+use std::boxed::Box;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::vec::Vec;
+
+/// Size of the RX and TX FIFOs
+pub const SIM_FIFO_SIZE: usize = 64;
+
+// SPI header bits
+const HEADER_READ: u8 = 0x80;
+const HEADER_BURST: u8 = 0x40;
+
+// Configuration registers
+const IOCFG2: usize = 0x00;
+const IOCFG1: usize = 0x01;
+const IOCFG0: usize = 0x02;
+const FIFOTHR: usize = 0x03;
+const SYNC1: usize = 0x04;
+const SYNC0: usize = 0x05;
+const PKTLEN: usize = 0x06;
+const PKTCTRL1: usize = 0x07;
+const PKTCTRL0: usize = 0x08;
+const ADDR: usize = 0x09;
+const CHANNR: usize = 0x0A;
+const FSCTRL0: usize = 0x0C;
+const FREQ2: usize = 0x0D;
+const FREQ1: usize = 0x0E;
+const FREQ0: usize = 0x0F;
+const MDMCFG4: usize = 0x10;
+const MDMCFG3: usize = 0x11;
+const MDMCFG2: usize = 0x12;
+const MDMCFG1: usize = 0x13;
+const MDMCFG0: usize = 0x14;
+const MCSM1: usize = 0x17;
+const FSTEST: usize = 0x29;
+const CONFIG_REGISTERS: usize = 0x2F;
+
+// Register values after reset, from the datasheet
+const CONFIG_RESET: [u8; CONFIG_REGISTERS] = [
+    0x29, 0x2E, 0x3F, 0x07, 0xD3, 0x91, 0xFF, 0x04, 0x45, 0x00, 0x00, 0x0F, 0x00, 0x1E, 0xC4, 0xEC,
+    0x8C, 0x22, 0x02, 0x22, 0xF8, 0x47, 0x07, 0x30, 0x04, 0x36, 0x6C, 0x03, 0x40, 0x91, 0x87, 0x6B,
+    0xF8, 0x56, 0x10, 0xA9, 0x0A, 0x20, 0x0D, 0x41, 0x00, 0x59, 0x7F, 0x3F, 0x88, 0x31, 0x0B,
+];
+
+// Command strobes
+const SRES: u8 = 0x30;
+const SFSTXON: u8 = 0x31;
+const SXOFF: u8 = 0x32;
+const SCAL: u8 = 0x33;
+const SRX: u8 = 0x34;
+const STX: u8 = 0x35;
+const SIDLE: u8 = 0x36;
+const SWOR: u8 = 0x38;
+const SPWD: u8 = 0x39;
+const SFRX: u8 = 0x3A;
+const SFTX: u8 = 0x3B;
+
+// Status registers
+const PARTNUM: u8 = 0x30;
+const VERSION: u8 = 0x31;
+const FREQEST: u8 = 0x32;
+const LQI: u8 = 0x33;
+const RSSI: u8 = 0x34;
+const MARCSTATE: u8 = 0x35;
+const PKTSTATUS: u8 = 0x38;
+const VCO_VC_DAC: u8 = 0x39;
+const TXBYTES: u8 = 0x3A;
+const RXBYTES: u8 = 0x3B;
+
+const PATABLE: u8 = 0x3E;
+const PATABLE_LEN: usize = 8;
+const PATABLE_RESET: [u8; PATABLE_LEN] = [0xC6, 0, 0, 0, 0, 0, 0, 0];
+
+const CHIP_PARTNUM: u8 = 0x00;
+const CHIP_VERSION: u8 = 0x14;
+
+// Crystal oscillator frequency [Hz]
+const FXOSC: i64 = 26_000_000;
+
+// Steps spent in manual calibration
+const CALIBRATION_STEPS: u32 = 2;
+
+/// Values of the MARCSTATE status register
+pub mod marc_state {
+    pub const SLEEP: u8 = 0x00;
+    pub const IDLE: u8 = 0x01;
+    pub const XOFF: u8 = 0x02;
+    pub const MANCAL: u8 = 0x05;
+    pub const RX: u8 = 0x0D;
+    pub const RXFIFO_OVERFLOW: u8 = 0x11;
+    pub const FSTXON: u8 = 0x12;
+    pub const TX: u8 = 0x13;
+    pub const TXFIFO_UNDERFLOW: u8 = 0x16;
+}
+
+use marc_state::*;
+
+/// Error returned by a failing simulated SPI transaction.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SimError;
+
+impl Error for SimError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Other
+    }
+}
+
+/// Fault injected in a simulated transceiver.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SimFault {
+    /// Every SPI transaction fails, until the faults are cleared
+    SpiError,
+    /// The next SPI transactions fail
+    FailTransactions(u32),
+    /// The chip does not answer, MISO stays high, until the faults are cleared
+    NotResponding,
+    /// The state machine hangs in the given MARCSTATE, until the chip is reset
+    StuckState(u8),
+    /// The next received packets have a CRC error
+    CorruptPackets(u32),
+    /// The next transmitted packets are lost on the air
+    DropPackets(u32),
+}
+
+/// Output pins of the transceiver.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SimGdo {
+    Gdo0,
+    Gdo1,
+    Gdo2,
+}
+
+// Data rate, modulation, sync word and coding of a radio
+type AirConfig = [u8; 7];
+
+#[derive(Clone, Debug)]
+struct AirPacket {
+    bytes: Vec<u8>,
+    config: AirConfig,
+    carrier_hz: i64,
+    crc: bool,
+}
+
+#[derive(Default)]
+struct Air {
+    inboxes: Vec<VecDeque<AirPacket>>,
+    busy: bool,
+}
+
+/// Radio channel shared by simulated transceivers.
+#[derive(Clone, Default)]
+pub struct SimChannel {
+    air: Arc<Mutex<Air>>,
+}
+
+impl SimChannel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Occupy the channel, as seen by the carrier sense and clear channel assessment.
+    pub fn set_busy(&self, busy: bool) {
+        self.air.lock().unwrap().busy = busy;
+    }
+
+    fn is_busy(&self) -> bool {
+        self.air.lock().unwrap().busy
+    }
+
+    fn attach(&self) -> usize {
+        let mut air = self.air.lock().unwrap();
+        air.inboxes.push(VecDeque::new());
+        air.inboxes.len() - 1
+    }
+
+    fn transmit(&self, from: usize, packet: &AirPacket) {
+        let mut air = self.air.lock().unwrap();
+        for (id, inbox) in air.inboxes.iter_mut().enumerate() {
+            if id != from {
+                inbox.push_back(packet.clone());
+            }
+        }
+    }
+
+    fn deliver(&self, to: usize, packet: AirPacket) {
+        self.air.lock().unwrap().inboxes[to].push_back(packet);
+    }
+
+    fn receive(&self, id: usize) -> Option<AirPacket> {
+        self.air.lock().unwrap().inboxes[id].pop_front()
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Access {
+    Header,
+    Register {
+        addr: usize,
+        read: bool,
+        burst: bool,
+    },
+    Status(u8),
+    Patable {
+        read: bool,
+        burst: bool,
+    },
+    Fifo {
+        read: bool,
+        burst: bool,
+    },
+    Ignore,
+}
+
+// Packet being moved between the air and a FIFO
+struct Transfer {
+    packet: AirPacket,
+    index: usize,
+    done: bool,
+}
+
+// Interrupt handler wired to an output pin
+struct GdoHandler {
+    pin: SimGdo,
+    level: bool,
+    handler: Box<dyn FnMut(bool) + Send>,
+}
+
+#[derive(Default)]
+struct Faults {
+    spi_error: bool,
+    failing_transactions: u32,
+    not_responding: bool,
+    stuck_state: Option<u8>,
+    corrupt_packets: u32,
+    drop_packets: u32,
+}
+
+struct Chip {
+    registers: [u8; CONFIG_REGISTERS],
+    patable: [u8; PATABLE_LEN],
+    patable_index: usize,
+    state: u8,
+    access: Access,
+    rx_fifo: VecDeque<u8>,
+    tx_fifo: VecDeque<u8>,
+    rx_overflow: bool,
+    tx_underflow: bool,
+    rx: Option<Transfer>,
+    tx: Option<Transfer>,
+    calibration: u32,
+    crc_ok: bool,
+    crc_ok_pin: bool,
+    lqi: u8,
+    rssi: u8,
+    freq_estimate: i8,
+    pending_sleep: Option<u8>,
+    wake_on_radio: bool,
+    channel: SimChannel,
+    id: usize,
+    rssi_dbm: i16,
+    link_quality: u8,
+    frequency_error_hz: i32,
+    bytes_per_transaction: usize,
+    transmitted: Vec<Vec<u8>>,
+    gdo_handlers: Vec<GdoHandler>,
+    faults: Faults,
+}
+
+impl Chip {
+    fn new(channel: &SimChannel) -> Self {
+        Self {
+            registers: CONFIG_RESET,
+            patable: PATABLE_RESET,
+            patable_index: 0,
+            state: IDLE,
+            access: Access::Header,
+            rx_fifo: VecDeque::new(),
+            tx_fifo: VecDeque::new(),
+            rx_overflow: false,
+            tx_underflow: false,
+            rx: None,
+            tx: None,
+            calibration: 0,
+            crc_ok: false,
+            crc_ok_pin: false,
+            lqi: 0,
+            rssi: 0,
+            freq_estimate: 0,
+            pending_sleep: None,
+            wake_on_radio: false,
+            channel: channel.clone(),
+            id: channel.attach(),
+            rssi_dbm: -60,
+            link_quality: 0x10,
+            frequency_error_hz: 0,
+            bytes_per_transaction: 16,
+            transmitted: Vec::new(),
+            gdo_handlers: Vec::new(),
+            faults: Faults::default(),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.registers = CONFIG_RESET;
+        self.patable = PATABLE_RESET;
+        self.state = IDLE;
+        self.rx_fifo.clear();
+        self.tx_fifo.clear();
+        self.rx_overflow = false;
+        self.tx_underflow = false;
+        self.rx = None;
+        self.tx = None;
+        self.calibration = 0;
+        self.crc_ok = false;
+        self.crc_ok_pin = false;
+        self.pending_sleep = None;
+        self.wake_on_radio = false;
+        self.faults.stuck_state = None;
+    }
+
+    fn marc_state(&self) -> u8 {
+        self.faults.stuck_state.unwrap_or(self.state)
+    }
+
+    // ---------------------------------------------------------------------------------
+
+    /// Start of a transaction (CSn low). Returns false if the transaction fails.
+    fn begin(&mut self) -> bool {
+        if self.faults.failing_transactions > 0 {
+            self.faults.failing_transactions -= 1;
+            return false;
+        }
+        if self.faults.spi_error {
+            return false;
+        }
+
+        // Pulling CSn low wakes the chip up, the TEST registers and the PATABLE are lost
+        if matches!(self.state, SLEEP | XOFF) {
+            if self.state == SLEEP {
+                self.registers[FSTEST..].copy_from_slice(&CONFIG_RESET[FSTEST..]);
+                self.patable[1..].fill(0);
+            }
+            self.state = IDLE;
+            self.wake_on_radio = false;
+        }
+
+        if self.faults.stuck_state.is_none() {
+            self.step();
+        }
+        self.access = Access::Header;
+        true
+    }
+
+    /// End of a transaction (CSn high).
+    fn end(&mut self) {
+        self.access = Access::Header;
+        self.patable_index = 0;
+        if let Some(state) = self.pending_sleep.take() {
+            self.state = state;
+        }
+        self.notify_gdo();
+    }
+
+    /// Exchange one byte. `mosi` is None for the dummy bytes of a read operation.
+    fn exchange(&mut self, mosi: Option<u8>) -> u8 {
+        match (self.access, mosi) {
+            (Access::Header, Some(header)) => self.header(header),
+            (Access::Header, None) => self.status_byte(true),
+            (_, mosi) => self.data(mosi.unwrap_or(0)),
+        }
+    }
+
+    fn header(&mut self, header: u8) -> u8 {
+        let read = header & HEADER_READ != 0;
+        let burst = header & HEADER_BURST != 0;
+        let addr = header & 0x3F;
+        let status = self.status_byte(read);
+
+        self.access = match addr {
+            0x00..=0x2E => Access::Register {
+                addr: addr as usize,
+                read,
+                burst,
+            },
+            0x30..=0x3D if read && burst => Access::Status(addr),
+            0x30..=0x3D => {
+                self.strobe(addr);
+                Access::Header
+            }
+            PATABLE => Access::Patable { read, burst },
+            0x3F => Access::Fifo { read, burst },
+            _ => Access::Ignore,
+        };
+
+        status
+    }
+
+    fn data(&mut self, byte: u8) -> u8 {
+        match self.access {
+            Access::Register { addr, read, burst } => {
+                let value = if read {
+                    self.registers[addr]
+                } else {
+                    self.registers[addr] = byte;
+                    self.status_byte(false)
+                };
+                self.access = match (burst, addr + 1 < CONFIG_REGISTERS) {
+                    (true, true) => Access::Register {
+                        addr: addr + 1,
+                        read,
+                        burst,
+                    },
+                    (true, false) => Access::Ignore,
+                    (false, _) => Access::Header,
+                };
+                value
+            }
+            Access::Status(addr) => {
+                self.access = Access::Header;
+                self.status_register(addr)
+            }
+            Access::Patable { read, burst } => {
+                let index = self.patable_index;
+                self.patable_index = (index + 1) % PATABLE_LEN;
+                if !burst {
+                    self.access = Access::Header;
+                }
+                if read {
+                    self.patable[index]
+                } else {
+                    self.patable[index] = byte;
+                    self.status_byte(false)
+                }
+            }
+            Access::Fifo { read, burst } => {
+                if !burst {
+                    self.access = Access::Header;
+                }
+                if read {
+                    self.crc_ok_pin = false;
+                    self.rx_fifo.pop_front().unwrap_or(0)
+                } else {
+                    self.push_tx_fifo(byte);
+                    self.status_byte(false)
+                }
+            }
+            Access::Header | Access::Ignore => self.status_byte(false),
+        }
+    }
+
+    fn status_byte(&self, read: bool) -> u8 {
+        let chip_rdyn = matches!(self.state, SLEEP | XOFF);
+        let state = match self.marc_state() {
+            IDLE | SLEEP | XOFF => 0,
+            0x0D..=0x0F => 1,
+            0x13 | 0x14 => 2,
+            FSTXON => 3,
+            0x04 | MANCAL | 0x08 | 0x0C => 4,
+            RXFIFO_OVERFLOW => 6,
+            TXFIFO_UNDERFLOW => 7,
+            _ => 5,
+        };
+        let fifo_bytes = if read {
+            self.rx_fifo.len()
+        } else {
+            SIM_FIFO_SIZE - self.tx_fifo.len()
+        };
+
+        (chip_rdyn as u8) << 7 | state << 4 | fifo_bytes.min(15) as u8
+    }
+
+    fn status_register(&self, addr: u8) -> u8 {
+        match addr {
+            PARTNUM => CHIP_PARTNUM,
+            VERSION => CHIP_VERSION,
+            FREQEST => self.freq_estimate as u8,
+            LQI => (self.crc_ok as u8) << 7 | self.lqi,
+            RSSI => self.rssi,
+            MARCSTATE => self.marc_state(),
+            PKTSTATUS => {
+                let receiving = self.rx.as_ref().is_some_and(|rx| !rx.done);
+                (self.crc_ok as u8) << 7
+                    | (self.carrier_sense() as u8) << 6
+                    | (self.channel_clear() as u8) << 4
+                    | (receiving as u8) << 3
+                    | (self.gdo(SimGdo::Gdo2) as u8) << 2
+                    | self.gdo(SimGdo::Gdo0) as u8
+            }
+            VCO_VC_DAC => 0x94,
+            TXBYTES => (self.tx_underflow as u8) << 7 | self.tx_fifo.len() as u8,
+            RXBYTES => (self.rx_overflow as u8) << 7 | self.rx_fifo.len() as u8,
+            _ => 0,
+        }
+    }
+
+    fn strobe(&mut self, command: u8) {
+        // A hanging state machine only recovers from a reset
+        if self.faults.stuck_state.is_some() && command != SRES {
+            return;
+        }
+
+        match command {
+            SRES => self.reset(),
+            SFSTXON if self.state == IDLE => self.state = FSTXON,
+            SXOFF if self.state == IDLE => self.pending_sleep = Some(XOFF),
+            SCAL if self.state == IDLE => {
+                self.state = MANCAL;
+                self.calibration = CALIBRATION_STEPS;
+            }
+            SRX if matches!(self.state, IDLE | FSTXON) => self.enter_rx(),
+            STX if matches!(self.state, IDLE | FSTXON) => self.enter_tx(),
+            STX if self.state == marc_state::RX && self.channel_clear() => self.enter_tx(),
+            SIDLE if !matches!(self.state, RXFIFO_OVERFLOW | TXFIFO_UNDERFLOW) => {
+                self.state = IDLE;
+                self.rx = None;
+                self.tx = None;
+                self.calibration = 0;
+            }
+            SWOR if self.state == IDLE => {
+                self.pending_sleep = Some(SLEEP);
+                self.wake_on_radio = true;
+            }
+            SPWD if self.state == IDLE => self.pending_sleep = Some(SLEEP),
+            SFRX if matches!(self.state, IDLE | RXFIFO_OVERFLOW) => {
+                self.rx_fifo.clear();
+                self.rx_overflow = false;
+                self.state = IDLE;
+            }
+            SFTX if matches!(self.state, IDLE | TXFIFO_UNDERFLOW) => {
+                self.tx_fifo.clear();
+                self.tx_underflow = false;
+                self.state = IDLE;
+            }
+            _ => { /* Ignored in the current state */ }
+        }
+    }
+
+    fn push_tx_fifo(&mut self, byte: u8) {
+        if self.tx_fifo.len() < SIM_FIFO_SIZE {
+            self.tx_fifo.push_back(byte);
+        } else {
+            // Writing to a full TX FIFO is reported as an underflow
+            self.tx_underflow = true;
+            self.state = TXFIFO_UNDERFLOW;
+            self.tx = None;
+        }
+    }
+
+    // ---------------------------------------------------------------------------------
+
+    fn enter_rx(&mut self) {
+        self.state = marc_state::RX;
+        self.crc_ok = false;
+        self.rx = None;
+    }
+
+    fn enter_tx(&mut self) {
+        self.state = TX;
+        self.tx = Some(Transfer {
+            packet: AirPacket {
+                bytes: Vec::new(),
+                config: self.air_config(),
+                carrier_hz: self.carrier_hz(),
+                crc: self.crc_enabled(),
+            },
+            index: 0,
+            done: false,
+        });
+    }
+
+    fn crc_enabled(&self) -> bool {
+        self.registers[PKTCTRL0] & 0x04 != 0
+    }
+
+    fn air_config(&self) -> AirConfig {
+        let r = &self.registers;
+        [
+            r[MDMCFG4] & 0x0F,
+            r[MDMCFG3],
+            r[MDMCFG2] & 0x7F,
+            r[MDMCFG1] & 0x80,
+            r[SYNC1],
+            r[SYNC0],
+            r[PKTCTRL0] & 0x40,
+        ]
+    }
+
+    /// Nominal carrier frequency of the base frequency and the channel [Hz]
+    fn channel_hz(&self) -> i64 {
+        let r = &self.registers;
+        let freq = (r[FREQ2] as i64) << 16 | (r[FREQ1] as i64) << 8 | r[FREQ0] as i64;
+        let spacing_m = 256 + r[MDMCFG0] as i64;
+        let spacing_e = (r[MDMCFG1] & 0x03) as u32;
+        let spacing = ((FXOSC * spacing_m) << spacing_e) >> 18;
+
+        ((FXOSC * freq) >> 16) + r[CHANNR] as i64 * spacing
+    }
+
+    /// Actual carrier frequency, with the frequency offset and the frequency error [Hz]
+    fn carrier_hz(&self) -> i64 {
+        let freqoff = self.registers[FSCTRL0] as i8 as i64;
+        self.channel_hz() + ((FXOSC * freqoff) >> 14) + self.frequency_error_hz as i64
+    }
+
+    /// Half of the channel filter bandwidth [Hz]
+    fn channel_filter_hz(&self) -> i64 {
+        let chanbw_e = (self.registers[MDMCFG4] >> 6) as u32;
+        let chanbw_m = ((self.registers[MDMCFG4] >> 4) & 0x03) as i64;
+        FXOSC / ((8 * (4 + chanbw_m)) << chanbw_e) / 2
+    }
+
+    fn carrier_sense(&self) -> bool {
+        self.channel.is_busy() || self.rx.as_ref().is_some_and(|rx| !rx.done)
+    }
+
+    /// Clear channel assessment, as selected by MCSM1.CCA_MODE
+    fn channel_clear(&self) -> bool {
+        let busy = self.channel.is_busy();
+        let receiving = self.rx.as_ref().is_some_and(|rx| !rx.done);
+        match (self.registers[MCSM1] >> 4) & 0x03 {
+            0 => true,
+            1 => !busy,
+            2 => !receiving,
+            _ => !busy && !receiving,
+        }
+    }
+
+    /// Length of a packet starting with `bytes`, or None while unknown.
+    fn packet_len(&self, bytes: &[u8]) -> Option<usize> {
+        match self.registers[PKTCTRL0] & 0x03 {
+            0 => {
+                // The packet ends when the byte counter modulo 256 reaches PKTLEN, which
+                // allows switching from infinite to fixed packet length mode on the fly
+                let count = bytes.len();
+                let target = self.registers[PKTLEN] as usize;
+                let end = count + (target + 256 - count % 256) % 256;
+                Some(if end == 0 { 256 } else { end })
+            }
+            1 => bytes.first().map(|&length| 1 + length as usize),
+            _ => None,
+        }
+    }
+
+    fn gdo(&self, pin: SimGdo) -> bool {
+        let config = match pin {
+            SimGdo::Gdo0 => self.registers[IOCFG0],
+            SimGdo::Gdo1 => self.registers[IOCFG1],
+            SimGdo::Gdo2 => self.registers[IOCFG2],
+        };
+        let threshold = (self.registers[FIFOTHR] & 0x0F) as usize;
+        let rx_threshold = 4 + 4 * threshold;
+        let tx_threshold = 61 - 4 * threshold;
+        let receiving = self.rx.as_ref().is_some_and(|rx| !rx.done);
+        let transmitting = self.tx.as_ref().is_some_and(|tx| !tx.done);
+
+        let level = match config & 0x3F {
+            0x00 => self.rx_fifo.len() >= rx_threshold,
+            0x01 => self.rx_fifo.len() >= rx_threshold || (!receiving && !self.rx_fifo.is_empty()),
+            0x02 => self.tx_fifo.len() >= tx_threshold,
+            0x03 => self.tx_fifo.len() >= SIM_FIFO_SIZE,
+            0x04 => self.rx_overflow,
+            0x05 => self.tx_underflow,
+            0x06 => receiving || transmitting,
+            0x07 => self.crc_ok_pin,
+            0x09 => self.channel_clear(),
+            0x0E => self.carrier_sense(),
+            0x29 => matches!(self.state, SLEEP | XOFF),
+            _ => false,
+        };
+
+        level ^ (config & 0x40 != 0)
+    }
+
+    /// Call the handlers of the pins whose level changed.
+    fn notify_gdo(&mut self) {
+        let mut handlers = std::mem::take(&mut self.gdo_handlers);
+        for handler in handlers.iter_mut() {
+            let level = self.gdo(handler.pin);
+            if level != handler.level {
+                handler.level = level;
+                (handler.handler)(level);
+            }
+        }
+        self.gdo_handlers = handlers;
+    }
+
+    // ---------------------------------------------------------------------------------
+
+    /// Advance the radio activity by one step.
+    fn step(&mut self) {
+        if self.calibration > 0 {
+            self.calibration -= 1;
+            if self.calibration == 0 {
+                self.state = IDLE;
+            }
+        }
+        self.step_tx();
+        self.step_rx();
+    }
+
+    fn step_tx(&mut self) {
+        let Some(mut tx) = self.tx.take() else {
+            return;
+        };
+
+        if tx.done {
+            self.finish_tx(tx.packet);
+            return;
+        }
+
+        for _ in 0..self.bytes_per_transaction {
+            let end = self.packet_len(&tx.packet.bytes);
+            if end.is_some_and(|end| tx.packet.bytes.len() >= end) {
+                break;
+            }
+            match self.tx_fifo.pop_front() {
+                Some(byte) => tx.packet.bytes.push(byte),
+                None => {
+                    self.tx_underflow = true;
+                    self.state = TXFIFO_UNDERFLOW;
+                    return;
+                }
+            }
+        }
+
+        let end = self.packet_len(&tx.packet.bytes);
+        tx.done = end.is_some_and(|end| tx.packet.bytes.len() >= end);
+        self.tx = Some(tx);
+    }
+
+    fn finish_tx(&mut self, packet: AirPacket) {
+        self.transmitted.push(packet.bytes.clone());
+        if self.faults.drop_packets > 0 {
+            self.faults.drop_packets -= 1;
+        } else {
+            self.channel.transmit(self.id, &packet);
+        }
+
+        // MCSM1.TXOFF_MODE
+        match self.registers[MCSM1] & 0x03 {
+            0 => self.state = IDLE,
+            1 => self.state = FSTXON,
+            2 => self.enter_tx(),
+            _ => self.enter_rx(),
+        }
+    }
+
+    fn step_rx(&mut self) {
+        // Packets arriving out of the RX state are lost, unless they wake up the radio
+        if self.state == SLEEP && self.wake_on_radio {
+            if let Some(packet) = self.next_packet() {
+                self.enter_rx();
+                self.start_rx(packet);
+            }
+            return;
+        }
+        if self.state != marc_state::RX {
+            while self.channel.receive(self.id).is_some() {}
+            return;
+        }
+
+        let Some(mut rx) = self.rx.take() else {
+            if let Some(packet) = self.next_packet() {
+                self.start_rx(packet);
+            }
+            return;
+        };
+
+        if rx.done {
+            self.finish_rx(rx);
+            return;
+        }
+
+        let mut budget = self.bytes_per_transaction;
+        loop {
+            let end = self.packet_len(&rx.packet.bytes[..rx.index]);
+            if end.is_some_and(|end| rx.index >= end) {
+                rx.done = true;
+                break;
+            }
+            if rx.index >= rx.packet.bytes.len() {
+                // In infinite packet length mode the radio keeps receiving after the packet
+                rx.done = end.is_some();
+                break;
+            }
+            if budget == 0 {
+                break;
+            }
+            if self.rx_fifo.len() >= SIM_FIFO_SIZE {
+                self.rx_overflow = true;
+                self.state = RXFIFO_OVERFLOW;
+                return;
+            }
+            self.rx_fifo.push_back(rx.packet.bytes[rx.index]);
+            rx.index += 1;
+            budget -= 1;
+        }
+        self.rx = Some(rx);
+    }
+
+    /// Next packet passing the configuration and address check.
+    fn next_packet(&mut self) -> Option<AirPacket> {
+        while let Some(packet) = self.channel.receive(self.id) {
+            if self.accepts(&packet) {
+                return Some(packet);
+            }
+        }
+        None
+    }
+
+    fn accepts(&self, packet: &AirPacket) -> bool {
+        if packet.config != self.air_config() {
+            return false;
+        }
+        if (packet.carrier_hz - self.carrier_hz()).abs() > self.channel_filter_hz() {
+            return false;
+        }
+
+        let variable = self.registers[PKTCTRL0] & 0x03 == 1;
+        if variable && packet.bytes.first().copied().unwrap_or(0) > self.registers[PKTLEN] {
+            return false;
+        }
+
+        let address = packet.bytes.get(variable as usize).copied().unwrap_or(0);
+        let device = self.registers[ADDR];
+        match self.registers[PKTCTRL1] & 0x03 {
+            0 => true,
+            1 => address == device,
+            2 => address == device || address == 0x00,
+            _ => address == device || address == 0x00 || address == 0xFF,
+        }
+    }
+
+    fn start_rx(&mut self, packet: AirPacket) {
+        // Sync word found, the data follows in the next steps
+        self.crc_ok = false;
+        let offset = ((packet.carrier_hz - self.carrier_hz()) << 14) / FXOSC;
+        self.freq_estimate = offset.clamp(i8::MIN as i64, i8::MAX as i64) as i8;
+        self.rx = Some(Transfer {
+            packet,
+            index: 0,
+            done: false,
+        });
+    }
+
+    fn finish_rx(&mut self, rx: Transfer) {
+        let length_ok = self.packet_len(&rx.packet.bytes[..rx.index]) == Some(rx.index)
+            && rx.index == rx.packet.bytes.len();
+        let corrupted = self.faults.corrupt_packets > 0;
+        if corrupted {
+            self.faults.corrupt_packets -= 1;
+        }
+        let crc_ok = self.crc_enabled() && rx.packet.crc && length_ok && !corrupted;
+
+        self.rssi = ((self.rssi_dbm + 74) * 2) as u8;
+        self.lqi = self.link_quality & 0x7F;
+        self.crc_ok = crc_ok;
+
+        // PKTCTRL1.APPEND_STATUS
+        if self.registers[PKTCTRL1] & 0x04 != 0 {
+            for byte in [self.rssi, (crc_ok as u8) << 7 | self.lqi] {
+                if self.rx_fifo.len() >= SIM_FIFO_SIZE {
+                    self.rx_overflow = true;
+                    self.state = RXFIFO_OVERFLOW;
+                    return;
+                }
+                self.rx_fifo.push_back(byte);
+            }
+        }
+
+        // PKTCTRL1.CRC_AUTOFLUSH
+        if !crc_ok && self.registers[PKTCTRL1] & 0x08 != 0 {
+            self.rx_fifo.clear();
+        }
+        self.crc_ok_pin = crc_ok;
+
+        // MCSM1.RXOFF_MODE
+        match (self.registers[MCSM1] >> 2) & 0x03 {
+            0 => self.state = IDLE,
+            1 => self.state = FSTXON,
+            2 => self.enter_tx(),
+            _ => { /* Stay in RX */ }
+        }
+    }
+}
+
+/// Simulated CC1101 transceiver. Clones are handles to the same transceiver, so that a
+/// test can inspect it and inject faults while the driver owns it.
+#[derive(Clone)]
+pub struct Cc1101Sim {
+    chip: Arc<Mutex<Chip>>,
+}
+
+impl Default for Cc1101Sim {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cc1101Sim {
+    /// Create a transceiver on its own channel.
+    pub fn new() -> Self {
+        Self::on_channel(&SimChannel::new())
+    }
+
+    /// Create a transceiver exchanging packets with the other transceivers of `channel`.
+    pub fn on_channel(channel: &SimChannel) -> Self {
+        Self {
+            chip: Arc::new(Mutex::new(Chip::new(channel))),
+        }
+    }
+
+    /// Create two transceivers sharing a channel.
+    pub fn linked_pair() -> (Self, Self) {
+        let channel = SimChannel::new();
+        (Self::on_channel(&channel), Self::on_channel(&channel))
+    }
+
+    pub fn inject(&self, fault: SimFault) {
+        let mut chip = self.chip.lock().unwrap();
+        match fault {
+            SimFault::SpiError => chip.faults.spi_error = true,
+            SimFault::FailTransactions(count) => chip.faults.failing_transactions = count,
+            SimFault::NotResponding => chip.faults.not_responding = true,
+            SimFault::StuckState(state) => chip.faults.stuck_state = Some(state),
+            SimFault::CorruptPackets(count) => chip.faults.corrupt_packets = count,
+            SimFault::DropPackets(count) => chip.faults.drop_packets = count,
+        }
+    }
+
+    pub fn clear_faults(&self) {
+        self.chip.lock().unwrap().faults = Faults::default();
+    }
+
+    /// Put a packet on the air towards this transceiver, on the nominal frequency of its
+    /// channel, with the over-the-air bytes following the sync word (length and address
+    /// bytes included) and a valid CRC.
+    pub fn deliver(&self, bytes: &[u8]) {
+        let chip = self.chip.lock().unwrap();
+        let packet = AirPacket {
+            bytes: bytes.to_vec(),
+            config: chip.air_config(),
+            carrier_hz: chip.channel_hz(),
+            crc: true,
+        };
+        chip.channel.deliver(chip.id, packet);
+    }
+
+    /// Over-the-air bytes of every packet transmitted so far.
+    pub fn transmitted(&self) -> Vec<Vec<u8>> {
+        self.chip.lock().unwrap().transmitted.clone()
+    }
+
+    /// Current MARCSTATE.
+    pub fn marc_state(&self) -> u8 {
+        self.chip.lock().unwrap().marc_state()
+    }
+
+    /// Value of a configuration register.
+    pub fn register(&self, addr: u8) -> u8 {
+        self.chip.lock().unwrap().registers[addr as usize]
+    }
+
+    pub fn patable(&self) -> [u8; PATABLE_LEN] {
+        self.chip.lock().unwrap().patable
+    }
+
+    /// Level of an output pin, as configured by the IOCFGx registers.
+    pub fn gdo(&self, pin: SimGdo) -> bool {
+        self.chip.lock().unwrap().gdo(pin)
+    }
+
+    /// Signal strength of the received packets [dBm].
+    pub fn set_rssi(&self, rssi_dbm: i16) {
+        self.chip.lock().unwrap().rssi_dbm = rssi_dbm.clamp(-138, 53);
+    }
+
+    /// Link quality indicator of the received packets.
+    pub fn set_link_quality(&self, lqi: u8) {
+        self.chip.lock().unwrap().link_quality = lqi;
+    }
+
+    /// Frequency error of the radio [Hz], e.g. of its crystal or the Doppler shift, moving
+    /// its carrier away from the nominal frequency.
+    pub fn set_frequency_error(&self, error_hz: i32) {
+        self.chip.lock().unwrap().frequency_error_hz = error_hz;
+    }
+
+    /// Number of bytes moved between the air and the FIFOs at each SPI transaction.
+    pub fn set_bytes_per_transaction(&self, bytes: usize) {
+        self.chip.lock().unwrap().bytes_per_transaction = bytes.max(1);
+    }
+
+    /// Advance the radio activity by `steps` steps, as done at the start of each transaction.
+    pub fn advance(&self, steps: u32) {
+        let mut chip = self.chip.lock().unwrap();
+        for _ in 0..steps {
+            if chip.faults.stuck_state.is_none() {
+                chip.step();
+            }
+            chip.notify_gdo();
+        }
+    }
+
+    /// Call `handler` with the new level of `pin` at each change, like an interrupt on
+    /// both edges. The handler is called with the transceiver locked and shall not access it.
+    pub fn on_gdo_change(&self, pin: SimGdo, handler: impl FnMut(bool) + Send + 'static) {
+        let mut chip = self.chip.lock().unwrap();
+        let level = chip.gdo(pin);
+        chip.gdo_handlers.push(GdoHandler {
+            pin,
+            level,
+            handler: Box::new(handler),
+        });
+    }
+}
+
+/// Simulated clock for host tests. Delays complete without waiting and advance the time,
+/// so that timeouts are reached right away. Clones share the same time.
+#[derive(Clone, Default)]
+pub struct SimClock {
+    nanos: Arc<AtomicU64>,
+    transceivers: Arc<Mutex<Vec<Cc1101Sim>>>,
+}
+
+impl SimClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Advance the radio activity of `sim` by one step per elapsed millisecond.
+    pub fn attach(&self, sim: &Cc1101Sim) {
+        self.transceivers.lock().unwrap().push(sim.clone());
+    }
+
+    /// Let `ms` milliseconds pass.
+    pub fn advance_ms(&self, ms: u64) {
+        self.advance_ns(ms * 1_000_000);
+    }
+
+    fn advance_ns(&self, ns: u64) {
+        let before = self.nanos.fetch_add(ns, Ordering::Relaxed);
+        let steps = (before + ns) / 1_000_000 - before / 1_000_000;
+        if steps > 0 {
+            for sim in self.transceivers.lock().unwrap().iter() {
+                sim.advance(steps as u32);
+            }
+        }
+    }
+}
+
+impl DelayNs for SimClock {
+    async fn delay_ns(&mut self, ns: u32) {
+        // Yield at each millisecond, so that the GDO events raised meanwhile by the attached
+        // transceivers are seen before the end of the delay
+        let mut remaining = ns as u64;
+        loop {
+            let step = remaining.min(1_000_000);
+            self.advance_ns(step);
+            remaining -= step;
+            if remaining == 0 {
+                break;
+            }
+            yield_now().await;
+        }
+    }
+}
+
+/// Let the executor poll the other futures once.
+async fn yield_now() {
+    let mut yielded = false;
+    poll_fn(|cx| {
+        if yielded {
+            Poll::Ready(())
+        } else {
+            yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+impl Clock for SimClock {
+    fn now(&mut self) -> Timestamp {
+        Timestamp::from_ticks(self.nanos.load(Ordering::Relaxed) / 1_000_000)
+    }
+}
+
+impl ErrorType for Cc1101Sim {
+    type Error = SimError;
+}
+
+impl SpiDevice<u8> for Cc1101Sim {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), SimError> {
+        let mut chip = self.chip.lock().unwrap();
+
+        if !chip.begin() {
+            return Err(SimError);
+        }
+
+        if chip.faults.not_responding {
+            for operation in operations.iter_mut() {
+                match operation {
+                    Operation::Read(read) | Operation::Transfer(read, _) => read.fill(0xFF),
+                    Operation::TransferInPlace(buffer) => buffer.fill(0xFF),
+                    _ => {}
+                }
+            }
+            return Ok(());
+        }
+
+        for operation in operations.iter_mut() {
+            match operation {
+                Operation::Read(read) => {
+                    for byte in read.iter_mut() {
+                        *byte = chip.exchange(None);
+                    }
+                }
+                Operation::Write(write) => {
+                    for &byte in write.iter() {
+                        chip.exchange(Some(byte));
+                    }
+                }
+                Operation::Transfer(read, write) => {
+                    for i in 0..read.len().max(write.len()) {
+                        let miso = chip.exchange(write.get(i).copied());
+                        if let Some(byte) = read.get_mut(i) {
+                            *byte = miso;
+                        }
+                    }
+                }
+                Operation::TransferInPlace(buffer) => {
+                    for byte in buffer.iter_mut() {
+                        *byte = chip.exchange(Some(*byte));
+                    }
+                }
+                Operation::DelayNs(_) => {}
+            }
+        }
+        chip.end();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec;
+
+    const SNOP: u8 = 0x3D;
+
+    fn write_register(sim: &mut Cc1101Sim, addr: u8, value: u8) {
+        sim.write(&[addr, value]).unwrap();
+    }
+
+    fn read_register(sim: &mut Cc1101Sim, addr: u8) -> u8 {
+        let mut buffer = [addr | HEADER_READ, 0];
+        sim.transfer_in_place(&mut buffer).unwrap();
+        buffer[1]
+    }
+
+    fn read_status(sim: &mut Cc1101Sim, addr: u8) -> u8 {
+        let mut buffer = [addr | HEADER_READ | HEADER_BURST, 0];
+        sim.transfer_in_place(&mut buffer).unwrap();
+        buffer[1]
+    }
+
+    fn strobe(sim: &mut Cc1101Sim, command: u8) -> u8 {
+        let mut status = [0];
+        sim.transfer(&mut status, &[command]).unwrap();
+        status[0]
+    }
+
+    fn write_fifo(sim: &mut Cc1101Sim, data: &[u8]) {
+        sim.transaction(&mut [Operation::Write(&[0x7F]), Operation::Write(data)])
+            .unwrap();
+    }
+
+    fn read_fifo(sim: &mut Cc1101Sim, len: usize) -> Vec<u8> {
+        let mut data = vec![0; len];
+        sim.transaction(&mut [Operation::Write(&[0xFF]), Operation::Read(&mut data)])
+            .unwrap();
+        data
+    }
+
+    /// Poll MARCSTATE until `state` is reached, as the driver does.
+    fn wait_state(sim: &mut Cc1101Sim, state: u8) -> bool {
+        (0..100).any(|_| read_status(sim, MARCSTATE) == state)
+    }
+
+    /// Variable packet length with address check against `address` and appended status.
+    fn configure(sim: &mut Cc1101Sim, address: u8) {
+        write_register(sim, PKTCTRL0 as u8, 0x05);
+        write_register(sim, PKTCTRL1 as u8, 0x0E);
+        write_register(sim, PKTLEN as u8, 61);
+        write_register(sim, ADDR as u8, address);
+    }
+
+    #[test]
+    fn registers_are_accessed_like_on_the_chip() {
+        let mut sim = Cc1101Sim::new();
+
+        assert_eq!(read_status(&mut sim, VERSION), CHIP_VERSION);
+        assert_eq!(read_status(&mut sim, MARCSTATE), IDLE);
+        assert_eq!(read_register(&mut sim, SYNC1 as u8), 0xD3);
+
+        // Burst write from FREQ2, then read back in burst
+        sim.write(&[FREQ2 as u8 | HEADER_BURST, 0x10, 0xA7, 0x62])
+            .unwrap();
+        let mut buffer = [FREQ2 as u8 | HEADER_READ | HEADER_BURST, 0, 0, 0];
+        sim.transfer_in_place(&mut buffer).unwrap();
+        assert_eq!(buffer[1..], [0x10, 0xA7, 0x62]);
+
+        sim.write(&[PATABLE | HEADER_BURST, 0x12, 0x0E, 0x1D])
+            .unwrap();
+        assert_eq!(sim.patable()[..4], [0x12, 0x0E, 0x1D, 0x00]);
+
+        strobe(&mut sim, SRES);
+        assert_eq!(sim.register(FREQ2 as u8), 0x1E);
+        assert_eq!(sim.patable(), PATABLE_RESET);
+    }
+
+    #[test]
+    fn strobes_drive_the_state_machine() {
+        let mut sim = Cc1101Sim::new();
+
+        strobe(&mut sim, SCAL);
+        assert_eq!(read_status(&mut sim, MARCSTATE), MANCAL);
+        assert!(wait_state(&mut sim, IDLE));
+
+        let status = strobe(&mut sim, SRX);
+        assert_eq!(status >> 4, 0);
+        assert_eq!(strobe(&mut sim, SNOP) >> 4, 1);
+        assert_eq!(read_status(&mut sim, MARCSTATE), marc_state::RX);
+
+        strobe(&mut sim, SIDLE);
+        assert_eq!(read_status(&mut sim, MARCSTATE), IDLE);
+
+        // Power down when CSn goes high, wake up on the next transaction
+        write_register(&mut sim, FSTEST as u8, 0x55);
+        strobe(&mut sim, SPWD);
+        assert_eq!(sim.marc_state(), SLEEP);
+        assert_eq!(read_status(&mut sim, MARCSTATE), IDLE);
+        assert_eq!(sim.register(FSTEST as u8), 0x59);
+    }
+
+    #[test]
+    fn linked_radios_exchange_packets() {
+        let (mut a, mut b) = Cc1101Sim::linked_pair();
+        configure(&mut a, 0x01);
+        configure(&mut b, 0x02);
+        b.set_rssi(-80);
+
+        strobe(&mut b, SRX);
+        write_fifo(&mut a, &[4, 0x02, 0x01, 0xCA, 0xFE]);
+        strobe(&mut a, STX);
+        assert!(wait_state(&mut a, TX));
+        assert!(wait_state(&mut a, IDLE));
+        assert_eq!(a.transmitted(), [vec![4, 0x02, 0x01, 0xCA, 0xFE]]);
+
+        assert!(wait_state(&mut b, IDLE));
+        assert_ne!(read_status(&mut b, PKTSTATUS) & 0x80, 0);
+        assert_eq!(read_status(&mut b, RXBYTES), 7);
+        let data = read_fifo(&mut b, 7);
+        assert_eq!(data[..5], [4, 0x02, 0x01, 0xCA, 0xFE]);
+        assert_eq!(data[5], ((-80 + 74) * 2) as u8);
+        assert_eq!(data[6] & 0x80, 0x80);
+
+        // Packets to another address are filtered out
+        strobe(&mut b, SRX);
+        write_fifo(&mut a, &[2, 0x03, 0x01]);
+        strobe(&mut a, STX);
+        assert!(wait_state(&mut a, IDLE));
+        for _ in 0..10 {
+            assert_eq!(read_status(&mut b, MARCSTATE), marc_state::RX);
+        }
+        assert_eq!(read_status(&mut b, RXBYTES), 0);
+    }
+
+    #[test]
+    fn mismatched_configuration_does_not_receive() {
+        let (mut a, mut b) = Cc1101Sim::linked_pair();
+        write_register(&mut b, CHANNR as u8, 3);
+
+        strobe(&mut b, SRX);
+        write_fifo(&mut a, &[3, 1, 2, 3]);
+        strobe(&mut a, STX);
+        assert!(wait_state(&mut a, IDLE));
+        for _ in 0..10 {
+            assert_eq!(read_status(&mut b, RXBYTES), 0);
+        }
+    }
+
+    #[test]
+    fn carrier_offset_is_estimated() {
+        let (mut a, mut b) = Cc1101Sim::linked_pair();
+        configure(&mut a, 0x01);
+        configure(&mut b, 0x02);
+
+        // 20 kHz above the receiver, within the 203 kHz channel filter
+        a.set_frequency_error(20_000);
+        strobe(&mut b, SRX);
+        write_fifo(&mut a, &[2, 0x02, 0x01]);
+        strobe(&mut a, STX);
+        assert!(wait_state(&mut a, IDLE));
+        assert!(wait_state(&mut b, IDLE));
+        assert_eq!(read_status(&mut b, FREQEST), 12);
+
+        // The frequency offset of the receiver follows the carrier
+        write_register(&mut b, FSCTRL0 as u8, 12);
+        strobe(&mut b, SFRX);
+        strobe(&mut b, SRX);
+        write_fifo(&mut a, &[2, 0x02, 0x01]);
+        strobe(&mut a, STX);
+        assert!(wait_state(&mut a, IDLE));
+        assert!(wait_state(&mut b, IDLE));
+        assert_eq!(read_status(&mut b, FREQEST), 0);
+
+        // Out of the channel filter
+        a.set_frequency_error(150_000);
+        strobe(&mut b, SFRX);
+        strobe(&mut b, SRX);
+        write_fifo(&mut a, &[2, 0x02, 0x01]);
+        strobe(&mut a, STX);
+        assert!(wait_state(&mut a, IDLE));
+        for _ in 0..10 {
+            assert_eq!(read_status(&mut b, RXBYTES), 0);
+        }
+    }
+
+    #[test]
+    fn corrupted_packet_is_flushed() {
+        let mut sim = Cc1101Sim::new();
+        configure(&mut sim, 0x02);
+        sim.inject(SimFault::CorruptPackets(1));
+
+        strobe(&mut sim, SRX);
+        sim.deliver(&[3, 0x02, 0xAB, 0xCD]);
+        assert!(wait_state(&mut sim, IDLE));
+        assert_eq!(read_status(&mut sim, PKTSTATUS) & 0x80, 0);
+        assert_eq!(read_status(&mut sim, RXBYTES), 0);
+
+        strobe(&mut sim, SRX);
+        sim.deliver(&[3, 0x02, 0xAB, 0xCD]);
+        assert!(wait_state(&mut sim, IDLE));
+        assert_eq!(read_status(&mut sim, RXBYTES), 6);
+    }
+
+    #[test]
+    fn fifo_errors_are_reported() {
+        let mut sim = Cc1101Sim::new();
+
+        // Fixed length of 10 bytes with only 4 bytes written
+        write_register(&mut sim, PKTCTRL0 as u8, 0x04);
+        write_register(&mut sim, PKTLEN as u8, 10);
+        write_fifo(&mut sim, &[1, 2, 3, 4]);
+        strobe(&mut sim, STX);
+        assert!(wait_state(&mut sim, TXFIFO_UNDERFLOW));
+        assert_eq!(read_status(&mut sim, TXBYTES) & 0x80, 0x80);
+        strobe(&mut sim, SIDLE);
+        assert_eq!(read_status(&mut sim, MARCSTATE), TXFIFO_UNDERFLOW);
+        strobe(&mut sim, SFTX);
+        assert_eq!(read_status(&mut sim, MARCSTATE), IDLE);
+
+        // Packet of the size of the FIFO, followed by the appended status
+        write_register(&mut sim, PKTLEN as u8, SIM_FIFO_SIZE as u8);
+        strobe(&mut sim, SRX);
+        sim.deliver(&[0x5A; SIM_FIFO_SIZE]);
+        assert!(wait_state(&mut sim, RXFIFO_OVERFLOW));
+        assert_eq!(read_status(&mut sim, RXBYTES), 0x80 | SIM_FIFO_SIZE as u8);
+        strobe(&mut sim, SFRX);
+        assert_eq!(read_status(&mut sim, MARCSTATE), IDLE);
+    }
+
+    #[test]
+    fn faults_are_injected() {
+        let mut sim = Cc1101Sim::new();
+
+        sim.inject(SimFault::FailTransactions(2));
+        assert_eq!(sim.write(&[SNOP]), Err(SimError));
+        assert_eq!(sim.write(&[SNOP]), Err(SimError));
+        assert_eq!(sim.write(&[SNOP]), Ok(()));
+
+        sim.inject(SimFault::NotResponding);
+        assert_eq!(read_status(&mut sim, VERSION), 0xFF);
+        sim.clear_faults();
+        assert_eq!(read_status(&mut sim, VERSION), CHIP_VERSION);
+
+        // A hanging chip ignores the strobes until it is reset
+        sim.inject(SimFault::StuckState(FSTXON));
+        strobe(&mut sim, SRX);
+        assert_eq!(read_status(&mut sim, MARCSTATE), FSTXON);
+        strobe(&mut sim, SRES);
+        assert_eq!(read_status(&mut sim, MARCSTATE), IDLE);
+
+        let (mut a, mut b) = Cc1101Sim::linked_pair();
+        a.inject(SimFault::DropPackets(1));
+        strobe(&mut b, SRX);
+        write_fifo(&mut a, &[3, 1, 2, 3]);
+        strobe(&mut a, STX);
+        assert!(wait_state(&mut a, IDLE));
+        assert_eq!(a.transmitted().len(), 1);
+        for _ in 0..10 {
+            assert_eq!(read_status(&mut b, MARCSTATE), marc_state::RX);
+        }
+    }
+}
@@ -0,0 +1,239 @@
+use crate::clock::Timestamp;
+use crate::Cc1101WrapperError;
+
+/// Number of samples kept by the sliding windows of the link statistics
+pub const STATS_WINDOW: usize = 16;
+
+/// Minimum, maximum and mean of the samples of a sliding window
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct SampleSummary {
+    pub min: i16,
+    pub max: i16,
+    pub mean: i16,
+    /// Number of samples in the window, up to `STATS_WINDOW`
+    pub count: u8,
+}
+
+/// Link quality statistics, e.g. for the housekeeping telemetry. Counters wrap around.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct LinkStats {
+    /// Packets transmitted
+    pub packets_sent: u32,
+    /// Valid packets received, including those dropped on a full receive queue
+    pub packets_received: u32,
+    /// Packets received with invalid CRC
+    pub crc_errors: u32,
+    /// RF operations timed out
+    pub timeouts: u32,
+    /// RX FIFO overflows
+    pub rx_overflows: u32,
+    /// TX FIFO underflows
+    pub tx_underflows: u32,
+    /// Transmissions given up on a busy channel
+    pub channel_busy: u32,
+    /// RSSI of the last valid packets [dBm]
+    pub rssi: Option<SampleSummary>,
+    /// LQI of the last valid packets, lower is better
+    pub lqi: Option<SampleSummary>,
+    /// RSSI sampled in Rx state while no packet is received [dBm]
+    pub noise_floor: Option<SampleSummary>,
+    /// Time since the last valid packet [ms]
+    pub since_last_packet_ms: Option<u64>,
+    /// Output power level of the transmissions [dBm]
+    pub tx_power_dbm: i8,
+    /// Frequency offset compensated [Hz]
+    pub freq_offset_hz: i32,
+    /// Channel currently used
+    pub channel: u8,
+}
+
+/// Sliding window of the last `STATS_WINDOW` samples
+#[derive(Default)]
+struct Window {
+    samples: [i16; STATS_WINDOW],
+    len: usize,
+    next: usize,
+}
+
+impl Window {
+    fn push(&mut self, sample: i16) {
+        self.samples[self.next] = sample;
+        self.next = (self.next + 1) % STATS_WINDOW;
+        self.len = (self.len + 1).min(STATS_WINDOW);
+    }
+
+    fn summary(&self) -> Option<SampleSummary> {
+        let samples = &self.samples[..self.len];
+        let sum: i32 = samples.iter().map(|&sample| sample as i32).sum();
+
+        Some(SampleSummary {
+            min: *samples.iter().min()?,
+            max: *samples.iter().max()?,
+            mean: (sum / samples.len() as i32) as i16,
+            count: samples.len() as u8,
+        })
+    }
+}
+
+/// Collects the link statistics of the wrapper
+#[derive(Default)]
+pub(crate) struct LinkMonitor {
+    counters: LinkStats,
+    rssi: Window,
+    lqi: Window,
+    noise_floor: Window,
+    last_packet: Option<Timestamp>,
+}
+
+impl LinkMonitor {
+    pub fn packet_sent(&mut self) {
+        self.counters.packets_sent = self.counters.packets_sent.wrapping_add(1);
+    }
+
+    pub fn packet_received(&mut self, rssi: i16, lqi: u8, now: Timestamp) {
+        self.counters.packets_received = self.counters.packets_received.wrapping_add(1);
+        self.rssi.push(rssi);
+        self.lqi.push(lqi as i16);
+        self.last_packet = Some(now);
+    }
+
+    pub fn noise_sampled(&mut self, rssi: i16) {
+        self.noise_floor.push(rssi);
+    }
+
+    pub fn error(&mut self, error: Cc1101WrapperError) {
+        let counter = match error {
+            Cc1101WrapperError::CrcMismatch => &mut self.counters.crc_errors,
+            Cc1101WrapperError::TimeoutError => &mut self.counters.timeouts,
+            Cc1101WrapperError::RxOverflow => &mut self.counters.rx_overflows,
+            Cc1101WrapperError::TxUnderflow => &mut self.counters.tx_underflows,
+            Cc1101WrapperError::ChannelBusy => &mut self.counters.channel_busy,
+            _ => return,
+        };
+        *counter = counter.wrapping_add(1);
+    }
+
+    pub fn stats(&self, now: Timestamp) -> LinkStats {
+        LinkStats {
+            rssi: self.rssi.summary(),
+            lqi: self.lqi.summary(),
+            noise_floor: self.noise_floor.summary(),
+            since_last_packet_ms: self.last_packet.map(|last| (now - last).to_millis()),
+            ..self.counters
+        }
+    }
+
+    /// Clear the counters and the windows, keeping the time of the last packet.
+    pub fn reset(&mut self) {
+        *self = Self {
+            last_packet: self.last_packet,
+            ..Self::default()
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_window_has_no_summary() {
+        assert_eq!(Window::default().summary(), None);
+    }
+
+    #[test]
+    fn window_summarizes_its_samples() {
+        let mut window = Window::default();
+        for sample in [-90, -70, -81] {
+            window.push(sample);
+        }
+        assert_eq!(
+            window.summary(),
+            Some(SampleSummary {
+                min: -90,
+                max: -70,
+                mean: -80,
+                count: 3,
+            })
+        );
+    }
+
+    #[test]
+    fn window_keeps_the_last_samples() {
+        let mut window = Window::default();
+        for sample in 0..STATS_WINDOW as i16 + 4 {
+            window.push(sample);
+        }
+
+        // The first 4 samples were overwritten
+        assert_eq!(
+            window.summary(),
+            Some(SampleSummary {
+                min: 4,
+                max: STATS_WINDOW as i16 + 3,
+                mean: 11,
+                count: STATS_WINDOW as u8,
+            })
+        );
+    }
+
+    #[test]
+    fn window_mean_does_not_overflow() {
+        let mut window = Window::default();
+        for _ in 0..STATS_WINDOW {
+            window.push(i16::MIN);
+        }
+        assert_eq!(window.summary().map(|summary| summary.mean), Some(i16::MIN));
+    }
+
+    #[test]
+    fn errors_are_counted_by_kind() {
+        let mut monitor = LinkMonitor::default();
+        monitor.error(Cc1101WrapperError::CrcMismatch);
+        monitor.error(Cc1101WrapperError::CrcMismatch);
+        monitor.error(Cc1101WrapperError::TimeoutError);
+        monitor.error(Cc1101WrapperError::RxOverflow);
+        monitor.error(Cc1101WrapperError::TxUnderflow);
+        monitor.error(Cc1101WrapperError::ChannelBusy);
+        monitor.error(Cc1101WrapperError::Spi);
+
+        let stats = monitor.stats(Timestamp::from_ticks(0));
+        assert_eq!(stats.crc_errors, 2);
+        assert_eq!(stats.timeouts, 1);
+        assert_eq!(stats.rx_overflows, 1);
+        assert_eq!(stats.tx_underflows, 1);
+        assert_eq!(stats.channel_busy, 1);
+    }
+
+    #[test]
+    fn counters_wrap_around() {
+        let mut monitor = LinkMonitor::default();
+        monitor.counters.packets_sent = u32::MAX;
+        monitor.packet_sent();
+        assert_eq!(monitor.stats(Timestamp::from_ticks(0)).packets_sent, 0);
+    }
+
+    #[test]
+    fn reset_keeps_the_time_of_the_last_packet() {
+        let mut monitor = LinkMonitor::default();
+        assert_eq!(
+            monitor.stats(Timestamp::from_ticks(0)).since_last_packet_ms,
+            None
+        );
+
+        monitor.packet_received(-60, 10, Timestamp::from_ticks(1_000));
+        monitor.noise_sampled(-100);
+        let stats = monitor.stats(Timestamp::from_ticks(1_500));
+        assert_eq!(stats.packets_received, 1);
+        assert_eq!(stats.lqi.map(|lqi| lqi.mean), Some(10));
+        assert_eq!(stats.noise_floor.map(|noise| noise.mean), Some(-100));
+        assert_eq!(stats.since_last_packet_ms, Some(500));
+
+        monitor.reset();
+        let stats = monitor.stats(Timestamp::from_ticks(2_000));
+        assert_eq!(stats.packets_received, 0);
+        assert_eq!(stats.rssi, None);
+        assert_eq!(stats.noise_floor, None);
+        assert_eq!(stats.since_last_packet_ms, Some(1_000));
+    }
+}
@@ -0,0 +1,100 @@
+// Crystal oscillator frequency [Hz]
+const FXOSC: u32 = 26_000_000;
+
+// Register addresses
+pub(crate) const MCSM2: u8 = 0x16;
+pub(crate) const WOREVT1: u8 = 0x1E;
+pub(crate) const WOREVT0: u8 = 0x1F;
+pub(crate) const WORCTRL: u8 = 0x20;
+
+/// TEST2, TEST1 and TEST0 registers, lost in SLEEP state
+pub(crate) const TEST_REGISTERS: [u8; 3] = [0x2C, 0x2D, 0x2E];
+
+/// Power mode of the transceiver between the RF operations
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RadioPowerMode {
+    /// Receiver always on
+    Continuous,
+    /// Sleep, waking the receiver up periodically to look for a packet
+    WakeOnRadio,
+    /// Sleep, without receiving
+    PowerDown,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum WorError {
+    /// Wake-up period is outside of 1..=1890 ms
+    PeriodOutOfRange,
+    /// Rx time is not in 0..=7
+    InvalidRxTime,
+    /// EVENT1 timeout is not in 0..=7
+    InvalidEvent1,
+}
+
+/// Wake-on-Radio configuration
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct WorConfig {
+    /// Period of the receiver wake-ups, EVENT0 [ms]. The preamble of the packets shall last
+    /// longer than the period.
+    pub period_ms: u16,
+    /// Receive duty cycle, MCSM2.RX_TIME: 12.5 % of the period at 0, halved at each step up
+    /// to 6. At 7 the receiver stays on until a packet is received. Once the sync word is
+    /// found, the packet is received regardless of the duty cycle.
+    pub rx_time: u8,
+    /// Go back to sleep before the end of the Rx time when no carrier is sensed
+    pub carrier_sense_timeout: bool,
+    /// Time for the crystal to start up before the Rx state, WORCTRL.EVENT1 from 0 (4 RC
+    /// oscillator periods) to 7 (48 periods)
+    pub event1: u8,
+    /// Calibrate the RC oscillator timing the wake-ups against the crystal at each wake-up
+    pub rc_calibration: bool,
+}
+
+impl WorConfig {
+    /// Project default: 100 ms period with 12.5 % duty cycle
+    pub const PROJECT: Self = Self {
+        period_ms: 100,
+        rx_time: 0,
+        carrier_sense_timeout: false,
+        event1: 7,
+        rc_calibration: true,
+    };
+
+    /// Check the configuration against the CC1101 capabilities.
+    pub fn validate(&self) -> Result<(), WorError> {
+        if !(1..=1890).contains(&self.period_ms) {
+            return Err(WorError::PeriodOutOfRange);
+        }
+        if self.rx_time > 7 {
+            return Err(WorError::InvalidRxTime);
+        }
+        if self.event1 > 7 {
+            return Err(WorError::InvalidEvent1);
+        }
+
+        Ok(())
+    }
+
+    /// Values of the MCSM2, WOREVT1, WOREVT0 and WORCTRL registers. The EVENT0 resolution
+    /// (WOR_RES) is kept at 2^0 periods, for which the Rx time is specified.
+    pub(crate) fn registers(&self) -> [(u8, u8); 4] {
+        // t_event0 = 750 / fXOSC * EVENT0
+        let event0 = self.period_ms as u32 * (FXOSC / 1000) / 750;
+        let mcsm2 = ((self.carrier_sense_timeout as u8) << 4) | self.rx_time;
+        // RC oscillator powered up, WOR_RES = 0
+        let worctrl = (self.event1 << 4) | ((self.rc_calibration as u8) << 3);
+
+        [
+            (MCSM2, mcsm2),
+            (WOREVT1, (event0 >> 8) as u8),
+            (WOREVT0, event0 as u8),
+            (WORCTRL, worctrl),
+        ]
+    }
+}
+
+impl Default for WorConfig {
+    fn default() -> Self {
+        Self::PROJECT
+    }
+}
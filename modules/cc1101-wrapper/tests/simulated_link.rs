@@ -0,0 +1,1307 @@
+use cc1101_wrapper::sim::{marc_state, Cc1101Sim, SimChannel, SimClock, SimFault, SimGdo};
+use cc1101_wrapper::{
+    hop_channel, AddressFilter, AfcConfig, Cc1101Wrapper, Cc1101WrapperError, ChannelError, Clock,
+    DopplerPoint, FrequencyError, Gdo, GdoEvents, HoppingConfig, LbtConfig, LbtError,
+    ModulationFormat, PacketLength, PowerConfig, PowerError, ProfileError, RadioPowerMode,
+    RadioProfile, RecoveryAction, RecoveryConfig, RecoveryError, SampleSummary, TxCompletion,
+    TxPriority, TxResult, WorConfig, WorError, BROADCAST_ADDRESS,
+};
+use embedded_hal::spi::SpiDevice;
+use futures_executor::block_on;
+
+type SimWrapper = Cc1101Wrapper<Cc1101Sim, SimClock>;
+
+fn profile(address: u8) -> RadioProfile {
+    RadioProfile {
+        packet_length: PacketLength::Variable(61),
+        address_filter: AddressFilter::DeviceLowBroadcast(address),
+        ..RadioProfile::default()
+    }
+}
+
+/// Initialized wrapper in Rx state, with the given device address.
+fn wrapper(sim: &Cc1101Sim, clock: &SimClock, address: u8) -> SimWrapper {
+    let mut wrapper = SimWrapper::new(sim.clone(), clock.clone()).unwrap();
+    wrapper.init_config().unwrap();
+    wrapper.apply_profile(&profile(address)).unwrap();
+    block_on(wrapper.main());
+    wrapper
+}
+
+/// Initialized wrapper in Rx state awaiting the GDO events of the transceiver, which
+/// advances with the clock.
+fn event_wrapper(sim: &Cc1101Sim, clock: &SimClock, address: u8) -> SimWrapper {
+    let events: &'static GdoEvents = Box::leak(Box::new(GdoEvents::new()));
+
+    // Interrupts on the falling edge of GDO2 and the rising edge of GDO0
+    sim.on_gdo_change(SimGdo::Gdo2, move |level| {
+        if !level {
+            events.signal(Gdo::Gdo2);
+        }
+    });
+    sim.on_gdo_change(SimGdo::Gdo0, move |level| {
+        if level {
+            events.signal(Gdo::Gdo0);
+        }
+    });
+    clock.attach(sim);
+
+    let mut wrapper = SimWrapper::new(sim.clone(), clock.clone()).unwrap();
+    wrapper.set_gdo_events(events);
+    wrapper.init_config().unwrap();
+    wrapper.apply_profile(&profile(address)).unwrap();
+    block_on(wrapper.main());
+    wrapper
+}
+
+/// Let the receiver get the packet, then handle the GDO2 (CRC OK) interrupt.
+fn receive(wrapper: &mut SimWrapper, sim: &Cc1101Sim, gdo2_idle: bool) -> bool {
+    for _ in 0..10 {
+        sim.advance(1);
+        if sim.gdo(SimGdo::Gdo2) != gdo2_idle {
+            wrapper.signal_rx_int();
+            block_on(wrapper.main());
+            return true;
+        }
+    }
+    false
+}
+
+#[test]
+fn packets_are_exchanged_between_wrappers() {
+    let (sim_a, sim_b) = Cc1101Sim::linked_pair();
+    let clock = SimClock::new();
+    let mut a = wrapper(&sim_a, &clock, 0x01);
+    let mut b = wrapper(&sim_b, &clock, 0x02);
+    let gdo2_idle = sim_b.gdo(SimGdo::Gdo2);
+
+    a.write_data(0x02, b"ping").unwrap();
+    block_on(a.main());
+    assert_eq!(sim_a.transmitted().len(), 1);
+    assert_eq!(sim_a.marc_state(), marc_state::RX);
+
+    assert!(receive(&mut b, &sim_b, gdo2_idle));
+    assert!(b.is_data_received());
+    let mut data = [0; 64];
+    let (mut rssi, mut lqi) = (0, 0);
+    assert_eq!(b.read_data(&mut data, &mut rssi, &mut lqi), Ok((4, 0x01)));
+    assert_eq!(&data[..4], b"ping");
+
+    // Broadcast packets are received, packets to other addresses are filtered out
+    a.write_data(0x03, b"lost").unwrap();
+    block_on(a.main());
+    assert!(!receive(&mut b, &sim_b, gdo2_idle));
+
+    a.write_data(BROADCAST_ADDRESS, b"beacon").unwrap();
+    block_on(a.main());
+    assert!(receive(&mut b, &sim_b, gdo2_idle));
+    assert_eq!(b.read_data(&mut data, &mut rssi, &mut lqi), Ok((6, 0x01)));
+    assert_eq!(b.read_last_error(), (None, 0));
+}
+
+#[test]
+fn gdo_events_drive_transmission_and_reception() {
+    let (sim_a, sim_b) = Cc1101Sim::linked_pair();
+    let mut clock = SimClock::new();
+    let mut a = event_wrapper(&sim_a, &clock, 0x01);
+    let mut b = event_wrapper(&sim_b, &clock, 0x02);
+    let mut data = [0; 64];
+    let (mut rssi, mut lqi) = (0, 0);
+    let timeout = fugit::ExtU64::millis(100);
+
+    assert_eq!(block_on(a.transmit(0x02, b"ping")), Ok(()));
+    assert_eq!(sim_a.transmitted().len(), 1);
+    assert_eq!(sim_a.marc_state(), marc_state::RX);
+    assert_eq!(
+        block_on(b.receive(&mut data, &mut rssi, &mut lqi, timeout)),
+        Ok((4, 0x01))
+    );
+    assert_eq!(&data[..4], b"ping");
+
+    // Nothing on the air
+    let start = clock.now();
+    assert_eq!(
+        block_on(b.receive(&mut data, &mut rssi, &mut lqi, timeout)),
+        Err(Cc1101WrapperError::TimeoutError)
+    );
+    assert!(clock.now() - start >= timeout);
+
+    // Queued packets, handled by main
+    a.write_data(0x02, b"pong").unwrap();
+    block_on(a.main());
+    clock.advance_ms(10);
+    block_on(b.main());
+    assert_eq!(b.read_data(&mut data, &mut rssi, &mut lqi), Ok((4, 0x01)));
+    assert_eq!(&data[..4], b"pong");
+    assert_eq!(a.read_last_error(), (None, 0));
+    assert_eq!(b.read_last_error(), (None, 0));
+}
+
+#[test]
+fn packets_are_received_without_gdo_events() {
+    let sim = Cc1101Sim::new();
+    let mut wrapper = wrapper(&sim, &SimClock::new(), 0x02);
+    let mut data = [0; 64];
+    let (mut rssi, mut lqi) = (0, 0);
+
+    // Length, destination, source, data
+    sim.deliver(&[4, 0x02, 0x07, b'h', b'i']);
+    assert_eq!(
+        block_on(wrapper.receive(&mut data, &mut rssi, &mut lqi, fugit::ExtU64::millis(100))),
+        Ok((2, 0x07))
+    );
+    assert_eq!(&data[..2], b"hi");
+}
+
+/// Payload larger than the FIFO, with a recognizable pattern
+fn long_payload(length: usize) -> Vec<u8> {
+    (0..length).map(|i| (i % 251) as u8).collect()
+}
+
+#[test]
+fn packets_larger_than_the_fifo_are_exchanged() {
+    let (sim_a, sim_b) = Cc1101Sim::linked_pair();
+    let clock = SimClock::new();
+    let mut a = event_wrapper(&sim_a, &clock, 0x01);
+    let mut b = event_wrapper(&sim_b, &clock, 0x02);
+    let profile = RadioProfile {
+        packet_length: PacketLength::Variable(255),
+        ..profile(0x01)
+    };
+    a.apply_profile(&profile).unwrap();
+    b.apply_profile(&RadioProfile {
+        address_filter: AddressFilter::DeviceLowBroadcast(0x02),
+        ..profile
+    })
+    .unwrap();
+    block_on(b.main());
+    let mut data = [0; 255];
+    let (mut rssi, mut lqi) = (0, 0);
+    let timeout = fugit::ExtU64::millis(100);
+
+    // SPI transactions are faster than the air
+    sim_a.set_bytes_per_transaction(4);
+    sim_b.set_bytes_per_transaction(4);
+
+    let payload = long_payload(253);
+    assert_eq!(block_on(a.transmit(0x02, &payload)), Ok(()));
+    assert_eq!(sim_a.transmitted()[0].len(), 256);
+    assert_eq!(
+        block_on(b.receive(&mut data, &mut rssi, &mut lqi, timeout)),
+        Ok((253, 0x01))
+    );
+    assert_eq!(&data[..253], &payload[..]);
+
+    // Queued packets, handled by main
+    a.write_data(0x02, &payload[..200]).unwrap();
+    block_on(a.main());
+    clock.advance_ms(10);
+    block_on(b.main());
+    assert_eq!(b.read_data(&mut data, &mut rssi, &mut lqi), Ok((200, 0x01)));
+    assert_eq!(&data[..200], &payload[..200]);
+    assert_eq!(a.read_last_error(), (None, 0));
+    assert_eq!(b.read_last_error(), (None, 0));
+}
+
+#[test]
+fn packets_larger_than_the_fifo_are_polled_without_gdo_events() {
+    let (sim_a, sim_b) = Cc1101Sim::linked_pair();
+    let clock = SimClock::new();
+    let mut a = wrapper(&sim_a, &clock, 0x01);
+    let mut b = wrapper(&sim_b, &clock, 0x02);
+    clock.attach(&sim_a);
+    clock.attach(&sim_b);
+    let profile = RadioProfile {
+        packet_length: PacketLength::Fixed(200),
+        address_filter: AddressFilter::Disabled,
+        ..RadioProfile::default()
+    };
+    a.apply_profile(&profile).unwrap();
+    b.apply_profile(&profile).unwrap();
+    block_on(b.main());
+    let mut data = [0; 255];
+    let (mut rssi, mut lqi) = (0, 0);
+
+    sim_a.set_bytes_per_transaction(4);
+    sim_b.set_bytes_per_transaction(4);
+
+    let payload = long_payload(200);
+    assert_eq!(block_on(a.transmit(BROADCAST_ADDRESS, &payload)), Ok(()));
+    assert_eq!(sim_a.transmitted()[0], payload);
+    assert_eq!(
+        block_on(b.receive(&mut data, &mut rssi, &mut lqi, fugit::ExtU64::millis(100))),
+        Ok((200, BROADCAST_ADDRESS))
+    );
+    assert_eq!(&data[..200], &payload[..]);
+}
+
+#[test]
+fn infinite_packets_carry_their_length() {
+    let (sim_a, sim_b) = Cc1101Sim::linked_pair();
+    let clock = SimClock::new();
+    let mut a = event_wrapper(&sim_a, &clock, 0x01);
+    let mut b = event_wrapper(&sim_b, &clock, 0x02);
+    let profile = RadioProfile {
+        packet_length: PacketLength::Infinite,
+        address_filter: AddressFilter::Disabled,
+        ..RadioProfile::default()
+    };
+    a.apply_profile(&profile).unwrap();
+    b.apply_profile(&profile).unwrap();
+    block_on(b.main());
+    let mut data = [0; 1000];
+    let (mut rssi, mut lqi) = (0, 0);
+    let timeout = fugit::ExtU64::millis(100);
+
+    sim_a.set_bytes_per_transaction(4);
+    sim_b.set_bytes_per_transaction(4);
+
+    // Data length, then data
+    let payload = long_payload(1000);
+    assert_eq!(block_on(a.transmit(BROADCAST_ADDRESS, &payload)), Ok(()));
+    let sent = sim_a.transmitted();
+    assert_eq!(sent[0][..2], [0x03, 0xE8]);
+    assert_eq!(sent[0][2..], payload[..]);
+    assert_eq!(sim_a.marc_state(), marc_state::RX);
+
+    assert_eq!(
+        block_on(b.receive(&mut data, &mut rssi, &mut lqi, timeout)),
+        Ok((1000, BROADCAST_ADDRESS))
+    );
+    assert_eq!(&data[..], &payload[..]);
+    assert_eq!(sim_b.marc_state(), marc_state::RX);
+
+    // Short packets as well
+    assert_eq!(block_on(a.transmit(BROADCAST_ADDRESS, b"ping")), Ok(()));
+    assert_eq!(sim_a.transmitted()[1], [0, 4, b'p', b'i', b'n', b'g']);
+    assert_eq!(
+        block_on(b.receive(&mut data, &mut rssi, &mut lqi, timeout)),
+        Ok((4, BROADCAST_ADDRESS))
+    );
+    assert_eq!(&data[..4], b"ping");
+
+    // Larger than the receive buffer
+    assert_eq!(block_on(a.transmit(BROADCAST_ADDRESS, &[0; 1001])), Ok(()));
+    assert_eq!(
+        block_on(b.receive(&mut data, &mut rssi, &mut lqi, timeout)),
+        Err(Cc1101WrapperError::InvalidLength)
+    );
+    assert_eq!(sim_b.marc_state(), marc_state::RX);
+}
+
+#[test]
+fn infinite_packets_are_checked_by_crc() {
+    let (sim_a, sim_b) = Cc1101Sim::linked_pair();
+    let clock = SimClock::new();
+    let mut a = event_wrapper(&sim_a, &clock, 0x01);
+    let mut b = event_wrapper(&sim_b, &clock, 0x02);
+    let profile = RadioProfile {
+        packet_length: PacketLength::Infinite,
+        address_filter: AddressFilter::Disabled,
+        ..RadioProfile::default()
+    };
+    a.apply_profile(&profile).unwrap();
+    b.apply_profile(&profile).unwrap();
+    block_on(b.main());
+    let mut data = [0; 1000];
+    let (mut rssi, mut lqi) = (0, 0);
+    let timeout = fugit::ExtU64::millis(100);
+
+    sim_a.set_bytes_per_transaction(4);
+    sim_b.set_bytes_per_transaction(4);
+
+    // Corrupted on the air, the end of the packet is received in fixed packet length mode
+    let payload = long_payload(600);
+    sim_b.inject(SimFault::CorruptPackets(1));
+    assert_eq!(block_on(a.transmit(BROADCAST_ADDRESS, &payload)), Ok(()));
+    assert_eq!(
+        block_on(b.receive(&mut data, &mut rssi, &mut lqi, timeout)),
+        Err(Cc1101WrapperError::CrcMismatch)
+    );
+    assert_eq!(sim_b.marc_state(), marc_state::RX);
+
+    // Back to infinite packet length mode for the next packet
+    assert_eq!(block_on(a.transmit(BROADCAST_ADDRESS, &payload)), Ok(()));
+    assert_eq!(
+        block_on(b.receive(&mut data, &mut rssi, &mut lqi, timeout)),
+        Ok((600, BROADCAST_ADDRESS))
+    );
+    assert_eq!(&data[..600], &payload[..]);
+
+    // No packet length to end a packet of 256 bytes with its data length
+    assert_eq!(
+        block_on(a.transmit(BROADCAST_ADDRESS, &[0; 254])),
+        Err(Cc1101WrapperError::InvalidLength)
+    );
+}
+
+#[test]
+fn rx_fifo_overflow_is_recovered() {
+    let (sim_a, sim_b) = Cc1101Sim::linked_pair();
+    let clock = SimClock::new();
+    let mut a = event_wrapper(&sim_a, &clock, 0x01);
+    let mut b = event_wrapper(&sim_b, &clock, 0x02);
+    let profile = RadioProfile {
+        packet_length: PacketLength::Variable(255),
+        ..profile(0x01)
+    };
+    a.apply_profile(&profile).unwrap();
+    b.apply_profile(&RadioProfile {
+        address_filter: AddressFilter::DeviceLowBroadcast(0x02),
+        ..profile
+    })
+    .unwrap();
+    block_on(b.main());
+    sim_a.set_bytes_per_transaction(4);
+
+    // The receiver is not serviced while the packet fills the RX FIFO
+    assert_eq!(block_on(a.transmit(0x02, &long_payload(200))), Ok(()));
+    sim_b.advance(20);
+    assert_eq!(sim_b.marc_state(), marc_state::RXFIFO_OVERFLOW);
+
+    block_on(b.main());
+    assert_eq!(
+        b.read_last_error(),
+        (Some(Cc1101WrapperError::RxOverflow), 1)
+    );
+    assert!(!b.is_data_received());
+    assert_eq!(sim_b.marc_state(), marc_state::RX);
+
+    // Next packets are received
+    assert_eq!(block_on(a.transmit(0x02, b"ping")), Ok(()));
+    clock.advance_ms(2);
+    block_on(b.main());
+    let mut data = [0; 64];
+    let (mut rssi, mut lqi) = (0, 0);
+    assert_eq!(b.read_data(&mut data, &mut rssi, &mut lqi), Ok((4, 0x01)));
+}
+
+#[test]
+fn queued_packets_are_sent_by_priority() {
+    let sim = Cc1101Sim::new();
+    let mut wrapper = wrapper(&sim, &SimClock::new(), 0x01);
+
+    let low = wrapper
+        .write_packet(0x02, b"low", TxPriority::Low, true)
+        .unwrap();
+    wrapper.write_data(0x02, b"normal").unwrap();
+    let high = wrapper
+        .write_packet(0x02, b"high", TxPriority::High, true)
+        .unwrap();
+    wrapper.write_data(0x02, b"later").unwrap();
+    assert_eq!(
+        wrapper.write_data(0x02, b"dropped"),
+        Err(Cc1101WrapperError::TxBufferBusy)
+    );
+    assert_eq!(wrapper.tx_pending(), 4);
+
+    block_on(wrapper.main());
+    assert_eq!(wrapper.tx_pending(), 0);
+
+    // Over-the-air bytes: length, destination, source, data
+    let sent: Vec<Vec<u8>> = sim.transmitted().iter().map(|p| p[3..].to_vec()).collect();
+    assert_eq!(sent, [&b"high"[..], b"normal", b"later", b"low"]);
+
+    let sent = TxResult::Sent;
+    assert_eq!(
+        wrapper.tx_completion(),
+        Some(TxCompletion {
+            id: high,
+            result: sent
+        })
+    );
+    assert_eq!(
+        wrapper.tx_completion(),
+        Some(TxCompletion {
+            id: low,
+            result: sent
+        })
+    );
+    assert_eq!(wrapper.tx_completion(), None);
+}
+
+#[test]
+fn failed_transmission_is_notified() {
+    let sim = Cc1101Sim::new();
+    let mut wrapper = wrapper(&sim, &SimClock::new(), 0x01);
+
+    sim.inject(SimFault::StuckState(marc_state::FSTXON));
+    let id = wrapper
+        .write_packet(0x02, b"ping", TxPriority::Normal, true)
+        .unwrap();
+    block_on(wrapper.main());
+
+    let completion = wrapper.tx_completion().unwrap();
+    assert_eq!(completion.id, id);
+    assert_eq!(
+        completion.result,
+        TxResult::Failed(Cc1101WrapperError::TimeoutError)
+    );
+}
+
+#[test]
+fn received_packets_are_queued() {
+    let sim = Cc1101Sim::new();
+    let mut wrapper = wrapper(&sim, &SimClock::new(), 0x02);
+    let gdo2_idle = sim.gdo(SimGdo::Gdo2);
+
+    // Length, destination, source, data
+    for source in 0x10..0x15 {
+        sim.deliver(&[4, 0x02, source, b'h', b'i']);
+        assert!(receive(&mut wrapper, &sim, gdo2_idle));
+    }
+
+    // Packets beyond the queue depth are dropped
+    assert_eq!(
+        wrapper.read_last_error(),
+        (Some(Cc1101WrapperError::RxQueueFull), 1)
+    );
+
+    let mut data = [0; 64];
+    let (mut rssi, mut lqi) = (0, 0);
+    for source in 0x10..0x14 {
+        assert_eq!(
+            wrapper.read_data(&mut data, &mut rssi, &mut lqi),
+            Ok((2, source))
+        );
+        assert_eq!(&data[..2], b"hi");
+    }
+    assert!(!wrapper.is_data_received());
+    assert_eq!(
+        wrapper.read_data(&mut data, &mut rssi, &mut lqi),
+        Err(Cc1101WrapperError::RxBufferEmpty)
+    );
+}
+
+#[test]
+fn busy_channel_defers_transmission() {
+    let channel = SimChannel::new();
+    let sim = Cc1101Sim::on_channel(&channel);
+    let mut clock = SimClock::new();
+    let mut wrapper = wrapper(&sim, &clock, 0x01);
+    let lbt = LbtConfig::default();
+
+    channel.set_busy(true);
+    let start = clock.now();
+    let id = wrapper
+        .write_packet(0x02, b"ping", TxPriority::Normal, true)
+        .unwrap();
+    block_on(wrapper.main());
+
+    let completion = wrapper.tx_completion().unwrap();
+    assert_eq!(completion.id, id);
+    assert_eq!(completion.result, TxResult::ChannelBusy);
+    assert!(sim.transmitted().is_empty());
+    let backoff = (clock.now() - start).to_millis();
+    assert!(backoff >= lbt.max_retries as u64 * lbt.backoff_min_ms as u64);
+    assert_eq!(sim.marc_state(), marc_state::RX);
+
+    // Sent once the channel is clear
+    channel.set_busy(false);
+    wrapper
+        .write_packet(0x02, b"ping", TxPriority::Normal, true)
+        .unwrap();
+    block_on(wrapper.main());
+    assert_eq!(wrapper.tx_completion().unwrap().result, TxResult::Sent);
+    assert_eq!(sim.transmitted().len(), 1);
+
+    // Sent regardless of the channel without Listen Before Talk
+    wrapper.set_lbt_config(&LbtConfig::DISABLED).unwrap();
+    channel.set_busy(true);
+    wrapper
+        .write_packet(0x02, b"ping", TxPriority::Normal, true)
+        .unwrap();
+    block_on(wrapper.main());
+    assert_eq!(wrapper.tx_completion().unwrap().result, TxResult::Sent);
+    assert_eq!(sim.transmitted().len(), 2);
+}
+
+#[test]
+fn link_stats_track_the_traffic() {
+    let (sim_a, sim_b) = Cc1101Sim::linked_pair();
+    let clock = SimClock::new();
+    let mut a = event_wrapper(&sim_a, &clock, 0x01);
+    let mut b = event_wrapper(&sim_b, &clock, 0x02);
+    let mut data = [0; 64];
+    let (mut rssi, mut lqi) = (0, 0);
+    let timeout = fugit::ExtU64::millis(100);
+
+    assert_eq!(b.link_stats().since_last_packet_ms, None);
+
+    for (rssi_dbm, link_quality) in [(-80, 10), (-60, 30)] {
+        sim_b.set_rssi(rssi_dbm);
+        sim_b.set_link_quality(link_quality);
+        assert_eq!(block_on(a.transmit(0x02, b"ping")), Ok(()));
+        assert!(block_on(b.receive(&mut data, &mut rssi, &mut lqi, timeout)).is_ok());
+    }
+
+    // Packets with CRC mismatch are not flushed, to be reported
+    b.apply_profile(&RadioProfile {
+        crc_autoflush: false,
+        ..profile(0x02)
+    })
+    .unwrap();
+    block_on(b.main());
+    sim_b.inject(SimFault::CorruptPackets(1));
+    assert_eq!(block_on(a.transmit(0x02, b"ping")), Ok(()));
+    assert_eq!(
+        block_on(b.receive(&mut data, &mut rssi, &mut lqi, timeout)),
+        Err(Cc1101WrapperError::CrcMismatch)
+    );
+    clock.advance_ms(500);
+
+    let stats = a.link_stats();
+    assert_eq!(stats.packets_sent, 3);
+    assert_eq!(stats.packets_received, 0);
+
+    let stats = b.link_stats();
+    assert_eq!(stats.packets_received, 2);
+    assert_eq!(stats.crc_errors, 1);
+    assert_eq!(
+        stats.rssi,
+        Some(SampleSummary {
+            min: -80,
+            max: -60,
+            mean: -70,
+            count: 2
+        })
+    );
+    assert_eq!(
+        stats.lqi,
+        Some(SampleSummary {
+            min: 10,
+            max: 30,
+            mean: 20,
+            count: 2
+        })
+    );
+    assert!(stats.since_last_packet_ms.unwrap() >= 500);
+
+    // Noise floor is sampled by the monitoring, while no packet is received
+    assert_eq!(stats.noise_floor, None);
+    clock.advance_ms(1000);
+    block_on(b.main());
+    assert_eq!(b.link_stats().noise_floor.map(|noise| noise.count), Some(1));
+
+    b.reset_link_stats();
+    let stats = b.link_stats();
+    assert_eq!(stats.packets_received, 0);
+    assert_eq!(stats.rssi, None);
+    assert!(stats.since_last_packet_ms.is_some());
+}
+
+#[test]
+fn invalid_lbt_config_is_rejected() {
+    let sim = Cc1101Sim::new();
+    let mut wrapper = wrapper(&sim, &SimClock::new(), 0x01);
+
+    let config = LbtConfig {
+        carrier_sense_threshold: 8,
+        ..LbtConfig::default()
+    };
+    assert_eq!(
+        wrapper.set_lbt_config(&config),
+        Err(Cc1101WrapperError::InvalidLbtConfig(
+            LbtError::ThresholdOutOfRange
+        ))
+    );
+
+    let config = LbtConfig {
+        backoff_min_ms: 10,
+        backoff_max_ms: 5,
+        ..LbtConfig::default()
+    };
+    assert_eq!(
+        wrapper.set_lbt_config(&config),
+        Err(Cc1101WrapperError::InvalidLbtConfig(
+            LbtError::InvalidBackoff
+        ))
+    );
+    assert_eq!(wrapper.current_lbt_config(), &LbtConfig::default());
+}
+
+#[test]
+fn output_power_is_mapped_to_the_patable() {
+    const FREND0: u8 = 0x22;
+    let sim = Cc1101Sim::new();
+    let mut wrapper = wrapper(&sim, &SimClock::new(), 0x01);
+
+    // Project default at 433 MHz
+    assert_eq!(sim.patable()[0], 0xC0);
+    assert_eq!(sim.register(FREND0) & 0x07, 0);
+    assert_eq!(wrapper.link_stats().tx_power_dbm, 10);
+
+    // Highest level not above the requested power
+    assert_eq!(wrapper.set_tx_power(3), Ok(0));
+    assert_eq!(sim.patable()[0], 0x60);
+    assert_eq!(wrapper.tx_power(), 0);
+
+    // Another frequency band
+    let profile = RadioProfile {
+        frequency: 868_000_000,
+        ..*wrapper.current_profile()
+    };
+    wrapper.apply_profile(&profile).unwrap();
+    assert_eq!(sim.patable()[0], 0x50);
+
+    // OOK ramps from the carrier off up to the output power
+    let profile = RadioProfile {
+        frequency: 433_000_000,
+        modulation: ModulationFormat::OnOffKeying,
+        ..profile
+    };
+    wrapper.apply_profile(&profile).unwrap();
+    let config = PowerConfig {
+        dbm: 10,
+        ramp_steps: 4,
+    };
+    wrapper.set_power_config(&config).unwrap();
+    assert_eq!(sim.patable()[..5], [0x00, 0x0E, 0x34, 0x84, 0xC0]);
+    assert_eq!(sim.register(FREND0) & 0x07, 4);
+
+    assert_eq!(
+        wrapper.set_tx_power(11),
+        Err(Cc1101WrapperError::InvalidPowerConfig(
+            PowerError::OutOfRange
+        ))
+    );
+    let config = PowerConfig {
+        ramp_steps: 8,
+        ..config
+    };
+    assert_eq!(
+        wrapper.set_power_config(&config),
+        Err(Cc1101WrapperError::InvalidPowerConfig(
+            PowerError::RampTooLong
+        ))
+    );
+    assert_eq!(wrapper.current_power_config().ramp_steps, 4);
+}
+
+#[test]
+fn wake_on_radio_receives_packets_while_asleep() {
+    let (sim_a, sim_b) = Cc1101Sim::linked_pair();
+    let clock = SimClock::new();
+    let mut a = event_wrapper(&sim_a, &clock, 0x01);
+    let mut b = event_wrapper(&sim_b, &clock, 0x02);
+    let mut data = [0; 64];
+    let (mut rssi, mut lqi) = (0, 0);
+
+    b.set_radio_power_mode(RadioPowerMode::WakeOnRadio);
+    block_on(b.main());
+    assert_eq!(sim_b.marc_state(), marc_state::SLEEP);
+    // EVENT0 of 100 ms, EVENT1 of 48 periods with RC oscillator calibration
+    assert_eq!(sim_b.register(0x1E), 0x0D);
+    assert_eq!(sim_b.register(0x1F), 0x8A);
+    assert_eq!(sim_b.register(0x20), 0x78);
+
+    // Nothing to do, the transceiver keeps sleeping
+    clock.advance_ms(10);
+    block_on(b.main());
+    assert_eq!(sim_b.marc_state(), marc_state::SLEEP);
+
+    // The packet wakes the receiver up
+    assert_eq!(block_on(a.transmit(0x02, b"wake")), Ok(()));
+    clock.advance_ms(10);
+    block_on(b.main());
+    assert_eq!(b.read_data(&mut data, &mut rssi, &mut lqi), Ok((4, 0x01)));
+    assert_eq!(&data[..4], b"wake");
+    assert_eq!(sim_b.marc_state(), marc_state::SLEEP);
+
+    // Back to the Rx state
+    b.set_radio_power_mode(RadioPowerMode::Continuous);
+    block_on(b.main());
+    assert_eq!(sim_b.marc_state(), marc_state::RX);
+    assert_eq!(b.read_last_error(), (None, 0));
+
+    let config = WorConfig {
+        period_ms: 2000,
+        ..WorConfig::default()
+    };
+    assert_eq!(
+        b.set_wor_config(&config),
+        Err(Cc1101WrapperError::InvalidWorConfig(
+            WorError::PeriodOutOfRange
+        ))
+    );
+}
+
+#[test]
+fn power_down_restores_the_registers_lost_in_sleep() {
+    const TEST0: u8 = 0x2E;
+    let sim = Cc1101Sim::new();
+    let mut wrapper = wrapper(&sim, &SimClock::new(), 0x01);
+
+    let profile = RadioProfile {
+        modulation: ModulationFormat::OnOffKeying,
+        ..*wrapper.current_profile()
+    };
+    wrapper.apply_profile(&profile).unwrap();
+    let config = PowerConfig {
+        dbm: 10,
+        ramp_steps: 2,
+    };
+    wrapper.set_power_config(&config).unwrap();
+    sim.clone().write(&[TEST0, 0x09]).unwrap();
+
+    wrapper.set_radio_power_mode(RadioPowerMode::PowerDown);
+    block_on(wrapper.main());
+    assert_eq!(sim.marc_state(), marc_state::SLEEP);
+
+    // Queued packets wake the transceiver up
+    wrapper.write_data(0x02, b"beacon").unwrap();
+    block_on(wrapper.main());
+    assert_eq!(sim.transmitted().len(), 1);
+    assert_eq!(sim.marc_state(), marc_state::SLEEP);
+
+    wrapper.set_radio_power_mode(RadioPowerMode::Continuous);
+    block_on(wrapper.main());
+    assert_eq!(sim.marc_state(), marc_state::RX);
+    assert_eq!(sim.patable()[..3], [0x00, 0x34, 0xC0]);
+    assert_eq!(sim.register(TEST0), 0x09);
+    assert_eq!(wrapper.read_last_error(), (None, 0));
+}
+
+#[test]
+fn monitoring_is_paused_while_asleep() {
+    let sim = Cc1101Sim::new();
+    let clock = SimClock::new();
+    let mut wrapper = wrapper(&sim, &clock, 0x01);
+
+    // The checks would force the sleeping transceiver back to the Rx state
+    for mode in [RadioPowerMode::WakeOnRadio, RadioPowerMode::PowerDown] {
+        wrapper.set_radio_power_mode(mode);
+        check_transceiver(&mut wrapper, &clock, 3);
+        assert_eq!(sim.marc_state(), marc_state::SLEEP);
+        assert_eq!(wrapper.recovery_record(), None);
+        assert_eq!(wrapper.read_last_error(), (None, 0));
+    }
+
+    // Checked again in the Rx state
+    wrapper.set_radio_power_mode(RadioPowerMode::Continuous);
+    check_transceiver(&mut wrapper, &clock, 1);
+    assert_eq!(sim.marc_state(), marc_state::RX);
+    assert_eq!(wrapper.recovery_record(), None);
+    assert_eq!(wrapper.read_last_error(), (None, 0));
+}
+
+#[test]
+fn invalid_length_is_rejected() {
+    let sim = Cc1101Sim::new();
+    let mut wrapper = wrapper(&sim, &SimClock::new(), 0x01);
+
+    assert_eq!(
+        wrapper.write_data(0x02, &[]),
+        Err(Cc1101WrapperError::InvalidLength)
+    );
+    assert_eq!(
+        wrapper.write_data(0x02, &[0; 60]),
+        Err(Cc1101WrapperError::InvalidLength)
+    );
+    assert_eq!(wrapper.write_data(0x02, &[0; 59]), Ok(()));
+}
+
+#[test]
+fn hanging_transceiver_times_out() {
+    let sim = Cc1101Sim::new();
+    let clock = SimClock::new();
+    let mut wrapper = wrapper(&sim, &clock, 0x01);
+
+    sim.inject(SimFault::StuckState(marc_state::FSTXON));
+    wrapper.write_data(0x02, b"ping").unwrap();
+    block_on(wrapper.main());
+
+    let (error, count) = wrapper.read_last_error();
+    assert_eq!(error, Some(Cc1101WrapperError::TimeoutError));
+    assert!(count > 0);
+    assert!(sim.transmitted().is_empty());
+}
+
+#[test]
+fn async_errors_are_stored() {
+    let sim = Cc1101Sim::new();
+    let clock = SimClock::new();
+    let mut wrapper = wrapper(&sim, &clock, 0x01);
+    let mut data = [0; 64];
+    let (mut rssi, mut lqi) = (0, 0);
+
+    // No packet in time is not an error
+    assert_eq!(
+        block_on(wrapper.receive(&mut data, &mut rssi, &mut lqi, fugit::ExtU64::millis(10))),
+        Err(Cc1101WrapperError::TimeoutError)
+    );
+    assert_eq!(wrapper.read_last_error(), (None, 0));
+
+    // Errors of the transceiver are stored, and fault the next check
+    sim.inject(SimFault::SpiError);
+    assert_eq!(
+        block_on(wrapper.receive(&mut data, &mut rssi, &mut lqi, fugit::ExtU64::millis(10))),
+        Err(Cc1101WrapperError::Spi)
+    );
+    sim.clear_faults();
+    assert_eq!(
+        wrapper.read_last_error(),
+        (Some(Cc1101WrapperError::Spi), 1)
+    );
+    check_transceiver(&mut wrapper, &clock, 1);
+    let record = wrapper.recovery_record().unwrap();
+    assert_eq!(record.cause, Cc1101WrapperError::Spi);
+
+    sim.inject(SimFault::StuckState(marc_state::FSTXON));
+    assert_eq!(
+        block_on(wrapper.transmit(0x02, b"ping")),
+        Err(Cc1101WrapperError::TimeoutError)
+    );
+    let (error, _) = wrapper.read_last_error();
+    assert_eq!(error, Some(Cc1101WrapperError::TimeoutError));
+}
+
+#[test]
+fn missing_transceiver_is_detected() {
+    let sim = Cc1101Sim::new();
+
+    sim.inject(SimFault::NotResponding);
+    let result = SimWrapper::new(sim.clone(), SimClock::new());
+    assert_eq!(
+        result.err(),
+        Some(Cc1101WrapperError::UnknownChip(0xFF, 0xFF))
+    );
+
+    sim.clear_faults();
+    sim.inject(SimFault::SpiError);
+    let result = SimWrapper::new(sim.clone(), SimClock::new());
+    assert_eq!(result.err(), Some(Cc1101WrapperError::Spi));
+
+    sim.clear_faults();
+    assert!(SimWrapper::new(sim, SimClock::new()).is_ok());
+}
+
+#[test]
+fn failed_configuration_enters_degraded_mode() {
+    let sim = Cc1101Sim::new();
+    let mut wrapper = SimWrapper::new(sim.clone(), SimClock::new()).unwrap();
+
+    sim.inject(SimFault::SpiError);
+    assert_eq!(wrapper.init_config(), Err(Cc1101WrapperError::Spi));
+    assert!(wrapper.is_degraded());
+    assert_eq!(
+        wrapper.write_data(BROADCAST_ADDRESS, &[0; 64]),
+        Err(Cc1101WrapperError::Degraded)
+    );
+
+    // Retry once the transceiver answers again
+    sim.clear_faults();
+    assert_eq!(wrapper.init_config(), Ok(()));
+    assert!(!wrapper.is_degraded());
+    block_on(wrapper.main());
+    assert_eq!(sim.marc_state(), marc_state::RX);
+}
+
+#[test]
+fn configuration_keeps_the_applied_profile() {
+    const PKTLEN: u8 = 0x06;
+    const ADDR: u8 = 0x09;
+    let sim = Cc1101Sim::new();
+    let clock = SimClock::new();
+    let mut wrapper = wrapper(&sim, &clock, 0x01);
+
+    assert_eq!(wrapper.init_config(), Ok(()));
+    assert_eq!(wrapper.current_profile(), &profile(0x01));
+    assert_eq!(sim.register(PKTLEN), 61);
+    assert_eq!(sim.register(ADDR), 0x01);
+}
+
+#[test]
+fn frequency_offset_is_tracked_from_received_packets() {
+    const FSCTRL0: u8 = 0x0C;
+    let (sim_a, sim_b) = Cc1101Sim::linked_pair();
+    let clock = SimClock::new();
+    let mut a = event_wrapper(&sim_a, &clock, 0x01);
+    let mut b = event_wrapper(&sim_b, &clock, 0x02);
+    let mut data = [0; 64];
+    let (mut rssi, mut lqi) = (0, 0);
+    let timeout = fugit::ExtU64::millis(100);
+
+    // Crystal of the transmitter 30 kHz off, within the 101 kHz channel filter
+    sim_a.set_frequency_error(30_000);
+    for _ in 0..6 {
+        assert_eq!(block_on(a.transmit(0x02, b"ping")), Ok(()));
+        assert_eq!(
+            block_on(b.receive(&mut data, &mut rssi, &mut lqi, timeout)),
+            Ok((4, 0x01))
+        );
+    }
+
+    // Converged within two FREQOFF steps
+    let offset = b.frequency_offset();
+    assert!((27_000..=30_000).contains(&offset), "{offset}");
+    assert_eq!(b.link_stats().freq_offset_hz, offset);
+    assert_eq!(
+        sim_b.register(FSCTRL0) as i8,
+        (offset * 16384 / 26_000_000) as i8
+    );
+
+    // Answers on the carrier of the other end
+    assert_eq!(block_on(b.transmit(0x01, b"pong")), Ok(()));
+    assert_eq!(
+        block_on(a.receive(&mut data, &mut rssi, &mut lqi, timeout)),
+        Ok((4, 0x02))
+    );
+
+    // Disabling the tracking drops the offset
+    b.set_afc_config(&AfcConfig::DISABLED).unwrap();
+    assert_eq!(b.frequency_offset(), 0);
+    assert_eq!(sim_b.register(FSCTRL0), 0);
+    let config = AfcConfig {
+        gain_shift: 8,
+        ..AfcConfig::default()
+    };
+    assert_eq!(
+        b.set_afc_config(&config),
+        Err(Cc1101WrapperError::InvalidFrequencyConfig(
+            FrequencyError::InvalidGain
+        ))
+    );
+}
+
+#[test]
+fn doppler_table_pre_corrects_the_carrier() {
+    let (sim_a, sim_b) = Cc1101Sim::linked_pair();
+    let mut clock = SimClock::new();
+    let mut a = event_wrapper(&sim_a, &clock, 0x01);
+    let mut b = event_wrapper(&sim_b, &clock, 0x02);
+    let mut data = [0; 64];
+    let (mut rssi, mut lqi) = (0, 0);
+    let timeout = fugit::ExtU64::millis(100);
+    a.set_afc_config(&AfcConfig::DISABLED).unwrap();
+    b.set_afc_config(&AfcConfig::DISABLED).unwrap();
+    let nominal = [0x0D, 0x0E, 0x0F].map(|addr| sim_a.register(addr));
+
+    // Pass starting in one second, from +40 kHz to -40 kHz
+    let start = clock.now();
+    let table = [
+        DopplerPoint {
+            time: start + fugit::ExtU64::millis(1000),
+            shift_hz: 40_000,
+        },
+        DopplerPoint {
+            time: start + fugit::ExtU64::millis(3000),
+            shift_hz: -40_000,
+        },
+    ];
+    a.set_doppler_table(&table).unwrap();
+    block_on(a.main());
+    assert_eq!(a.doppler_correction(), 0);
+
+    clock.advance_ms(1000);
+    block_on(a.main());
+    let shift = a.doppler_correction();
+    assert!((39_000..=40_000).contains(&shift), "{shift}");
+
+    // Packets of the other end arrive shifted up, out of the channel filter uncorrected
+    sim_b.set_frequency_error(shift);
+    assert_eq!(block_on(b.transmit(0x01, b"down")), Ok(()));
+    assert_eq!(
+        block_on(a.receive(&mut data, &mut rssi, &mut lqi, timeout)),
+        Ok((4, 0x02))
+    );
+
+    // Transmissions are shifted down, to be received on the nominal frequency
+    sim_b.set_frequency_error(-shift);
+    assert_eq!(block_on(a.transmit(0x02, b"up")), Ok(()));
+    assert_eq!(
+        block_on(b.receive(&mut data, &mut rssi, &mut lqi, timeout)),
+        Ok((2, 0x01))
+    );
+
+    // Back on the nominal frequency after the pass
+    clock.advance_ms(2000);
+    block_on(a.main());
+    assert_eq!(a.doppler_correction(), 0);
+    assert_eq!([0x0D, 0x0E, 0x0F].map(|addr| sim_a.register(addr)), nominal);
+
+    let table = [table[1], table[0]];
+    assert_eq!(
+        a.set_doppler_table(&table),
+        Err(Cc1101WrapperError::InvalidFrequencyConfig(
+            FrequencyError::TableNotSorted
+        ))
+    );
+}
+
+/// Let the monitoring check the transceiver `count` times.
+fn check_transceiver(wrapper: &mut SimWrapper, clock: &SimClock, count: usize) {
+    for _ in 0..count {
+        clock.advance_ms(1001);
+        block_on(wrapper.main());
+    }
+}
+
+#[test]
+fn stuck_transceiver_is_recovered_by_escalation() {
+    let sim = Cc1101Sim::new();
+    let clock = SimClock::new();
+    let mut wrapper = wrapper(&sim, &clock, 0x01);
+
+    // Calibration never ends, until the transceiver is reset
+    sim.inject(SimFault::StuckState(marc_state::MANCAL));
+    check_transceiver(&mut wrapper, &clock, 5);
+    assert_eq!(sim.marc_state(), marc_state::RX);
+
+    let actions = [
+        RecoveryAction::FlushFifos,
+        RecoveryAction::Restrobe,
+        RecoveryAction::Recalibrate,
+        RecoveryAction::Reset,
+    ];
+    for (faults, action) in actions.into_iter().enumerate() {
+        let record = wrapper.recovery_record().unwrap();
+        assert_eq!(record.action, action);
+        assert_eq!(record.faults, faults as u8 + 1);
+        // Out of RX first, then the failed action of the previous check
+        let cause = match faults {
+            0 => Cc1101WrapperError::MonitoringError,
+            _ => Cc1101WrapperError::TimeoutError,
+        };
+        assert_eq!(record.cause, cause);
+        assert_eq!(record.result.is_ok(), action == RecoveryAction::Reset);
+    }
+    assert_eq!(wrapper.recovery_record(), None);
+
+    // The radio profile is applied again after the reset
+    assert_eq!(sim.register(0x06), 61);
+    assert_eq!(sim.register(0x09), 0x01);
+    assert!(!wrapper.is_degraded());
+
+    // Healthy checks restart the escalation
+    sim.inject(SimFault::StuckState(marc_state::IDLE));
+    check_transceiver(&mut wrapper, &clock, 1);
+    assert_eq!(
+        wrapper.recovery_record().map(|record| record.action),
+        Some(RecoveryAction::FlushFifos)
+    );
+}
+
+#[test]
+fn first_error_is_the_recovery_cause() {
+    let sim = Cc1101Sim::new();
+    let clock = SimClock::new();
+    let mut wrapper = wrapper(&sim, &clock, 0x01);
+
+    sim.inject(SimFault::StuckState(marc_state::FSTXON));
+    assert_eq!(
+        block_on(wrapper.transmit(0x02, b"ping")),
+        Err(Cc1101WrapperError::TimeoutError)
+    );
+
+    // The check cannot read the state either
+    sim.inject(SimFault::SpiError);
+    check_transceiver(&mut wrapper, &clock, 1);
+    let record = wrapper.recovery_record().unwrap();
+    assert_eq!(record.cause, Cc1101WrapperError::TimeoutError);
+    assert_eq!(record.action, RecoveryAction::FlushFifos);
+    assert_eq!(wrapper.recovery_record(), None);
+    sim.clear_faults();
+
+    // The failed action faults the next check
+    check_transceiver(&mut wrapper, &clock, 1);
+    let record = wrapper.recovery_record().unwrap();
+    assert_eq!(record.cause, Cc1101WrapperError::Spi);
+    assert_eq!(record.action, RecoveryAction::Restrobe);
+
+    // Out of RX after an SPI error in the same period
+    let mut data = [0; 64];
+    let (mut rssi, mut lqi) = (0, 0);
+    sim.inject(SimFault::SpiError);
+    assert_eq!(
+        block_on(wrapper.receive(&mut data, &mut rssi, &mut lqi, fugit::ExtU64::millis(10))),
+        Err(Cc1101WrapperError::Spi)
+    );
+    sim.clear_faults();
+    sim.inject(SimFault::StuckState(marc_state::FSTXON));
+    check_transceiver(&mut wrapper, &clock, 1);
+    let record = wrapper.recovery_record().unwrap();
+    assert_eq!(record.cause, Cc1101WrapperError::Spi);
+}
+
+#[test]
+fn dead_transceiver_is_marked_failed() {
+    let sim = Cc1101Sim::new();
+    let clock = SimClock::new();
+    let mut wrapper = wrapper(&sim, &clock, 0x01);
+
+    let config = RecoveryConfig {
+        reset: 0,
+        ..RecoveryConfig::default()
+    };
+    assert_eq!(
+        wrapper.set_recovery_config(&config),
+        Err(Cc1101WrapperError::InvalidRecoveryConfig(
+            RecoveryError::InvalidThresholds
+        ))
+    );
+
+    // Recalibrate at once, reset at the second faulty check
+    let config = RecoveryConfig {
+        flush_fifos: 1,
+        restrobe: 1,
+        recalibrate: 1,
+        reset: 2,
+        mark_failed: 3,
+        ..RecoveryConfig::default()
+    };
+    wrapper.set_recovery_config(&config).unwrap();
+
+    sim.inject(SimFault::NotResponding);
+    check_transceiver(&mut wrapper, &clock, 3);
+    assert!(wrapper.is_degraded());
+
+    let record = wrapper.recovery_record().unwrap();
+    assert_eq!(record.action, RecoveryAction::Recalibrate);
+    let record = wrapper.recovery_record().unwrap();
+    assert_eq!(record.action, RecoveryAction::Reset);
+    assert_eq!(
+        record.result,
+        Err(Cc1101WrapperError::UnknownChip(0xFF, 0xFF))
+    );
+    let record = wrapper.recovery_record().unwrap();
+    assert_eq!(record.action, RecoveryAction::MarkFailed);
+
+    // Nothing is done in degraded mode
+    check_transceiver(&mut wrapper, &clock, 1);
+    assert_eq!(wrapper.recovery_record(), None);
+}
+
+/// Radio profile with a plan of `channels` channels, 200 kHz apart.
+fn channel_plan(address: u8, channels: u8) -> RadioProfile {
+    RadioProfile {
+        channels,
+        ..profile(address)
+    }
+}
+
+#[test]
+fn commanded_channel_switch_tunes_the_transceiver() {
+    const CHANNR: u8 = 0x0A;
+    let (sim_a, sim_b) = Cc1101Sim::linked_pair();
+    let clock = SimClock::new();
+    let mut a = event_wrapper(&sim_a, &clock, 0x01);
+    let mut b = event_wrapper(&sim_b, &clock, 0x02);
+    let mut data = [0; 64];
+    let (mut rssi, mut lqi) = (0, 0);
+    let timeout = fugit::ExtU64::millis(100);
+    a.apply_profile(&channel_plan(0x01, 4)).unwrap();
+    b.apply_profile(&channel_plan(0x02, 4)).unwrap();
+    block_on(a.main());
+    block_on(b.main());
+
+    assert_eq!(block_on(a.switch_channel(2)), Ok(()));
+    assert_eq!(a.current_channel(), 2);
+    assert_eq!(a.link_stats().channel, 2);
+    assert_eq!(sim_a.register(CHANNR), 2);
+    assert_eq!(sim_a.marc_state(), marc_state::RX);
+
+    // Not heard on another channel
+    assert_eq!(block_on(a.transmit(0x02, b"ch2")), Ok(()));
+    assert_eq!(
+        block_on(b.receive(&mut data, &mut rssi, &mut lqi, timeout)),
+        Err(Cc1101WrapperError::TimeoutError)
+    );
+
+    assert_eq!(block_on(b.switch_channel(2)), Ok(()));
+    assert_eq!(block_on(a.transmit(0x02, b"ch2")), Ok(()));
+    assert_eq!(
+        block_on(b.receive(&mut data, &mut rssi, &mut lqi, timeout)),
+        Ok((3, 0x01))
+    );
+
+    // The channel is kept while in the channel plan
+    a.apply_profile(&channel_plan(0x01, 3)).unwrap();
+    assert_eq!(sim_a.register(CHANNR), 2);
+    a.apply_profile(&channel_plan(0x01, 2)).unwrap();
+    assert_eq!(a.current_channel(), 0);
+    assert_eq!(sim_a.register(CHANNR), 0);
+
+    assert_eq!(
+        block_on(a.switch_channel(2)),
+        Err(Cc1101WrapperError::InvalidChannel(ChannelError::OutOfPlan))
+    );
+    assert_eq!(
+        a.apply_profile(&channel_plan(0x01, 255)),
+        Err(Cc1101WrapperError::InvalidProfile(
+            ProfileError::ChannelPlanOutOfBand
+        ))
+    );
+}
+
+#[test]
+fn hopping_sequence_is_shared_by_both_ends() {
+    let (sim_a, sim_b) = Cc1101Sim::linked_pair();
+    let mut clock = SimClock::new();
+    let mut a = event_wrapper(&sim_a, &clock, 0x01);
+    let mut b = event_wrapper(&sim_b, &clock, 0x02);
+    let mut data = [0; 64];
+    let (mut rssi, mut lqi) = (0, 0);
+    let timeout = fugit::ExtU64::millis(100);
+    a.apply_profile(&channel_plan(0x01, 8)).unwrap();
+    b.apply_profile(&channel_plan(0x02, 8)).unwrap();
+
+    let config = HoppingConfig {
+        seed: 0x5EED_1234,
+        dwell_ms: 1000,
+        epoch: clock.now() + fugit::ExtU64::millis(1000),
+    };
+    a.start_hopping(&config).unwrap();
+    b.start_hopping(&config).unwrap();
+    assert_eq!(a.current_hopping_config(), Some(&config));
+
+    // Every channel is visited once per cycle
+    let mut channels = Vec::new();
+    for slot in 0..8 {
+        let start = config.epoch + fugit::ExtU64::millis(slot as u64 * 1000);
+        let elapsed = (start - clock.now()).to_millis();
+        clock.advance_ms(elapsed);
+        block_on(a.main());
+        block_on(b.main());
+
+        let channel = hop_channel(config.seed, slot, 8);
+        assert_eq!(a.current_channel(), channel);
+        assert_eq!(b.current_channel(), channel);
+        assert_eq!(sim_b.register(0x0A), channel);
+        channels.push(channel);
+
+        assert_eq!(block_on(a.transmit(0x02, b"hop")), Ok(()));
+        assert_eq!(
+            block_on(b.receive(&mut data, &mut rssi, &mut lqi, timeout)),
+            Ok((3, 0x01))
+        );
+    }
+    channels.sort();
+    assert_eq!(channels, (0..8).collect::<Vec<u8>>());
+
+    // Another cycle, another order
+    let cycle = |cycle: u32| -> Vec<u8> {
+        (0..8)
+            .map(|slot| hop_channel(config.seed, cycle * 8 + slot, 8))
+            .collect()
+    };
+    assert_ne!(cycle(0), cycle(1));
+
+    // A commanded switch stops hopping
+    assert_eq!(block_on(a.switch_channel(3)), Ok(()));
+    assert_eq!(a.current_hopping_config(), None);
+    clock.advance_ms(1000);
+    block_on(a.main());
+    assert_eq!(a.current_channel(), 3);
+
+    let config = HoppingConfig {
+        dwell_ms: 5,
+        ..config
+    };
+    assert_eq!(
+        a.start_hopping(&config),
+        Err(Cc1101WrapperError::InvalidChannel(
+            ChannelError::DwellTooShort
+        ))
+    );
+}
@@ -0,0 +1,868 @@
+use crate::config::SfpConfig;
+use crate::encoder::{write_extended_frame, EncodeError, WriteFrameError};
+use crate::header::{FrameHeader, FrameType, HeaderError, Sequence, FLAG_ACK_REQUEST};
+
+/// Reliable delivery (ARQ) configuration.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ArqConfig {
+    /// Maximum number of unacknowledged frames in flight. A window of 1 gives stop-and-wait,
+    /// larger windows give selective repeat. Capped to the endpoint capacity, both ends of the
+    /// link shall use the same window.
+    pub window: usize,
+    /// Time after which an unacknowledged frame is retransmitted, in milliseconds
+    pub retransmit_timeout_ms: u64,
+    /// Number of retransmissions after which a frame is given up
+    pub max_retries: u8,
+}
+
+impl Default for ArqConfig {
+    fn default() -> Self {
+        Self {
+            window: 1,
+            retransmit_timeout_ms: 500,
+            max_retries: 5,
+        }
+    }
+}
+
+/// Delivery statistics of an ARQ endpoint.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct ArqStats {
+    /// Data frames sent for the first time
+    pub sent: u32,
+    /// Data frames sent again after a timeout or a NACK
+    pub retransmitted: u32,
+    /// Data frames acknowledged by the peer
+    pub acked: u32,
+    /// Data frames given up after the maximum number of retries
+    pub failed: u32,
+    /// Data frames delivered in order to the application
+    pub delivered: u32,
+    /// Data frames received more than once
+    pub duplicates: u32,
+    /// Data frames received ahead of a missing one
+    pub out_of_order: u32,
+    /// Data frames given up by the peer, no longer awaited
+    pub skipped: u32,
+    /// NACK frames sent because of corrupted frames
+    pub nacks_sent: u32,
+    /// NACK frames received from the peer
+    pub nacks_received: u32,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ArqError<E> {
+    /// All the frames of the window are waiting for an acknowledgement
+    WindowFull,
+    /// Payload does not fit in the endpoint buffers
+    PayloadTooLarge,
+    /// Error of the underlying transport
+    Transport(E),
+}
+
+/// Link used by the ARQ endpoint to send frames, e.g. SFP over UART or CC1101 packets.
+pub trait ArqTransport {
+    type Error;
+
+    fn send_frame(&mut self, header: &FrameHeader, payload: &[u8]) -> Result<(), Self::Error>;
+}
+
+impl<F, E> ArqTransport for F
+where
+    F: FnMut(&FrameHeader, &[u8]) -> Result<(), E>,
+{
+    type Error = E;
+
+    fn send_frame(&mut self, header: &FrameHeader, payload: &[u8]) -> Result<(), Self::Error> {
+        self(header, payload)
+    }
+}
+
+/// ARQ transport sending SFP 0.2 frames into a serial writer.
+pub struct SfpTransport<'a, W> {
+    pub config: SfpConfig,
+    pub writer: &'a mut W,
+}
+
+impl<'a, W> ArqTransport for SfpTransport<'a, W>
+where
+    W: embedded_io::Write,
+{
+    type Error = WriteFrameError<W::Error>;
+
+    fn send_frame(&mut self, header: &FrameHeader, payload: &[u8]) -> Result<(), Self::Error> {
+        write_extended_frame(&self.config, self.writer, header, payload).map(|_| ())
+    }
+}
+
+/// Encode an ARQ frame as a raw radio packet (e.g. for the CC1101 link, which has its own
+/// CRC): the extended header followed by the payload. Returns the size of the packet.
+pub fn encode_packet(
+    header: &FrameHeader,
+    payload: &[u8],
+    buffer: &mut [u8],
+) -> Result<usize, EncodeError> {
+    let header_len = header.encode(buffer)?;
+    let packet_len = header_len + payload.len();
+    if buffer.len() < packet_len {
+        return Err(EncodeError::BufferTooSmall);
+    }
+
+    buffer[header_len..packet_len].copy_from_slice(payload);
+    Ok(packet_len)
+}
+
+/// Split a raw radio packet into the extended header and the payload.
+pub fn parse_packet(packet: &[u8]) -> Result<(FrameHeader, &[u8]), HeaderError> {
+    let (header, header_len) = FrameHeader::parse(packet)?;
+    Ok((header, &packet[header_len..]))
+}
+
+#[derive(Copy, Clone)]
+struct Slot<const MTU: usize> {
+    used: bool,
+    skipped: bool,
+    sequence: u16,
+    length: usize,
+    data: [u8; MTU],
+    sent_at: u64,
+    retries: u8,
+}
+
+impl<const MTU: usize> Slot<MTU> {
+    const EMPTY: Self = Self {
+        used: false,
+        skipped: false,
+        sequence: 0,
+        length: 0,
+        data: [0; MTU],
+        sent_at: 0,
+        retries: 0,
+    };
+
+    fn data(&self) -> &[u8] {
+        &self.data[..self.length]
+    }
+}
+
+/// Reliable delivery endpoint with up to `N` frames in flight in each direction, each
+/// carrying up to `MTU` bytes of payload.
+///
+/// Data frames are numbered with a 16 bit sequence counter and acknowledged one by one.
+/// The caller supplies the time in milliseconds, feeds the received frames and calls
+/// `poll` periodically to drive the retransmissions.
+pub struct ArqEndpoint<const N: usize, const MTU: usize> {
+    config: ArqConfig,
+    stats: ArqStats,
+    tx_next: u16,
+    tx_slots: [Slot<MTU>; N],
+    rx_next: u16,
+    rx_slots: [Slot<MTU>; N],
+}
+
+impl<const N: usize, const MTU: usize> ArqEndpoint<N, MTU> {
+    pub fn new(config: ArqConfig) -> Self {
+        Self {
+            config,
+            stats: ArqStats::default(),
+            tx_next: 0,
+            tx_slots: [Slot::EMPTY; N],
+            rx_next: 0,
+            rx_slots: [Slot::EMPTY; N],
+        }
+    }
+
+    pub fn stats(&self) -> &ArqStats {
+        &self.stats
+    }
+
+    /// Number of frames waiting for an acknowledgement.
+    pub fn in_flight(&self) -> usize {
+        self.tx_slots.iter().filter(|slot| slot.used).count()
+    }
+
+    /// Check if a new frame can be sent without exceeding the window, i.e. it would be
+    /// less than `window` frames ahead of the oldest unacknowledged one.
+    pub fn can_send(&self) -> bool {
+        let window = self.window() as u16;
+        self.tx_slots
+            .iter()
+            .filter(|slot| slot.used)
+            .all(|slot| self.tx_next.wrapping_sub(slot.sequence) < window)
+    }
+
+    /// Send `payload` reliably. Returns the sequence number of the frame.
+    pub fn send<T>(
+        &mut self,
+        payload: &[u8],
+        now: u64,
+        transport: &mut T,
+    ) -> Result<u16, ArqError<T::Error>>
+    where
+        T: ArqTransport,
+    {
+        if payload.len() > MTU {
+            return Err(ArqError::PayloadTooLarge);
+        }
+        if !self.can_send() {
+            return Err(ArqError::WindowFull);
+        }
+
+        let sequence = self.tx_next;
+        let slot = self
+            .tx_slots
+            .iter_mut()
+            .find(|slot| !slot.used)
+            .ok_or(ArqError::WindowFull)?;
+
+        slot.used = true;
+        slot.sequence = sequence;
+        slot.length = payload.len();
+        slot.data[..payload.len()].copy_from_slice(payload);
+        slot.sent_at = now;
+        slot.retries = 0;
+
+        self.tx_next = self.tx_next.wrapping_add(1);
+        self.stats.sent += 1;
+
+        let mut header = FrameHeader::new(FrameType::Data, Sequence::Long(sequence));
+        header.flags = FLAG_ACK_REQUEST;
+        transport
+            .send_frame(&header, payload)
+            .map_err(ArqError::Transport)?;
+
+        Ok(sequence)
+    }
+
+    /// Retransmit the frames whose acknowledgement timed out and give up the ones which
+    /// exceeded the retries, announcing them to the peer with a Skip frame. Returns the
+    /// number of frames given up.
+    pub fn poll<T>(&mut self, now: u64, transport: &mut T) -> Result<usize, T::Error>
+    where
+        T: ArqTransport,
+    {
+        let mut failed = 0;
+
+        for index in 0..N {
+            let slot = &mut self.tx_slots[index];
+            if !slot.used || now.wrapping_sub(slot.sent_at) < self.config.retransmit_timeout_ms {
+                continue;
+            }
+
+            if slot.retries >= self.config.max_retries {
+                slot.used = false;
+                self.stats.failed += 1;
+                failed += 1;
+
+                let header = FrameHeader::new(FrameType::Skip, Sequence::Long(slot.sequence));
+                transport.send_frame(&header, &[])?;
+            } else {
+                self.retransmit(index, now, transport)?;
+            }
+        }
+
+        Ok(failed)
+    }
+
+    /// Process a frame received from the peer, delivering the in-order payloads to `deliver`.
+    pub fn on_frame<T, D>(
+        &mut self,
+        header: &FrameHeader,
+        payload: &[u8],
+        now: u64,
+        transport: &mut T,
+        mut deliver: D,
+    ) -> Result<(), T::Error>
+    where
+        T: ArqTransport,
+        D: FnMut(&[u8]),
+    {
+        let sequence = header.sequence.value();
+
+        match header.frame_type {
+            FrameType::Data => self.on_data(sequence, payload, transport, &mut deliver)?,
+            FrameType::Ack => self.on_ack(sequence),
+            FrameType::Nack => {
+                self.stats.nacks_received += 1;
+                if let Some(index) = self.find_tx_slot(sequence) {
+                    self.retransmit(index, now, transport)?;
+                }
+            }
+            FrameType::Skip => self.on_skip(sequence, &mut deliver),
+            FrameType::Hello => { /* Not part of the delivery */ }
+        }
+
+        Ok(())
+    }
+
+    /// Report a corrupted frame received from the peer. Requests the retransmission of the
+    /// oldest frame not delivered yet.
+    pub fn on_corrupted_frame<T>(&mut self, transport: &mut T) -> Result<(), T::Error>
+    where
+        T: ArqTransport,
+    {
+        self.stats.nacks_sent += 1;
+        let header = FrameHeader::new(FrameType::Nack, Sequence::Long(self.rx_next));
+        transport.send_frame(&header, &[])
+    }
+
+    // ---------------------------------------------------------------------------------
+
+    fn window(&self) -> usize {
+        self.config.window.clamp(1, N)
+    }
+
+    fn find_tx_slot(&self, sequence: u16) -> Option<usize> {
+        self.tx_slots
+            .iter()
+            .position(|slot| slot.used && slot.sequence == sequence)
+    }
+
+    fn retransmit<T>(&mut self, index: usize, now: u64, transport: &mut T) -> Result<(), T::Error>
+    where
+        T: ArqTransport,
+    {
+        let slot = &mut self.tx_slots[index];
+        slot.sent_at = now;
+        slot.retries += 1;
+        self.stats.retransmitted += 1;
+
+        let mut header = FrameHeader::new(FrameType::Data, Sequence::Long(slot.sequence));
+        header.flags = FLAG_ACK_REQUEST;
+        transport.send_frame(&header, slot.data())
+    }
+
+    fn on_ack(&mut self, sequence: u16) {
+        if let Some(index) = self.find_tx_slot(sequence) {
+            self.tx_slots[index].used = false;
+            self.stats.acked += 1;
+        }
+    }
+
+    fn on_data<T, D>(
+        &mut self,
+        sequence: u16,
+        payload: &[u8],
+        transport: &mut T,
+        deliver: &mut D,
+    ) -> Result<(), T::Error>
+    where
+        T: ArqTransport,
+        D: FnMut(&[u8]),
+    {
+        let window = self.window() as u16;
+        self.resync(sequence, deliver);
+        let ahead = sequence.wrapping_sub(self.rx_next);
+        let behind = self.rx_next.wrapping_sub(sequence);
+
+        if ahead < window {
+            if payload.len() > MTU {
+                // Cannot be stored, let it time out on the sender side
+                return Ok(());
+            }
+
+            if ahead == 0 {
+                deliver(payload);
+                self.stats.delivered += 1;
+                self.rx_next = self.rx_next.wrapping_add(1);
+                self.deliver_buffered(deliver);
+            } else if self
+                .rx_slots
+                .iter()
+                .any(|s| s.used && s.sequence == sequence)
+            {
+                self.stats.duplicates += 1;
+            } else if let Some(slot) = self.rx_slots.iter_mut().find(|slot| !slot.used) {
+                slot.used = true;
+                slot.skipped = false;
+                slot.sequence = sequence;
+                slot.length = payload.len();
+                slot.data[..payload.len()].copy_from_slice(payload);
+                self.stats.out_of_order += 1;
+            }
+        } else if behind > 0 && behind <= window {
+            // Already delivered, the acknowledgement was probably lost
+            self.stats.duplicates += 1;
+        } else {
+            // Outside of the window
+            return Ok(());
+        }
+
+        let header = FrameHeader::new(FrameType::Ack, Sequence::Long(sequence));
+        transport.send_frame(&header, &[])
+    }
+
+    fn on_skip<D>(&mut self, sequence: u16, deliver: &mut D)
+    where
+        D: FnMut(&[u8]),
+    {
+        let window = self.window() as u16;
+        self.resync(sequence, deliver);
+        let ahead = sequence.wrapping_sub(self.rx_next);
+
+        if ahead == 0 {
+            self.stats.skipped += 1;
+            self.rx_next = self.rx_next.wrapping_add(1);
+            self.deliver_buffered(deliver);
+        } else if ahead < window
+            && !self
+                .rx_slots
+                .iter()
+                .any(|s| s.used && s.sequence == sequence)
+        {
+            // Skipped once the frames before it are delivered
+            if let Some(slot) = self.rx_slots.iter_mut().find(|slot| !slot.used) {
+                slot.used = true;
+                slot.skipped = true;
+                slot.sequence = sequence;
+                slot.length = 0;
+            }
+        }
+    }
+
+    /// Move the receive window up to `sequence` when it is beyond the window. The sender
+    /// only sends a frame once all the frames a window before it are acknowledged or given
+    /// up, so the missing ones were given up and their Skip frames were lost.
+    fn resync<D>(&mut self, sequence: u16, deliver: &mut D)
+    where
+        D: FnMut(&[u8]),
+    {
+        let window = self.window() as u16;
+        let ahead = sequence.wrapping_sub(self.rx_next);
+        if ahead < window || ahead >= u16::MAX / 2 {
+            // Within the window, or behind it
+            return;
+        }
+
+        let start = sequence.wrapping_sub(window - 1);
+        while self.rx_next != start {
+            if let Some(slot) = self
+                .rx_slots
+                .iter_mut()
+                .find(|slot| slot.used && slot.sequence == self.rx_next)
+            {
+                slot.used = false;
+                if slot.skipped {
+                    self.stats.skipped += 1;
+                } else {
+                    deliver(slot.data());
+                    self.stats.delivered += 1;
+                }
+            } else {
+                self.stats.skipped += 1;
+            }
+            self.rx_next = self.rx_next.wrapping_add(1);
+        }
+        self.deliver_buffered(deliver);
+    }
+
+    fn deliver_buffered<D>(&mut self, deliver: &mut D)
+    where
+        D: FnMut(&[u8]),
+    {
+        while let Some(slot) = self
+            .rx_slots
+            .iter_mut()
+            .find(|slot| slot.used && slot.sequence == self.rx_next)
+        {
+            slot.used = false;
+            if slot.skipped {
+                self.stats.skipped += 1;
+            } else {
+                deliver(slot.data());
+                self.stats.delivered += 1;
+            }
+            self.rx_next = self.rx_next.wrapping_add(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::{SfpDecoder, SfpEvent};
+    use crate::encoder::encode_extended_frame;
+    use std::collections::VecDeque;
+
+    type Endpoint = ArqEndpoint<4, 32>;
+    type Queue = VecDeque<(FrameHeader, Vec<u8>)>;
+
+    fn queue_transport(
+        queue: &mut Queue,
+    ) -> impl FnMut(&FrameHeader, &[u8]) -> Result<(), ()> + '_ {
+        move |header, payload| {
+            queue.push_back((*header, payload.to_vec()));
+            Ok(())
+        }
+    }
+
+    /// Two endpoints linked by a simulated channel, which may lose frames.
+    struct Link {
+        a: Endpoint,
+        b: Endpoint,
+        a_to_b: Queue,
+        b_to_a: Queue,
+        received: Vec<Vec<u8>>,
+        now: u64,
+    }
+
+    impl Link {
+        fn new(config: ArqConfig) -> Self {
+            Self {
+                a: Endpoint::new(config),
+                b: Endpoint::new(config),
+                a_to_b: Queue::new(),
+                b_to_a: Queue::new(),
+                received: Vec::new(),
+                now: 0,
+            }
+        }
+
+        /// Carry the queued frames across, dropping the ones selected by `lose`.
+        fn exchange(&mut self, lose: &mut impl FnMut(&FrameHeader) -> bool) {
+            while let Some((header, payload)) = self.a_to_b.pop_front() {
+                if lose(&header) {
+                    continue;
+                }
+                let received = &mut self.received;
+                self.b
+                    .on_frame(
+                        &header,
+                        &payload,
+                        self.now,
+                        &mut queue_transport(&mut self.b_to_a),
+                        |data| received.push(data.to_vec()),
+                    )
+                    .unwrap();
+            }
+            while let Some((header, payload)) = self.b_to_a.pop_front() {
+                if lose(&header) {
+                    continue;
+                }
+                self.a
+                    .on_frame(
+                        &header,
+                        &payload,
+                        self.now,
+                        &mut queue_transport(&mut self.a_to_b),
+                        |_| {},
+                    )
+                    .unwrap();
+            }
+        }
+
+        /// Send all the messages, advancing the time until everything is acknowledged.
+        fn transfer(&mut self, messages: &[Vec<u8>], mut lose: impl FnMut(&FrameHeader) -> bool) {
+            let mut pending = messages.iter();
+            let mut next = pending.next();
+
+            for _ in 0..1000 {
+                while let Some(message) = next {
+                    if !self.a.can_send() {
+                        break;
+                    }
+                    self.a
+                        .send(message, self.now, &mut queue_transport(&mut self.a_to_b))
+                        .unwrap();
+                    next = pending.next();
+                }
+
+                self.exchange(&mut lose);
+
+                if next.is_none() && self.a.in_flight() == 0 {
+                    return;
+                }
+
+                self.now += 100;
+                self.a
+                    .poll(self.now, &mut queue_transport(&mut self.a_to_b))
+                    .unwrap();
+            }
+            panic!("Transfer did not complete");
+        }
+    }
+
+    fn messages(count: u8) -> Vec<Vec<u8>> {
+        (0..count).map(|i| vec![i; (i as usize % 16) + 1]).collect()
+    }
+
+    /// Pseudo-random loss of `percent` of the frames.
+    fn random_loss(percent: u32) -> impl FnMut(&FrameHeader) -> bool {
+        let mut state = 0x2545_f491u32;
+        move |_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state % 100 < percent
+        }
+    }
+
+    fn config(window: usize) -> ArqConfig {
+        ArqConfig {
+            window,
+            retransmit_timeout_ms: 300,
+            max_retries: 10,
+        }
+    }
+
+    #[test]
+    fn lossless_transfer() {
+        let messages = messages(20);
+        let mut link = Link::new(config(4));
+        link.transfer(&messages, |_| false);
+
+        assert_eq!(link.received, messages);
+        assert_eq!(link.a.stats().acked, 20);
+        assert_eq!(link.a.stats().retransmitted, 0);
+    }
+
+    #[test]
+    fn stop_and_wait_transfer_with_loss() {
+        let messages = messages(10);
+        let mut link = Link::new(config(1));
+        link.transfer(&messages, random_loss(30));
+
+        assert_eq!(link.received, messages);
+        assert!(link.a.stats().retransmitted > 0);
+        assert!(link.b.stats().duplicates > 0);
+    }
+
+    #[test]
+    fn selective_repeat_transfer_with_loss() {
+        let messages = messages(30);
+        let mut link = Link::new(config(4));
+        link.transfer(&messages, random_loss(25));
+
+        assert_eq!(link.received, messages);
+        assert_eq!(link.b.stats().delivered, 30);
+        assert_eq!(link.a.stats().failed, 0);
+    }
+
+    #[test]
+    fn reordered_frames_are_delivered_in_order() {
+        let messages = messages(4);
+        let mut a = Endpoint::new(config(4));
+        let mut b = Endpoint::new(config(4));
+        let mut a_to_b = Queue::new();
+        let mut b_to_a = Queue::new();
+        let mut received = Vec::new();
+
+        for message in &messages {
+            a.send(message, 0, &mut queue_transport(&mut a_to_b))
+                .unwrap();
+        }
+        assert!(!a.can_send());
+
+        // Deliver in reverse order
+        while let Some((header, payload)) = a_to_b.pop_back() {
+            b.on_frame(
+                &header,
+                &payload,
+                0,
+                &mut queue_transport(&mut b_to_a),
+                |data| received.push(data.to_vec()),
+            )
+            .unwrap();
+        }
+        while let Some((header, payload)) = b_to_a.pop_front() {
+            a.on_frame(
+                &header,
+                &payload,
+                0,
+                &mut queue_transport(&mut a_to_b),
+                |_| {},
+            )
+            .unwrap();
+        }
+
+        assert_eq!(received, messages);
+        assert_eq!(b.stats().out_of_order, 3);
+        assert_eq!(a.in_flight(), 0);
+    }
+
+    #[test]
+    fn corrupted_frame_is_retransmitted_after_nack() {
+        let config_sfp = SfpConfig::default();
+        let mut a = Endpoint::new(config(1));
+        let mut b = Endpoint::new(config(1));
+        let mut a_to_b = Queue::new();
+        let mut b_to_a = Queue::new();
+        let mut received = Vec::new();
+
+        a.send(b"telecommand", 0, &mut queue_transport(&mut a_to_b))
+            .unwrap();
+
+        // Corrupt the frame on the serial line
+        let (header, payload) = a_to_b.pop_front().unwrap();
+        let mut buffer = [0; 64];
+        let frame_len = encode_extended_frame(&config_sfp, &header, &payload, &mut buffer).unwrap();
+        buffer[frame_len - 3] ^= 0x10;
+
+        let mut decoder = SfpDecoder::new(config_sfp);
+        let mut corrupted = false;
+        decoder.feed(&buffer[..frame_len], |event| {
+            corrupted |= event == SfpEvent::CrcError;
+        });
+        assert!(corrupted);
+        b.on_corrupted_frame(&mut queue_transport(&mut b_to_a))
+            .unwrap();
+
+        // NACK triggers the retransmission before the timeout
+        let (header, payload) = b_to_a.pop_front().unwrap();
+        assert_eq!(header.frame_type, FrameType::Nack);
+        a.on_frame(
+            &header,
+            &payload,
+            10,
+            &mut queue_transport(&mut a_to_b),
+            |_| {},
+        )
+        .unwrap();
+        let (header, payload) = a_to_b.pop_front().unwrap();
+        b.on_frame(
+            &header,
+            &payload,
+            10,
+            &mut queue_transport(&mut b_to_a),
+            |data| received.push(data.to_vec()),
+        )
+        .unwrap();
+
+        assert_eq!(received, [b"telecommand".to_vec()]);
+        assert_eq!(a.stats().nacks_received, 1);
+        assert_eq!(a.stats().retransmitted, 1);
+    }
+
+    #[test]
+    fn frame_is_given_up_after_max_retries() {
+        let mut a = Endpoint::new(ArqConfig {
+            window: 2,
+            retransmit_timeout_ms: 100,
+            max_retries: 2,
+        });
+        let mut lost = Queue::new();
+
+        a.send(b"lost", 0, &mut queue_transport(&mut lost)).unwrap();
+        assert_eq!(a.poll(100, &mut queue_transport(&mut lost)), Ok(0));
+        assert_eq!(a.poll(200, &mut queue_transport(&mut lost)), Ok(0));
+        assert_eq!(a.poll(300, &mut queue_transport(&mut lost)), Ok(1));
+
+        assert_eq!(lost.len(), 4);
+        assert_eq!(a.stats().failed, 1);
+        assert_eq!(a.in_flight(), 0);
+
+        // Only the Skip frame gets through, the next frame is delivered
+        let (header, payload) = lost.pop_back().unwrap();
+        assert_eq!(header.frame_type, FrameType::Skip);
+        assert_eq!(header.sequence, Sequence::Long(0));
+        let mut b = Endpoint::new(a.config);
+        let mut a_to_b = Queue::new();
+        let mut b_to_a = Queue::new();
+        let mut received = Vec::new();
+        b.on_frame(
+            &header,
+            &payload,
+            300,
+            &mut queue_transport(&mut b_to_a),
+            |_| {},
+        )
+        .unwrap();
+
+        a.send(b"next", 300, &mut queue_transport(&mut a_to_b))
+            .unwrap();
+        let (header, payload) = a_to_b.pop_front().unwrap();
+        b.on_frame(
+            &header,
+            &payload,
+            300,
+            &mut queue_transport(&mut b_to_a),
+            |data| received.push(data.to_vec()),
+        )
+        .unwrap();
+
+        assert_eq!(received, [b"next".to_vec()]);
+        assert_eq!(b.stats().skipped, 1);
+    }
+
+    #[test]
+    fn receiver_resyncs_when_the_skip_frame_is_lost() {
+        for window in [1, 2, 4] {
+            let messages = messages(12);
+            let mut link = Link::new(ArqConfig {
+                window,
+                retransmit_timeout_ms: 100,
+                max_retries: 2,
+            });
+
+            // Every transmission of the first frame is lost, and so is its Skip frame
+            link.transfer(&messages, |header| {
+                header.sequence.value() == 0
+                    && matches!(header.frame_type, FrameType::Data | FrameType::Skip)
+            });
+
+            assert_eq!(link.received, &messages[1..]);
+            assert_eq!(link.a.stats().failed, 1);
+            assert_eq!(link.b.stats().skipped, 1);
+        }
+    }
+
+    #[test]
+    fn send_checks_window_and_payload() {
+        let mut a = ArqEndpoint::<2, 4>::new(config(2));
+        let mut queue = Queue::new();
+
+        assert_eq!(
+            a.send(&[0; 5], 0, &mut queue_transport(&mut queue)),
+            Err(ArqError::PayloadTooLarge)
+        );
+        assert_eq!(a.send(&[1], 0, &mut queue_transport(&mut queue)), Ok(0));
+        assert_eq!(a.send(&[2], 0, &mut queue_transport(&mut queue)), Ok(1));
+        assert_eq!(
+            a.send(&[3], 0, &mut queue_transport(&mut queue)),
+            Err(ArqError::WindowFull)
+        );
+    }
+
+    #[test]
+    fn packet_round_trip() {
+        let mut header = FrameHeader::new(FrameType::Data, Sequence::Long(0x0102));
+        header.flags = FLAG_ACK_REQUEST;
+        let mut buffer = [0; 64];
+
+        let length = encode_packet(&header, b"beacon", &mut buffer).unwrap();
+        assert_eq!(length, 11);
+        assert_eq!(
+            parse_packet(&buffer[..length]),
+            Ok((header, &b"beacon"[..]))
+        );
+        assert_eq!(
+            encode_packet(&header, &[0; 60], &mut buffer),
+            Err(EncodeError::BufferTooSmall)
+        );
+    }
+
+    #[test]
+    fn frames_are_sent_over_sfp() {
+        let mut a = Endpoint::new(config(1));
+        let mut output = [0; 64];
+        let mut writer = &mut output[..];
+        let mut transport = SfpTransport {
+            config: SfpConfig::default(),
+            writer: &mut writer,
+        };
+        a.send(b"hk", 0, &mut transport).unwrap();
+
+        let mut decoded = None;
+        SfpDecoder::default().feed(&output, |event| {
+            if let SfpEvent::ExtendedFrame(header, payload) = event {
+                decoded = Some((header, payload.to_vec()));
+            }
+        });
+
+        let (header, payload) = decoded.unwrap();
+        assert_eq!(header.frame_type, FrameType::Data);
+        assert_eq!(header.sequence, Sequence::Long(0));
+        assert!(header.has_flag(FLAG_ACK_REQUEST));
+        assert_eq!(payload, b"hk");
+    }
+}
@@ -0,0 +1,222 @@
+use crate::config::SfpConfig;
+use crate::decoder::SfpEvent;
+use crate::encoder::{EncodeError, WriteFrameError, SFP_CRC_LEN};
+use crate::frame::SFP_DATA_LEN_MAX;
+
+/// Frame delimiter, the only byte value which never appears inside a COBS frame
+pub const COBS_DELIMITER: u8 = 0x00;
+
+// Largest block of non-zero bytes encoded behind one code byte
+const BLOCK_LEN_MAX: usize = 254;
+
+/// Largest size of a COBS frame carrying `data_len` bytes of data: the stuffed Data and
+/// CRC fields and the delimiter.
+pub const fn cobs_frame_len(data_len: usize) -> usize {
+    let stuffed_len = data_len + SFP_CRC_LEN;
+    stuffed_len + stuffed_len / BLOCK_LEN_MAX + 1 + 1
+}
+
+/// Encode a COBS frame carrying `data` into `buffer`. Returns the size of the frame.
+///
+/// The frame is the COBS encoding of the Data field followed by its big-endian CRC,
+/// terminated by COBS_DELIMITER.
+pub fn encode_cobs_frame(
+    config: &SfpConfig,
+    data: &[u8],
+    buffer: &mut [u8],
+) -> Result<usize, EncodeError> {
+    let mut written = 0;
+    stuff(config, data, |chunk| {
+        let end = written + chunk.len();
+        if end > buffer.len() {
+            return Err(EncodeError::BufferTooSmall);
+        }
+        buffer[written..end].copy_from_slice(chunk);
+        written = end;
+        Ok(())
+    })?;
+
+    Ok(written)
+}
+
+/// Encode a COBS frame carrying `data` directly into `writer`. Returns the size of the frame.
+pub fn write_cobs_frame<W>(
+    config: &SfpConfig,
+    writer: &mut W,
+    data: &[u8],
+) -> Result<usize, WriteFrameError<W::Error>>
+where
+    W: embedded_io::Write,
+{
+    let mut written = 0;
+    stuff(config, data, |chunk| {
+        written += chunk.len();
+        writer.write_all(chunk).map_err(WriteFrameError::Io)
+    })?;
+
+    Ok(written)
+}
+
+/// Run the COBS encoding of the Data and CRC fields, passing the frame in chunks to `emit`.
+fn stuff<E, F>(config: &SfpConfig, data: &[u8], mut emit: F) -> Result<(), E>
+where
+    E: From<EncodeError>,
+    F: FnMut(&[u8]) -> Result<(), E>,
+{
+    if !config.is_length_valid(data.len()) {
+        return Err(EncodeError::InvalidLength.into());
+    }
+
+    let crc = config.crc.checksum(data).to_be_bytes();
+    let mut block = [0; 1 + BLOCK_LEN_MAX];
+    let mut block_len = 0;
+
+    for &byte in data.iter().chain(crc.iter()) {
+        if byte == COBS_DELIMITER {
+            block[0] = block_len as u8 + 1;
+            emit(&block[..=block_len])?;
+            block_len = 0;
+        } else {
+            block_len += 1;
+            block[block_len] = byte;
+
+            if block_len == BLOCK_LEN_MAX {
+                block[0] = 0xFF;
+                emit(&block)?;
+                block_len = 0;
+            }
+        }
+    }
+
+    block[0] = block_len as u8 + 1;
+    emit(&block[..=block_len])?;
+    emit(&[COBS_DELIMITER])
+}
+
+/// Streaming COBS decoder, which unstuffs the incoming bytes on the fly and checks the
+/// length and CRC of each frame when its delimiter is received.
+///
+/// Reports `Frame`, `CrcError`, `LengthOutOfRange` and `EncodingError` events. Empty
+/// frames (consecutive delimiters) are ignored, so a sender may also start its frames
+/// with a delimiter to flush any noise received before.
+pub struct CobsDecoder {
+    config: SfpConfig,
+    buffer: [u8; SFP_DATA_LEN_MAX + SFP_CRC_LEN],
+    index: usize,
+    // Code byte of the current block
+    code: u8,
+    // Data bytes left in the current block
+    remaining: u8,
+    // A zero byte follows the current block, unless it is the last one
+    pending_zero: bool,
+    started: bool,
+    overflow: bool,
+}
+
+impl Default for CobsDecoder {
+    fn default() -> Self {
+        Self::new(SfpConfig::default())
+    }
+}
+
+impl CobsDecoder {
+    pub const fn new(config: SfpConfig) -> Self {
+        Self {
+            config,
+            buffer: [0; SFP_DATA_LEN_MAX + SFP_CRC_LEN],
+            index: 0,
+            code: 0,
+            remaining: 0,
+            pending_zero: false,
+            started: false,
+            overflow: false,
+        }
+    }
+
+    /// Drop any partially received frame and wait for the next delimiter.
+    pub fn reset(&mut self) {
+        self.index = 0;
+        self.code = 0;
+        self.remaining = 0;
+        self.pending_zero = false;
+        self.started = false;
+        self.overflow = false;
+    }
+
+    /// Process a slice of incoming bytes, reporting every produced event to `on_event`.
+    pub fn feed<F>(&mut self, data: &[u8], mut on_event: F)
+    where
+        F: FnMut(SfpEvent<'_>),
+    {
+        for &byte in data {
+            self.push(byte, &mut on_event);
+        }
+    }
+
+    /// Process one incoming byte, reporting the produced events to `on_event`.
+    pub fn push<F>(&mut self, byte: u8, mut on_event: F)
+    where
+        F: FnMut(SfpEvent<'_>),
+    {
+        if byte == COBS_DELIMITER {
+            if self.started {
+                self.end_of_frame(&mut on_event);
+            }
+            self.reset();
+            return;
+        }
+
+        self.started = true;
+
+        if self.remaining == 0 {
+            // Code byte, starting a new block
+            if self.pending_zero {
+                self.store(0);
+            }
+            self.code = byte;
+            self.remaining = byte - 1;
+        } else {
+            self.store(byte);
+            self.remaining -= 1;
+        }
+
+        // Blocks shorter than the maximum are followed by a zero byte, unless they are the
+        // last one of the frame
+        self.pending_zero = self.remaining == 0 && self.code != 0xFF;
+    }
+
+    fn store(&mut self, byte: u8) {
+        if self.index < self.buffer.len() {
+            self.buffer[self.index] = byte;
+            self.index += 1;
+        } else {
+            self.overflow = true;
+        }
+    }
+
+    fn end_of_frame<F>(&mut self, on_event: &mut F)
+    where
+        F: FnMut(SfpEvent<'_>),
+    {
+        if self.overflow {
+            on_event(SfpEvent::LengthOutOfRange);
+            return;
+        }
+        if self.remaining != 0 {
+            // Delimiter inside a block
+            on_event(SfpEvent::EncodingError);
+            return;
+        }
+        if self.index < SFP_CRC_LEN || !self.config.is_length_valid(self.index - SFP_CRC_LEN) {
+            on_event(SfpEvent::LengthOutOfRange);
+            return;
+        }
+
+        let (data, crc) = self.buffer[..self.index].split_at(self.index - SFP_CRC_LEN);
+        if u16::from_be_bytes([crc[0], crc[1]]) == self.config.crc.checksum(data) {
+            on_event(SfpEvent::Frame(data));
+        } else {
+            on_event(SfpEvent::CrcError);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use crate::frame::{SFP_DATA_LEN_MAX, SFP_DATA_LEN_MIN};
+use crc::{Crc, Digest, CRC_16_IBM_3740, CRC_16_USB};
+
+// Define the Crc algorithms
+static CRC_16_CCITT: Crc<u16> = Crc::<u16>::new(&CRC_16_IBM_3740);
+static CRC_16_USB_LEGACY: Crc<u16> = Crc::<u16>::new(&CRC_16_USB);
+
+/// CRC algorithm calculated on the Data Length and Data fields of a frame.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum SfpCrc {
+    /// CRC-16-CCITT (poly 0x1021, init 0xFFFF), as required by the SFP specification
+    #[default]
+    Crc16Ccitt,
+    /// CRC-16-USB, used by peers implemented before the specification was fixed
+    Crc16Usb,
+}
+
+impl SfpCrc {
+    /// Calculate the CRC of the given bytes.
+    pub fn checksum(self, bytes: &[u8]) -> u16 {
+        self.algorithm().checksum(bytes)
+    }
+
+    /// Start an incremental CRC calculation.
+    pub fn digest(self) -> Digest<'static, u16> {
+        self.algorithm().digest()
+    }
+
+    fn algorithm(self) -> &'static Crc<u16> {
+        match self {
+            SfpCrc::Crc16Ccitt => &CRC_16_CCITT,
+            SfpCrc::Crc16Usb => &CRC_16_USB_LEGACY,
+        }
+    }
+}
+
+/// Serial Frame Protocol version.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+pub enum SfpVersion {
+    /// Opaque payload only
+    V0_1,
+    /// Extended header with version, frame type, flags and sequence counter
+    V0_2,
+}
+
+impl SfpVersion {
+    /// Decode a version byte (major in high nibble, minor in low nibble).
+    pub fn from_byte(value: u8) -> Option<Self> {
+        match (value >> 4, value & 0x0F) {
+            (0, 1) => Some(SfpVersion::V0_1),
+            // Later 0.x versions keep the 0.2 header layout
+            (0, minor) if minor >= 2 => Some(SfpVersion::V0_2),
+            _ => None,
+        }
+    }
+}
+
+/// Serial Frame Protocol configuration, shared by the encoding and decoding sides.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SfpConfig {
+    /// CRC algorithm of the frames
+    pub crc: SfpCrc,
+    /// Maximum accepted Data Length. Values above SFP_DATA_LEN_MAX are capped to it.
+    pub max_len: usize,
+    /// Highest protocol version accepted. Legacy 0.1 frames are always accepted.
+    pub version: SfpVersion,
+}
+
+impl Default for SfpConfig {
+    fn default() -> Self {
+        Self {
+            crc: SfpCrc::Crc16Ccitt,
+            max_len: SFP_DATA_LEN_MAX,
+            version: SfpVersion::V0_2,
+        }
+    }
+}
+
+impl SfpConfig {
+    /// Configuration for the peers implementing SFP 0.1 with the CRC-16-USB algorithm.
+    pub fn legacy() -> Self {
+        Self {
+            crc: SfpCrc::Crc16Usb,
+            version: SfpVersion::V0_1,
+            ..Default::default()
+        }
+    }
+
+    /// Check if the Data Length is within SFP_DATA_LEN_MIN and the configured maximum.
+    pub fn is_length_valid(&self, data_len: usize) -> bool {
+        let max_len = self.max_len.min(SFP_DATA_LEN_MAX);
+        (SFP_DATA_LEN_MIN..=max_len).contains(&data_len)
+    }
+}
@@ -0,0 +1,336 @@
+use crate::config::{SfpConfig, SfpVersion};
+use crate::frame::{SFP_DATA_LEN_MAX, SFP_EXT_FRAME_MARKER, SFP_FRAME_MARKER};
+use crate::header::{FrameHeader, HeaderError};
+
+/// Events produced by the SFP decoder while processing incoming bytes.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SfpEvent<'a> {
+    /// A complete frame with a valid CRC was received. Holds the Data field.
+    Frame(&'a [u8]),
+    /// A complete SFP 0.2 frame with a valid CRC was received. Holds the extended header
+    /// and the rest of the Data field.
+    ExtendedFrame(FrameHeader, &'a [u8]),
+    /// A complete SFP 0.2 frame was received, but its extended header is invalid.
+    InvalidHeader(HeaderError),
+    /// A complete frame was received, but its CRC does not match the calculated one.
+    CrcError,
+    /// A complete frame was received, but its byte stuffing is invalid (COBS framing).
+    EncodingError,
+    /// The Data Length field is outside of SFP_DATA_LEN_MIN and the configured maximum.
+    LengthOutOfRange,
+    /// A byte which is not part of any frame.
+    NonFrameByte(u8),
+}
+
+/// Consumer of the bytes which are not part of any SFP frame, such as an ASCII console
+/// sharing the same serial bus.
+pub trait Passthrough {
+    fn pass(&mut self, byte: u8);
+}
+
+impl<F> Passthrough for F
+where
+    F: FnMut(u8),
+{
+    fn pass(&mut self, byte: u8) {
+        self(byte)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum DecoderState {
+    Marker,
+    MarkerSecond,
+    LengthHigh,
+    LengthLow,
+    Data,
+    CrcHigh,
+    CrcLow,
+}
+
+/// Streaming SFP decoder, which is fed one byte at a time and walks the
+/// Marker -> Length -> Data -> CRC states of the Serial Frame Protocol.
+pub struct SfpDecoder {
+    config: SfpConfig,
+    state: DecoderState,
+    extended: bool,
+    buffer: [u8; SFP_DATA_LEN_MAX],
+    data_len: usize,
+    index: usize,
+    frame_crc: u16,
+}
+
+impl Default for SfpDecoder {
+    fn default() -> Self {
+        Self::new(SfpConfig::default())
+    }
+}
+
+impl SfpDecoder {
+    pub const fn new(config: SfpConfig) -> Self {
+        Self {
+            config,
+            state: DecoderState::Marker,
+            extended: false,
+            buffer: [0; SFP_DATA_LEN_MAX],
+            data_len: 0,
+            index: 0,
+            frame_crc: 0,
+        }
+    }
+
+    /// Drop any partially received frame and wait for a new Frame Marker.
+    pub fn reset(&mut self) {
+        self.state = DecoderState::Marker;
+        self.extended = false;
+        self.data_len = 0;
+        self.index = 0;
+        self.frame_crc = 0;
+    }
+
+    /// Process a slice of incoming bytes, reporting every produced event to `on_event`.
+    pub fn feed<F>(&mut self, data: &[u8], mut on_event: F)
+    where
+        F: FnMut(SfpEvent<'_>),
+    {
+        for &byte in data {
+            self.push(byte, &mut on_event);
+        }
+    }
+
+    /// Process a slice of incoming bytes, re-routing the non-frame bytes to `passthrough`
+    /// and reporting the frame events to `on_event`.
+    pub fn feed_routed<P, F>(&mut self, data: &[u8], passthrough: &mut P, mut on_event: F)
+    where
+        P: Passthrough,
+        F: FnMut(SfpEvent<'_>),
+    {
+        for &byte in data {
+            self.push_routed(byte, passthrough, &mut on_event);
+        }
+    }
+
+    /// Process one incoming byte, re-routing the non-frame bytes to `passthrough`
+    /// and reporting the frame events to `on_event`.
+    pub fn push_routed<P, F>(&mut self, byte: u8, passthrough: &mut P, mut on_event: F)
+    where
+        P: Passthrough,
+        F: FnMut(SfpEvent<'_>),
+    {
+        self.push(byte, |event| match event {
+            SfpEvent::NonFrameByte(byte) => passthrough.pass(byte),
+            event => on_event(event),
+        });
+    }
+
+    /// Process one incoming byte, reporting the produced events to `on_event`.
+    ///
+    /// Bytes of a rejected header are reported back as non-frame bytes, except the ones
+    /// which may start a new frame. Bytes of a frame with a CRC error are discarded.
+    pub fn push<F>(&mut self, byte: u8, mut on_event: F)
+    where
+        F: FnMut(SfpEvent<'_>),
+    {
+        if let Some(replay) = self.step(byte, &mut on_event) {
+            // Search again for the Frame Marker in the rejected header
+            for byte in replay {
+                let _ = self.step(byte, &mut on_event);
+            }
+        }
+    }
+
+    /// Advance the state machine with one byte. Returns the header bytes which shall be
+    /// processed again when the header gets rejected.
+    fn step<F>(&mut self, byte: u8, on_event: &mut F) -> Option<[u8; 3]>
+    where
+        F: FnMut(SfpEvent<'_>),
+    {
+        match self.state {
+            DecoderState::Marker => {
+                if byte == SFP_FRAME_MARKER {
+                    self.state = DecoderState::MarkerSecond;
+                } else {
+                    on_event(SfpEvent::NonFrameByte(byte));
+                }
+            }
+            DecoderState::MarkerSecond => {
+                self.state = DecoderState::Marker;
+
+                if byte == SFP_FRAME_MARKER {
+                    self.extended = false;
+                    self.state = DecoderState::LengthHigh;
+                } else if byte == SFP_EXT_FRAME_MARKER && self.config.version >= SfpVersion::V0_2 {
+                    self.extended = true;
+                    self.state = DecoderState::LengthHigh;
+                } else {
+                    // The lone marker byte is not part of a frame either
+                    on_event(SfpEvent::NonFrameByte(SFP_FRAME_MARKER));
+                    on_event(SfpEvent::NonFrameByte(byte));
+                }
+            }
+            DecoderState::LengthHigh => {
+                self.data_len = (byte as usize) << 8;
+                self.state = DecoderState::LengthLow;
+            }
+            DecoderState::LengthLow => {
+                self.data_len |= byte as usize;
+
+                // Validate the length before waiting for any data
+                if self.config.is_length_valid(self.data_len) {
+                    self.index = 0;
+                    self.state = DecoderState::Data;
+                } else {
+                    let second_marker = if self.extended {
+                        SFP_EXT_FRAME_MARKER
+                    } else {
+                        SFP_FRAME_MARKER
+                    };
+                    let replay = [
+                        second_marker,
+                        (self.data_len >> 8) as u8,
+                        self.data_len as u8,
+                    ];
+                    self.reset();
+                    on_event(SfpEvent::LengthOutOfRange);
+                    on_event(SfpEvent::NonFrameByte(SFP_FRAME_MARKER));
+                    return Some(replay);
+                }
+            }
+            DecoderState::Data => {
+                self.buffer[self.index] = byte;
+                self.index += 1;
+
+                if self.index == self.data_len {
+                    self.state = DecoderState::CrcHigh;
+                }
+            }
+            DecoderState::CrcHigh => {
+                self.frame_crc = (byte as u16) << 8;
+                self.state = DecoderState::CrcLow;
+            }
+            DecoderState::CrcLow => {
+                self.frame_crc |= byte as u16;
+                self.state = DecoderState::Marker;
+
+                if self.frame_crc != self.compute_crc() {
+                    on_event(SfpEvent::CrcError);
+                } else if self.extended {
+                    let data = &self.buffer[..self.data_len];
+                    match FrameHeader::parse(data) {
+                        Ok((header, header_len)) => {
+                            on_event(SfpEvent::ExtendedFrame(header, &data[header_len..]))
+                        }
+                        Err(error) => on_event(SfpEvent::InvalidHeader(error)),
+                    }
+                } else {
+                    on_event(SfpEvent::Frame(&self.buffer[..self.data_len]));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Calculate the CRC on the joined Data Length and Data fields.
+    fn compute_crc(&self) -> u16 {
+        let length = [(self.data_len >> 8) as u8, self.data_len as u8];
+
+        let mut crc = self.config.crc.digest();
+        crc.update(&length);
+        crc.update(&self.buffer[..self.data_len]);
+        crc.finalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::pack_frame;
+
+    fn frame(data: &[u8]) -> Vec<u8> {
+        let mut buffer = [0; 1024];
+        let length = pack_frame(data, &mut buffer).unwrap();
+        buffer[..length].to_vec()
+    }
+
+    /// Decode the stream, returning the recovered frames and the passthrough bytes.
+    fn decode(stream: &[u8]) -> (Vec<Vec<u8>>, Vec<u8>) {
+        let mut decoder = SfpDecoder::default();
+        let mut frames = Vec::new();
+        let mut text = Vec::new();
+
+        decoder.feed_routed(stream, &mut |byte| text.push(byte), |event| {
+            if let SfpEvent::Frame(data) = event {
+                frames.push(data.to_vec());
+            }
+        });
+
+        (frames, text)
+    }
+
+    #[test]
+    fn interleaved_text_and_frames_are_recovered() {
+        let mut stream = Vec::new();
+        stream.extend_from_slice(b"boot ok\n");
+        stream.extend(frame(&[0x01, 0x02, 0x03]));
+        stream.extend_from_slice(b"temp: 21 C\n");
+        stream.extend(frame(b"hello"));
+        stream.extend(frame(&[0xAA; 8]));
+        stream.extend_from_slice(b"bye\n");
+
+        let (frames, text) = decode(&stream);
+
+        assert_eq!(
+            frames,
+            [&[0x01, 0x02, 0x03][..], &b"hello"[..], &[0xAA; 8][..]]
+        );
+        assert_eq!(text, b"boot ok\ntemp: 21 C\nbye\n");
+    }
+
+    #[test]
+    fn lone_marker_byte_is_passed_through() {
+        // UTF-8 encoding of "ê" contains the marker byte
+        let mut stream = Vec::new();
+        stream.extend_from_slice("tête\n".as_bytes());
+        stream.extend(frame(&[0x42]));
+
+        let (frames, text) = decode(&stream);
+
+        assert_eq!(frames, [&[0x42][..]]);
+        assert_eq!(text, "tête\n".as_bytes());
+    }
+
+    #[test]
+    fn rejected_header_is_passed_through_and_resynchronized() {
+        // Extra marker byte in front of a valid frame
+        let mut stream = Vec::new();
+        stream.extend_from_slice(b"log");
+        stream.push(SFP_FRAME_MARKER);
+        stream.extend(frame(&[0x10, 0x20]));
+
+        let mut decoder = SfpDecoder::default();
+        let mut frames = Vec::new();
+        let mut text = Vec::new();
+        let mut rejected = 0;
+
+        decoder.feed_routed(&stream, &mut |byte| text.push(byte), |event| match event {
+            SfpEvent::Frame(data) => frames.push(data.to_vec()),
+            SfpEvent::LengthOutOfRange => rejected += 1,
+            _ => {}
+        });
+
+        assert_eq!(frames, [&[0x10, 0x20][..]]);
+        assert_eq!(text, [b'l', b'o', b'g', SFP_FRAME_MARKER]);
+        assert_eq!(rejected, 1);
+    }
+
+    #[test]
+    fn empty_frame_header_is_passed_through() {
+        let stream = [SFP_FRAME_MARKER, SFP_FRAME_MARKER, 0x00, 0x00, b'A'];
+
+        let (frames, text) = decode(&stream);
+
+        assert!(frames.is_empty());
+        assert_eq!(text, stream);
+    }
+}
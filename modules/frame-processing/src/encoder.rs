@@ -0,0 +1,399 @@
+use crate::config::{SfpConfig, SfpVersion};
+use crate::frame::{SFP_EXT_FRAME_MARKER, SFP_FRAME_MARKER};
+use crate::header::{FrameHeader, SFP_EXT_HEADER_LEN_MAX};
+use crc::Digest;
+
+/// Size of the Frame Marker and Data Length fields
+pub const SFP_HEADER_LEN: usize = 4;
+/// Size of the CRC field
+pub const SFP_CRC_LEN: usize = 2;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum EncodeError {
+    /// Data length is outside of SFP_DATA_LEN_MIN and the configured maximum
+    InvalidLength,
+    /// Output buffer is too small to hold the frame
+    BufferTooSmall,
+    /// Amount of payload does not match the announced Data Length
+    LengthMismatch,
+    /// Frame format is not enabled by the configured protocol version
+    UnsupportedVersion,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum WriteFrameError<E> {
+    /// Frame could not be encoded
+    Encode(EncodeError),
+    /// Error of the underlying writer
+    Io(E),
+}
+
+impl<E> From<EncodeError> for WriteFrameError<E> {
+    fn from(e: EncodeError) -> Self {
+        WriteFrameError::Encode(e)
+    }
+}
+
+/// Total size of a frame carrying `data_len` bytes of data.
+pub const fn frame_len(data_len: usize) -> usize {
+    SFP_HEADER_LEN + data_len + SFP_CRC_LEN
+}
+
+/// Encode a frame carrying `data` into `buffer`. Returns the size of the frame.
+pub fn encode_frame(
+    config: &SfpConfig,
+    data: &[u8],
+    buffer: &mut [u8],
+) -> Result<usize, EncodeError> {
+    let encoder = SfpEncoder::new(config, data.len())?;
+    encode_with(encoder, data, buffer)
+}
+
+/// Encode an SFP 0.2 frame with the extended `header`, carrying `payload`, into `buffer`.
+/// Returns the size of the frame.
+pub fn encode_extended_frame(
+    config: &SfpConfig,
+    header: &FrameHeader,
+    payload: &[u8],
+    buffer: &mut [u8],
+) -> Result<usize, EncodeError> {
+    let encoder = SfpEncoder::new_extended(config, header, payload.len())?;
+    encode_with(encoder, payload, buffer)
+}
+
+/// Encode a frame carrying `data` directly into `writer`. Returns the size of the frame.
+pub fn write_frame<W>(
+    config: &SfpConfig,
+    writer: &mut W,
+    data: &[u8],
+) -> Result<usize, WriteFrameError<W::Error>>
+where
+    W: embedded_io::Write,
+{
+    let encoder = SfpEncoder::new(config, data.len())?;
+    write_with(encoder, writer, data)
+}
+
+/// Encode an SFP 0.2 frame with the extended `header`, carrying `payload`, directly into
+/// `writer`. Returns the size of the frame.
+pub fn write_extended_frame<W>(
+    config: &SfpConfig,
+    writer: &mut W,
+    header: &FrameHeader,
+    payload: &[u8],
+) -> Result<usize, WriteFrameError<W::Error>>
+where
+    W: embedded_io::Write,
+{
+    let encoder = SfpEncoder::new_extended(config, header, payload.len())?;
+    write_with(encoder, writer, payload)
+}
+
+fn encode_with(
+    mut encoder: SfpEncoder,
+    payload: &[u8],
+    buffer: &mut [u8],
+) -> Result<usize, EncodeError> {
+    let frame_len = encoder.frame_len();
+
+    if buffer.len() < frame_len {
+        return Err(EncodeError::BufferTooSmall);
+    }
+
+    let (header, rest) = buffer.split_at_mut(encoder.header().len());
+    let (data, rest) = rest.split_at_mut(payload.len());
+
+    header.copy_from_slice(encoder.header());
+    data.copy_from_slice(payload);
+    encoder.payload(payload)?;
+    rest[..SFP_CRC_LEN].copy_from_slice(&encoder.finish()?);
+
+    Ok(frame_len)
+}
+
+fn write_with<W>(
+    mut encoder: SfpEncoder,
+    writer: &mut W,
+    payload: &[u8],
+) -> Result<usize, WriteFrameError<W::Error>>
+where
+    W: embedded_io::Write,
+{
+    let frame_len = encoder.frame_len();
+
+    writer
+        .write_all(encoder.header())
+        .map_err(WriteFrameError::Io)?;
+    encoder.payload(payload)?;
+    writer.write_all(payload).map_err(WriteFrameError::Io)?;
+    writer
+        .write_all(&encoder.finish()?)
+        .map_err(WriteFrameError::Io)?;
+
+    Ok(frame_len)
+}
+
+/// Incremental SFP encoder, which emits the header, the payload in chunks and the CRC
+/// of a frame without needing the whole frame in a contiguous buffer.
+pub struct SfpEncoder {
+    header: [u8; SFP_HEADER_LEN + SFP_EXT_HEADER_LEN_MAX],
+    header_len: usize,
+    data_len: usize,
+    written: usize,
+    digest: Digest<'static, u16>,
+}
+
+impl SfpEncoder {
+    /// Start a frame which will carry `data_len` bytes of data.
+    pub fn new(config: &SfpConfig, data_len: usize) -> Result<Self, EncodeError> {
+        Self::start(config, SFP_FRAME_MARKER, data_len)
+    }
+
+    /// Start an SFP 0.2 frame with the extended `header`, which will carry `payload_len`
+    /// bytes of payload after the header.
+    pub fn new_extended(
+        config: &SfpConfig,
+        header: &FrameHeader,
+        payload_len: usize,
+    ) -> Result<Self, EncodeError> {
+        if config.version < SfpVersion::V0_2 {
+            return Err(EncodeError::UnsupportedVersion);
+        }
+
+        let mut encoder = Self::start(
+            config,
+            SFP_EXT_FRAME_MARKER,
+            header.encoded_len() + payload_len,
+        )?;
+
+        // The extended header is the beginning of the Data field
+        let header_len = header.encode(&mut encoder.header[SFP_HEADER_LEN..])?;
+        let header_end = SFP_HEADER_LEN + header_len;
+        encoder
+            .digest
+            .update(&encoder.header[SFP_HEADER_LEN..header_end]);
+        encoder.header_len = header_end;
+        encoder.written = header_len;
+
+        Ok(encoder)
+    }
+
+    fn start(config: &SfpConfig, second_marker: u8, data_len: usize) -> Result<Self, EncodeError> {
+        if !config.is_length_valid(data_len) {
+            return Err(EncodeError::InvalidLength);
+        }
+
+        let length = (data_len as u16).to_be_bytes();
+        let mut header = [0; SFP_HEADER_LEN + SFP_EXT_HEADER_LEN_MAX];
+        header[..SFP_HEADER_LEN].copy_from_slice(&[
+            SFP_FRAME_MARKER,
+            second_marker,
+            length[0],
+            length[1],
+        ]);
+
+        // The CRC is calculated on the Data Length and Data fields
+        let mut digest = config.crc.digest();
+        digest.update(&length);
+
+        Ok(Self {
+            header,
+            header_len: SFP_HEADER_LEN,
+            data_len,
+            written: 0,
+            digest,
+        })
+    }
+
+    /// Frame Marker, Data Length and extended header fields, to be sent before the payload.
+    pub fn header(&self) -> &[u8] {
+        &self.header[..self.header_len]
+    }
+
+    /// Total size of the frame.
+    pub fn frame_len(&self) -> usize {
+        frame_len(self.data_len)
+    }
+
+    /// Account for a chunk of payload, which the caller sends after the header.
+    pub fn payload(&mut self, chunk: &[u8]) -> Result<(), EncodeError> {
+        if self.written + chunk.len() > self.data_len {
+            return Err(EncodeError::LengthMismatch);
+        }
+
+        self.digest.update(chunk);
+        self.written += chunk.len();
+
+        Ok(())
+    }
+
+    /// CRC field, to be sent after the whole payload.
+    pub fn finish(self) -> Result<[u8; SFP_CRC_LEN], EncodeError> {
+        if self.written != self.data_len {
+            return Err(EncodeError::LengthMismatch);
+        }
+
+        Ok(self.digest.finalize().to_be_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::{SfpDecoder, SfpEvent};
+    use crate::frame::{pack_frame, SFP_DATA_LEN_MAX};
+    use crate::header::{FrameType, Sequence};
+
+    #[test]
+    fn encoded_frame_matches_packed_frame() {
+        let mut expected = [0; 1024];
+        let expected_len = pack_frame(b"telemetry", &mut expected).unwrap();
+
+        let mut buffer = [0; 32];
+        let frame_len = encode_frame(&SfpConfig::default(), b"telemetry", &mut buffer);
+
+        assert_eq!(frame_len, Ok(expected_len));
+        assert_eq!(&buffer[..expected_len], &expected[..expected_len]);
+    }
+
+    #[test]
+    fn maximum_frame_is_encoded() {
+        let data = [0x5A; SFP_DATA_LEN_MAX];
+        let mut buffer = [0; frame_len(SFP_DATA_LEN_MAX)];
+
+        assert_eq!(
+            encode_frame(&SfpConfig::default(), &data, &mut buffer),
+            Ok(frame_len(SFP_DATA_LEN_MAX))
+        );
+    }
+
+    #[test]
+    fn invalid_frames_are_rejected() {
+        let config = SfpConfig::default();
+        let mut buffer = [0; 8];
+
+        assert_eq!(
+            encode_frame(&config, &[], &mut buffer),
+            Err(EncodeError::InvalidLength)
+        );
+        assert_eq!(
+            encode_frame(&config, &[0; SFP_DATA_LEN_MAX + 1], &mut buffer),
+            Err(EncodeError::InvalidLength)
+        );
+        assert_eq!(
+            encode_frame(&config, &[0; 3], &mut buffer),
+            Err(EncodeError::BufferTooSmall)
+        );
+    }
+
+    #[test]
+    fn incremental_encoder_matches_encoded_frame() {
+        let config = SfpConfig::default();
+        let data = [0x11, 0x22, 0x33, 0x44, 0x55];
+        let mut expected = [0; 16];
+        let expected_len = encode_frame(&config, &data, &mut expected).unwrap();
+
+        let mut encoder = SfpEncoder::new(&config, data.len()).unwrap();
+        let mut frame = encoder.header().to_vec();
+        assert_eq!(frame.len(), SFP_HEADER_LEN);
+        for chunk in data.chunks(2) {
+            encoder.payload(chunk).unwrap();
+            frame.extend_from_slice(chunk);
+        }
+        frame.extend_from_slice(&encoder.finish().unwrap());
+
+        assert_eq!(frame, &expected[..expected_len]);
+    }
+
+    #[test]
+    fn incremental_encoder_checks_payload_length() {
+        let config = SfpConfig::default();
+
+        let mut encoder = SfpEncoder::new(&config, 2).unwrap();
+        assert_eq!(
+            encoder.payload(&[1, 2, 3]),
+            Err(EncodeError::LengthMismatch)
+        );
+        encoder.payload(&[1]).unwrap();
+        assert_eq!(encoder.finish(), Err(EncodeError::LengthMismatch));
+    }
+
+    #[test]
+    fn frame_is_written_into_writer() {
+        let config = SfpConfig::default();
+        let mut expected = [0; 16];
+        let expected_len = encode_frame(&config, b"ok", &mut expected).unwrap();
+
+        let mut output = [0; 16];
+        let mut writer = &mut output[..];
+        assert_eq!(write_frame(&config, &mut writer, b"ok"), Ok(expected_len));
+        assert_eq!(&output[..expected_len], &expected[..expected_len]);
+
+        let mut small = [0; 4];
+        let mut writer = &mut small[..];
+        assert!(matches!(
+            write_frame(&config, &mut writer, b"ok"),
+            Err(WriteFrameError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn extended_frame_is_decoded() {
+        let config = SfpConfig::default();
+        let header = FrameHeader::new(FrameType::Data, Sequence::Short(42));
+        let mut buffer = [0; 32];
+        let frame_len = encode_extended_frame(&config, &header, b"tc", &mut buffer).unwrap();
+
+        assert_eq!(frame_len, 12);
+        assert_eq!(&buffer[..4], &[0xAA, 0x55, 0x00, 0x06]);
+
+        let mut decoded = None;
+        SfpDecoder::new(config).feed(&buffer[..frame_len], |event| {
+            if let SfpEvent::ExtendedFrame(header, payload) = event {
+                decoded = Some((header, payload.to_vec()));
+            }
+        });
+        assert_eq!(decoded, Some((header, b"tc".to_vec())));
+    }
+
+    #[test]
+    fn extended_frame_without_payload_is_encoded() {
+        let config = SfpConfig::default();
+        let header = FrameHeader::new(FrameType::Ack, Sequence::Long(0xBEEF));
+        let mut buffer = [0; 16];
+
+        assert_eq!(
+            encode_extended_frame(&config, &header, &[], &mut buffer),
+            Ok(11)
+        );
+    }
+
+    #[test]
+    fn extended_frame_requires_version() {
+        let header = FrameHeader::new(FrameType::Data, Sequence::Short(0));
+
+        assert!(matches!(
+            SfpEncoder::new_extended(&SfpConfig::legacy(), &header, 1),
+            Err(EncodeError::UnsupportedVersion)
+        ));
+    }
+
+    #[test]
+    fn legacy_decoder_passes_extended_frame_through() {
+        let header = FrameHeader::new(FrameType::Data, Sequence::Short(1));
+        let mut buffer = [0; 16];
+        let frame_len =
+            encode_extended_frame(&SfpConfig::default(), &header, b"x", &mut buffer).unwrap();
+
+        let config = SfpConfig {
+            version: SfpVersion::V0_1,
+            ..Default::default()
+        };
+        let mut passthrough = Vec::new();
+        SfpDecoder::new(config).feed(&buffer[..frame_len], |event| match event {
+            SfpEvent::NonFrameByte(byte) => passthrough.push(byte),
+            other => panic!("Unexpected event: {:?}", other),
+        });
+        assert_eq!(passthrough, &buffer[..frame_len]);
+    }
+}
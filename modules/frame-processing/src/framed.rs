@@ -0,0 +1,253 @@
+use crate::cobs::cobs_frame_len;
+use crate::decoder::SfpEvent;
+use crate::encoder::EncodeError;
+use crate::frame::SFP_DATA_LEN_MAX;
+use crate::framing::{FrameDecoder, FrameEncoder};
+use crate::header::FrameHeader;
+
+// Largest frame of any framing mode
+const TX_BUFFER_LEN: usize = cobs_frame_len(SFP_DATA_LEN_MAX);
+// Size of the chunks read from the serial
+const RX_CHUNK_LEN: usize = 64;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FramedError<E> {
+    /// Frame could not be encoded
+    Encode(EncodeError),
+    /// Error of the underlying serial
+    Io(E),
+    /// Serial reached the end of the stream
+    EndOfStream,
+}
+
+impl<E> From<EncodeError> for FramedError<E> {
+    fn from(e: EncodeError) -> Self {
+        FramedError::Encode(e)
+    }
+}
+
+/// Frame received by `FramedSerial`, or the reason why a frame was rejected.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FramedEvent<'a> {
+    /// A valid frame was received. Holds the Data field.
+    Frame(&'a [u8]),
+    /// A valid SFP 0.2 frame was received. Holds the extended header and the payload.
+    ExtendedFrame(FrameHeader, &'a [u8]),
+    /// A frame was rejected by the decoder (CRC, length, header or stuffing error).
+    Rejected(SfpEvent<'static>),
+}
+
+// Received frame kept in the rx buffer until it is returned to the caller
+#[derive(Copy, Clone)]
+enum Received {
+    Frame(usize),
+    ExtendedFrame(FrameHeader, usize),
+    Rejected(SfpEvent<'static>),
+}
+
+/// Async framed transport over a serial implementing the `embedded-io-async` traits,
+/// which yields the decoded frames and sends whole frames.
+///
+/// Bytes which are not part of any frame are discarded.
+pub struct FramedSerial<IO, E, D> {
+    io: IO,
+    encoder: E,
+    decoder: D,
+    tx_buffer: [u8; TX_BUFFER_LEN],
+    rx_chunk: [u8; RX_CHUNK_LEN],
+    rx_chunk_len: usize,
+    rx_index: usize,
+    frame: [u8; SFP_DATA_LEN_MAX],
+}
+
+impl<IO, E, D> FramedSerial<IO, E, D>
+where
+    IO: embedded_io_async::Read + embedded_io_async::Write,
+    E: FrameEncoder,
+    D: FrameDecoder,
+{
+    pub fn new(io: IO, encoder: E, decoder: D) -> Self {
+        Self {
+            io,
+            encoder,
+            decoder,
+            tx_buffer: [0; TX_BUFFER_LEN],
+            rx_chunk: [0; RX_CHUNK_LEN],
+            rx_chunk_len: 0,
+            rx_index: 0,
+            frame: [0; SFP_DATA_LEN_MAX],
+        }
+    }
+
+    /// Release the underlying serial.
+    pub fn release(self) -> IO {
+        self.io
+    }
+
+    /// Send a frame carrying `data`.
+    pub async fn send(&mut self, data: &[u8]) -> Result<(), FramedError<IO::Error>> {
+        let frame_len = self.encoder.encode(data, &mut self.tx_buffer)?;
+
+        self.io
+            .write_all(&self.tx_buffer[..frame_len])
+            .await
+            .map_err(FramedError::Io)?;
+        self.io.flush().await.map_err(FramedError::Io)
+    }
+
+    /// Wait for the next frame, or for the next frame rejected by the decoder.
+    pub async fn receive(&mut self) -> Result<FramedEvent<'_>, FramedError<IO::Error>> {
+        loop {
+            while self.rx_index < self.rx_chunk_len {
+                let byte = self.rx_chunk[self.rx_index];
+                self.rx_index += 1;
+
+                if let Some(received) = self.decode(byte) {
+                    return Ok(match received {
+                        Received::Frame(len) => FramedEvent::Frame(&self.frame[..len]),
+                        Received::ExtendedFrame(header, len) => {
+                            FramedEvent::ExtendedFrame(header, &self.frame[..len])
+                        }
+                        Received::Rejected(event) => FramedEvent::Rejected(event),
+                    });
+                }
+            }
+
+            let len = self
+                .io
+                .read(&mut self.rx_chunk)
+                .await
+                .map_err(FramedError::Io)?;
+            if len == 0 {
+                return Err(FramedError::EndOfStream);
+            }
+            self.rx_chunk_len = len;
+            self.rx_index = 0;
+        }
+    }
+
+    /// Feed one byte to the decoder, keeping the first frame event it produces.
+    fn decode(&mut self, byte: u8) -> Option<Received> {
+        let frame = &mut self.frame;
+        let mut received = None;
+
+        self.decoder.push(byte, |event| {
+            if received.is_some() {
+                return;
+            }
+            received = match event {
+                SfpEvent::Frame(data) => {
+                    frame[..data.len()].copy_from_slice(data);
+                    Some(Received::Frame(data.len()))
+                }
+                SfpEvent::ExtendedFrame(header, payload) => {
+                    frame[..payload.len()].copy_from_slice(payload);
+                    Some(Received::ExtendedFrame(header, payload.len()))
+                }
+                SfpEvent::InvalidHeader(error) => {
+                    Some(Received::Rejected(SfpEvent::InvalidHeader(error)))
+                }
+                SfpEvent::CrcError => Some(Received::Rejected(SfpEvent::CrcError)),
+                SfpEvent::EncodingError => Some(Received::Rejected(SfpEvent::EncodingError)),
+                SfpEvent::LengthOutOfRange => Some(Received::Rejected(SfpEvent::LengthOutOfRange)),
+                SfpEvent::NonFrameByte(_) => None,
+            };
+        });
+
+        received
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SfpConfig;
+    use crate::framing::Framing;
+    use core::convert::Infallible;
+    use futures_executor::block_on;
+
+    /// Serial returning the input in small chunks and collecting the output.
+    struct MockSerial {
+        input: Vec<u8>,
+        output: Vec<u8>,
+    }
+
+    impl embedded_io_async::ErrorType for MockSerial {
+        type Error = Infallible;
+    }
+
+    impl embedded_io_async::Read for MockSerial {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            let len = buf.len().min(self.input.len()).min(5);
+            buf[..len].copy_from_slice(&self.input[..len]);
+            self.input.drain(..len);
+            Ok(len)
+        }
+    }
+
+    impl embedded_io_async::Write for MockSerial {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.output.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    fn framed(
+        framing: Framing,
+        input: Vec<u8>,
+    ) -> FramedSerial<MockSerial, impl FrameEncoder, impl FrameDecoder> {
+        let config = SfpConfig::default();
+        let serial = MockSerial {
+            input,
+            output: Vec::new(),
+        };
+        FramedSerial::new(serial, framing.encoder(config), framing.decoder(config))
+    }
+
+    #[test]
+    fn sent_frames_are_received() {
+        for framing in [Framing::Sfp, Framing::Cobs] {
+            let mut sender = framed(framing, Vec::new());
+            block_on(sender.send(b"telemetry")).unwrap();
+            block_on(sender.send(&[0xAA; 40])).unwrap();
+
+            let stream = sender.release().output;
+            let mut receiver = framed(framing, stream);
+
+            assert_eq!(
+                block_on(receiver.receive()),
+                Ok(FramedEvent::Frame(b"telemetry"))
+            );
+            assert_eq!(
+                block_on(receiver.receive()),
+                Ok(FramedEvent::Frame(&[0xAA; 40]))
+            );
+            assert_eq!(block_on(receiver.receive()), Err(FramedError::EndOfStream));
+        }
+    }
+
+    #[test]
+    fn rejected_frames_are_reported() {
+        let mut sender = framed(Framing::Sfp, Vec::new());
+        block_on(sender.send(b"telecommand")).unwrap();
+        let mut stream = sender.release().output;
+        stream[6] ^= 0x01;
+
+        let mut receiver = framed(Framing::Sfp, stream);
+        assert_eq!(
+            block_on(receiver.receive()),
+            Ok(FramedEvent::Rejected(SfpEvent::CrcError))
+        );
+    }
+
+    #[test]
+    fn invalid_frame_is_not_sent() {
+        let mut sender = framed(Framing::Sfp, Vec::new());
+
+        assert_eq!(
+            block_on(sender.send(&[])),
+            Err(FramedError::Encode(EncodeError::InvalidLength))
+        );
+        assert!(sender.release().output.is_empty());
+    }
+}
@@ -0,0 +1,304 @@
+use crate::cobs::{cobs_frame_len, encode_cobs_frame, write_cobs_frame, CobsDecoder};
+use crate::config::SfpConfig;
+use crate::decoder::{SfpDecoder, SfpEvent};
+use crate::encoder::{encode_frame, frame_len, write_frame, EncodeError, WriteFrameError};
+
+/// Encoder of one of the framing modes of the serial link.
+pub trait FrameEncoder {
+    /// Largest size of a frame carrying `data_len` bytes of data.
+    fn max_frame_len(&self, data_len: usize) -> usize;
+
+    /// Encode a frame carrying `data` into `buffer`. Returns the size of the frame.
+    fn encode(&self, data: &[u8], buffer: &mut [u8]) -> Result<usize, EncodeError>;
+
+    /// Encode a frame carrying `data` directly into `writer`. Returns the size of the frame.
+    fn write<W>(&self, writer: &mut W, data: &[u8]) -> Result<usize, WriteFrameError<W::Error>>
+    where
+        W: embedded_io::Write;
+}
+
+/// Streaming decoder of one of the framing modes of the serial link.
+pub trait FrameDecoder {
+    /// Process one incoming byte, reporting the produced events to `on_event`.
+    fn push<F>(&mut self, byte: u8, on_event: F)
+    where
+        F: FnMut(SfpEvent<'_>);
+
+    /// Drop any partially received frame.
+    fn reset(&mut self);
+
+    /// Process a slice of incoming bytes, reporting every produced event to `on_event`.
+    fn feed<F>(&mut self, data: &[u8], mut on_event: F)
+    where
+        F: FnMut(SfpEvent<'_>),
+    {
+        for &byte in data {
+            self.push(byte, &mut on_event);
+        }
+    }
+}
+
+/// Framing mode of the serial link, for selection at runtime.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum Framing {
+    /// Serial Frame Protocol, delimited by the Frame Marker and Data Length fields
+    #[default]
+    Sfp,
+    /// Consistent Overhead Byte Stuffing with CRC, delimited by a zero byte
+    Cobs,
+}
+
+impl Framing {
+    pub fn encoder(self, config: SfpConfig) -> AnyFrameEncoder {
+        match self {
+            Framing::Sfp => AnyFrameEncoder::Sfp(SfpFrameEncoder { config }),
+            Framing::Cobs => AnyFrameEncoder::Cobs(CobsEncoder { config }),
+        }
+    }
+
+    pub fn decoder(self, config: SfpConfig) -> AnyFrameDecoder {
+        match self {
+            Framing::Sfp => AnyFrameDecoder::Sfp(SfpDecoder::new(config)),
+            Framing::Cobs => AnyFrameDecoder::Cobs(CobsDecoder::new(config)),
+        }
+    }
+}
+
+/// SFP frame encoder.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct SfpFrameEncoder {
+    pub config: SfpConfig,
+}
+
+impl FrameEncoder for SfpFrameEncoder {
+    fn max_frame_len(&self, data_len: usize) -> usize {
+        frame_len(data_len)
+    }
+
+    fn encode(&self, data: &[u8], buffer: &mut [u8]) -> Result<usize, EncodeError> {
+        encode_frame(&self.config, data, buffer)
+    }
+
+    fn write<W>(&self, writer: &mut W, data: &[u8]) -> Result<usize, WriteFrameError<W::Error>>
+    where
+        W: embedded_io::Write,
+    {
+        write_frame(&self.config, writer, data)
+    }
+}
+
+/// COBS frame encoder.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct CobsEncoder {
+    pub config: SfpConfig,
+}
+
+impl FrameEncoder for CobsEncoder {
+    fn max_frame_len(&self, data_len: usize) -> usize {
+        cobs_frame_len(data_len)
+    }
+
+    fn encode(&self, data: &[u8], buffer: &mut [u8]) -> Result<usize, EncodeError> {
+        encode_cobs_frame(&self.config, data, buffer)
+    }
+
+    fn write<W>(&self, writer: &mut W, data: &[u8]) -> Result<usize, WriteFrameError<W::Error>>
+    where
+        W: embedded_io::Write,
+    {
+        write_cobs_frame(&self.config, writer, data)
+    }
+}
+
+impl FrameDecoder for SfpDecoder {
+    fn push<F>(&mut self, byte: u8, on_event: F)
+    where
+        F: FnMut(SfpEvent<'_>),
+    {
+        SfpDecoder::push(self, byte, on_event)
+    }
+
+    fn reset(&mut self) {
+        SfpDecoder::reset(self)
+    }
+}
+
+impl FrameDecoder for CobsDecoder {
+    fn push<F>(&mut self, byte: u8, on_event: F)
+    where
+        F: FnMut(SfpEvent<'_>),
+    {
+        CobsDecoder::push(self, byte, on_event)
+    }
+
+    fn reset(&mut self) {
+        CobsDecoder::reset(self)
+    }
+}
+
+/// Frame encoder of the framing mode selected at runtime.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AnyFrameEncoder {
+    Sfp(SfpFrameEncoder),
+    Cobs(CobsEncoder),
+}
+
+impl FrameEncoder for AnyFrameEncoder {
+    fn max_frame_len(&self, data_len: usize) -> usize {
+        match self {
+            AnyFrameEncoder::Sfp(encoder) => encoder.max_frame_len(data_len),
+            AnyFrameEncoder::Cobs(encoder) => encoder.max_frame_len(data_len),
+        }
+    }
+
+    fn encode(&self, data: &[u8], buffer: &mut [u8]) -> Result<usize, EncodeError> {
+        match self {
+            AnyFrameEncoder::Sfp(encoder) => encoder.encode(data, buffer),
+            AnyFrameEncoder::Cobs(encoder) => encoder.encode(data, buffer),
+        }
+    }
+
+    fn write<W>(&self, writer: &mut W, data: &[u8]) -> Result<usize, WriteFrameError<W::Error>>
+    where
+        W: embedded_io::Write,
+    {
+        match self {
+            AnyFrameEncoder::Sfp(encoder) => encoder.write(writer, data),
+            AnyFrameEncoder::Cobs(encoder) => encoder.write(writer, data),
+        }
+    }
+}
+
+/// Frame decoder of the framing mode selected at runtime.
+pub enum AnyFrameDecoder {
+    Sfp(SfpDecoder),
+    Cobs(CobsDecoder),
+}
+
+impl FrameDecoder for AnyFrameDecoder {
+    fn push<F>(&mut self, byte: u8, on_event: F)
+    where
+        F: FnMut(SfpEvent<'_>),
+    {
+        match self {
+            AnyFrameDecoder::Sfp(decoder) => decoder.push(byte, on_event),
+            AnyFrameDecoder::Cobs(decoder) => decoder.push(byte, on_event),
+        }
+    }
+
+    fn reset(&mut self) {
+        match self {
+            AnyFrameDecoder::Sfp(decoder) => decoder.reset(),
+            AnyFrameDecoder::Cobs(decoder) => decoder.reset(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{SFP_DATA_LEN_MAX, SFP_FRAME_MARKER};
+
+    fn round_trip(framing: Framing, data: &[u8]) -> Vec<Vec<u8>> {
+        let config = SfpConfig::default();
+        let encoder = framing.encoder(config);
+        let mut decoder = framing.decoder(config);
+
+        let mut buffer = vec![0; encoder.max_frame_len(data.len())];
+        let frame_len = encoder.encode(data, &mut buffer).unwrap();
+
+        let mut frames = Vec::new();
+        decoder.feed(&buffer[..frame_len], |event| {
+            if let SfpEvent::Frame(data) = event {
+                frames.push(data.to_vec());
+            }
+        });
+        frames
+    }
+
+    #[test]
+    fn frames_round_trip_in_both_modes() {
+        let data = [0x00, SFP_FRAME_MARKER, SFP_FRAME_MARKER, 0x00, 0x00, 0x12];
+
+        for framing in [Framing::Sfp, Framing::Cobs] {
+            assert_eq!(round_trip(framing, &data), [data.to_vec()]);
+            assert_eq!(round_trip(framing, &[0; 300]), [vec![0; 300]]);
+            assert_eq!(
+                round_trip(framing, &[0x55; SFP_DATA_LEN_MAX]),
+                [vec![0x55; SFP_DATA_LEN_MAX]]
+            );
+        }
+    }
+
+    #[test]
+    fn cobs_frame_has_no_zero_before_delimiter() {
+        let encoder = Framing::Cobs.encoder(SfpConfig::default());
+        let mut buffer = [0; 16];
+        let frame_len = encoder.encode(&[0x11, 0x00, 0x22], &mut buffer).unwrap();
+
+        assert!(!buffer[..frame_len - 1].contains(&0x00));
+        assert_eq!(buffer[frame_len - 1], 0x00);
+        assert!(frame_len <= encoder.max_frame_len(3));
+    }
+
+    #[test]
+    fn cobs_decoder_resynchronises_on_delimiter() {
+        let config = SfpConfig::default();
+        let mut buffer = [0; 32];
+        let frame_len = encode_cobs_frame(&config, b"beacon", &mut buffer).unwrap();
+
+        // Second frame lost a byte, the third one follows a burst of noise
+        let mut stream = buffer[..frame_len].to_vec();
+        stream.extend(
+            buffer[..frame_len]
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != 3)
+                .map(|(_, b)| *b),
+        );
+        stream.extend([0x17, 0xAA, 0x00]);
+        stream.extend(&buffer[..frame_len]);
+
+        let mut events = Vec::new();
+        CobsDecoder::new(config).feed(&stream, |event| {
+            events.push(match event {
+                SfpEvent::Frame(data) => Ok(data.to_vec()),
+                other => Err(format!("{:?}", other)),
+            })
+        });
+
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[0], Ok(b"beacon".to_vec()));
+        assert!(events[1].is_err());
+        assert!(events[2].is_err());
+        assert_eq!(events[3], Ok(b"beacon".to_vec()));
+    }
+
+    #[test]
+    fn cobs_encoder_checks_length_and_buffer() {
+        let encoder = CobsEncoder::default();
+
+        assert_eq!(
+            encoder.encode(&[], &mut [0; 8]),
+            Err(EncodeError::InvalidLength)
+        );
+        assert_eq!(
+            encoder.encode(&[1, 2, 3, 4, 5], &mut [0; 8]),
+            Err(EncodeError::BufferTooSmall)
+        );
+    }
+
+    #[test]
+    fn cobs_frames_are_written() {
+        let mut output = [0; 16];
+        let mut writer = &mut output[..];
+        let frame_len = Framing::Cobs
+            .encoder(SfpConfig::default())
+            .write(&mut writer, b"ok")
+            .unwrap();
+
+        let mut expected = [0; 16];
+        let expected_len = encode_cobs_frame(&SfpConfig::default(), b"ok", &mut expected).unwrap();
+        assert_eq!(&output[..frame_len], &expected[..expected_len]);
+    }
+}
@@ -0,0 +1,278 @@
+use crate::config::SfpVersion;
+use crate::encoder::EncodeError;
+
+/// Version field value of the SFP 0.2 extended header (major in high nibble, minor in low nibble)
+pub const SFP_EXT_VERSION: u8 = 0x02;
+/// Maximum size of the extended header
+pub const SFP_EXT_HEADER_LEN_MAX: usize = 5;
+
+/// Flag: the sequence counter is 16 bit wide
+pub const FLAG_SEQ_16: u8 = 0x01;
+/// Flag: the sender requests an acknowledgement of this frame
+pub const FLAG_ACK_REQUEST: u8 = 0x02;
+
+// Extended header field offsets
+const VERSION_BYTE: usize = 0;
+const TYPE_BYTE: usize = 1;
+const FLAGS_BYTE: usize = 2;
+const SEQUENCE_BYTE: usize = 3;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FrameType {
+    /// Application data
+    Data,
+    /// Positive acknowledgement
+    Ack,
+    /// Negative acknowledgement
+    Nack,
+    /// Version announcement, used to negotiate the extended format
+    Hello,
+    /// Data frame given up by the sender, the receiver stops waiting for it
+    Skip,
+}
+
+impl FrameType {
+    fn to_byte(self) -> u8 {
+        match self {
+            FrameType::Data => 0x00,
+            FrameType::Ack => 0x01,
+            FrameType::Nack => 0x02,
+            FrameType::Hello => 0x03,
+            FrameType::Skip => 0x04,
+        }
+    }
+}
+
+impl TryFrom<u8> for FrameType {
+    type Error = HeaderError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(FrameType::Data),
+            0x01 => Ok(FrameType::Ack),
+            0x02 => Ok(FrameType::Nack),
+            0x03 => Ok(FrameType::Hello),
+            0x04 => Ok(FrameType::Skip),
+            other => Err(HeaderError::UnknownFrameType(other)),
+        }
+    }
+}
+
+/// Sequence counter of a frame, either 8 or 16 bit wide.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Sequence {
+    Short(u8),
+    Long(u16),
+}
+
+impl Sequence {
+    pub fn value(self) -> u16 {
+        match self {
+            Sequence::Short(value) => value as u16,
+            Sequence::Long(value) => value,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum HeaderError {
+    /// Data field is shorter than the extended header
+    Truncated,
+    /// Extended header of an incompatible major version
+    UnsupportedVersion(u8),
+    /// Unknown frame type
+    UnknownFrameType(u8),
+}
+
+/// Extended header of the SFP 0.2 frames, placed at the beginning of the Data field.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FrameHeader {
+    /// Protocol version of the sender
+    pub version: u8,
+    pub frame_type: FrameType,
+    /// Frame flags, except FLAG_SEQ_16 which follows the sequence width
+    pub flags: u8,
+    pub sequence: Sequence,
+}
+
+impl FrameHeader {
+    pub fn new(frame_type: FrameType, sequence: Sequence) -> Self {
+        Self {
+            version: SFP_EXT_VERSION,
+            frame_type,
+            flags: 0,
+            sequence,
+        }
+    }
+
+    /// Size of the encoded header.
+    pub fn encoded_len(&self) -> usize {
+        match self.sequence {
+            Sequence::Short(_) => SEQUENCE_BYTE + 1,
+            Sequence::Long(_) => SEQUENCE_BYTE + 2,
+        }
+    }
+
+    /// Check if the given flag is set.
+    pub fn has_flag(&self, flag: u8) -> bool {
+        self.flags & flag != 0
+    }
+
+    /// Parse the header from the beginning of the Data field. Returns the header
+    /// and the size it occupies.
+    pub fn parse(bytes: &[u8]) -> Result<(Self, usize), HeaderError> {
+        if bytes.len() <= SEQUENCE_BYTE {
+            return Err(HeaderError::Truncated);
+        }
+
+        let version = bytes[VERSION_BYTE];
+        if SfpVersion::from_byte(version) != Some(SfpVersion::V0_2) {
+            return Err(HeaderError::UnsupportedVersion(version));
+        }
+
+        let frame_type = FrameType::try_from(bytes[TYPE_BYTE])?;
+        let flags = bytes[FLAGS_BYTE];
+
+        let sequence = if flags & FLAG_SEQ_16 != 0 {
+            if bytes.len() <= SEQUENCE_BYTE + 1 {
+                return Err(HeaderError::Truncated);
+            }
+            Sequence::Long(u16::from_be_bytes([
+                bytes[SEQUENCE_BYTE],
+                bytes[SEQUENCE_BYTE + 1],
+            ]))
+        } else {
+            Sequence::Short(bytes[SEQUENCE_BYTE])
+        };
+
+        let header = Self {
+            version,
+            frame_type,
+            flags: flags & !FLAG_SEQ_16,
+            sequence,
+        };
+
+        Ok((header, header.encoded_len()))
+    }
+
+    /// Encode the header into `buffer`. Returns the size of the header.
+    pub fn encode(&self, buffer: &mut [u8]) -> Result<usize, EncodeError> {
+        let header_len = self.encoded_len();
+        if buffer.len() < header_len {
+            return Err(EncodeError::BufferTooSmall);
+        }
+
+        buffer[VERSION_BYTE] = self.version;
+        buffer[TYPE_BYTE] = self.frame_type.to_byte();
+        buffer[FLAGS_BYTE] = self.flags & !FLAG_SEQ_16;
+
+        match self.sequence {
+            Sequence::Short(value) => {
+                buffer[SEQUENCE_BYTE] = value;
+            }
+            Sequence::Long(value) => {
+                buffer[FLAGS_BYTE] |= FLAG_SEQ_16;
+                buffer[SEQUENCE_BYTE..header_len].copy_from_slice(&value.to_be_bytes());
+            }
+        }
+
+        Ok(header_len)
+    }
+}
+
+/// Negotiation of the SFP version used towards a peer. Frames are sent in the legacy
+/// 0.1 format until the peer proves that it understands the extended 0.2 format.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct VersionNegotiation {
+    local: SfpVersion,
+    peer: SfpVersion,
+}
+
+impl VersionNegotiation {
+    /// Start the negotiation, with the highest version supported locally.
+    pub fn new(local: SfpVersion) -> Self {
+        Self {
+            local,
+            peer: SfpVersion::V0_1,
+        }
+    }
+
+    /// Account for a header received from the peer.
+    pub fn on_header_received(&mut self, header: &FrameHeader) {
+        if let Some(version) = SfpVersion::from_byte(header.version) {
+            self.peer = self.peer.max(version);
+        }
+    }
+
+    /// Highest version supported by both sides.
+    pub fn version(&self) -> SfpVersion {
+        self.local.min(self.peer)
+    }
+
+    /// Peer version has not been confirmed yet, a Hello frame shall be sent to announce
+    /// the local version.
+    pub fn is_pending(&self) -> bool {
+        self.peer < self.local
+    }
+
+    /// Header of the Hello frame announcing the local version.
+    pub fn hello(&self) -> FrameHeader {
+        FrameHeader::new(FrameType::Hello, Sequence::Short(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_round_trip() {
+        let mut header = FrameHeader::new(FrameType::Data, Sequence::Long(0x1234));
+        header.flags = FLAG_ACK_REQUEST;
+
+        let mut buffer = [0; SFP_EXT_HEADER_LEN_MAX];
+        assert_eq!(header.encode(&mut buffer), Ok(5));
+        assert_eq!(buffer, [0x02, 0x00, 0x03, 0x12, 0x34]);
+        assert_eq!(FrameHeader::parse(&buffer), Ok((header, 5)));
+        assert!(header.has_flag(FLAG_ACK_REQUEST));
+
+        let header = FrameHeader::new(FrameType::Ack, Sequence::Short(7));
+        assert_eq!(header.encode(&mut buffer), Ok(4));
+        assert_eq!(FrameHeader::parse(&buffer[..4]), Ok((header, 4)));
+    }
+
+    #[test]
+    fn invalid_headers_are_rejected() {
+        assert_eq!(
+            FrameHeader::parse(&[0x02, 0x00, 0x00]),
+            Err(HeaderError::Truncated)
+        );
+        assert_eq!(
+            FrameHeader::parse(&[0x02, 0x00, FLAG_SEQ_16, 0x00]),
+            Err(HeaderError::Truncated)
+        );
+        assert_eq!(
+            FrameHeader::parse(&[0x12, 0x00, 0x00, 0x00]),
+            Err(HeaderError::UnsupportedVersion(0x12))
+        );
+        assert_eq!(
+            FrameHeader::parse(&[0x02, 0x7F, 0x00, 0x00]),
+            Err(HeaderError::UnknownFrameType(0x7F))
+        );
+    }
+
+    #[test]
+    fn version_is_negotiated() {
+        let mut negotiation = VersionNegotiation::new(SfpVersion::V0_2);
+        assert_eq!(negotiation.version(), SfpVersion::V0_1);
+        assert!(negotiation.is_pending());
+
+        negotiation.on_header_received(&negotiation.hello());
+        assert_eq!(negotiation.version(), SfpVersion::V0_2);
+        assert!(!negotiation.is_pending());
+
+        let mut legacy = VersionNegotiation::new(SfpVersion::V0_1);
+        legacy.on_header_received(&negotiation.hello());
+        assert_eq!(legacy.version(), SfpVersion::V0_1);
+    }
+}
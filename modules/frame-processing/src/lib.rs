@@ -1,4 +1,23 @@
-#![no_std]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+
+/// Report a diagnostic message through the "log" facade, when the "log" feature is enabled.
+macro_rules! diag {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "log")]
+        log::debug!($($arg)*);
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($arg)*);
+    };
+}
 
 /// Frame Processing Crate
+pub mod arq;
+pub mod cobs;
+pub mod config;
+pub mod decoder;
+pub mod encoder;
 pub mod frame;
+pub mod framed;
+pub mod framing;
+pub mod header;
+pub mod segment;
@@ -0,0 +1,526 @@
+use crate::frame::{SFP_DATA_LEN_MAX, SFP_DATA_LEN_MIN};
+
+/// Size of the fragment header: transfer id, fragment index, fragment count and the
+/// 16-bit total payload length
+pub const FRAGMENT_HEADER_LEN: usize = 5;
+/// Maximum number of fragments of a transfer
+pub const FRAGMENT_COUNT_MAX: usize = u8::MAX as usize;
+
+// Fragment header field offsets
+const TRANSFER_BYTE: usize = 0;
+const INDEX_BYTE: usize = 1;
+const COUNT_BYTE: usize = 2;
+const LENGTH_BYTE: usize = 3;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SegmentError {
+    /// Payload length is outside of SFP_DATA_LEN_MIN and SFP_DATA_LEN_MAX, or needs too many fragments
+    InvalidLength,
+    /// Fragment is shorter than its header and data
+    Truncated,
+    /// Fragment header is inconsistent (index beyond count, count not matching the length)
+    InvalidHeader,
+}
+
+/// Fail the build of a segmenter or reassembler whose fragments cannot carry any payload.
+const fn check_size(size: usize) {
+    assert!(
+        size > FRAGMENT_HEADER_LEN,
+        "fragment SIZE must be larger than FRAGMENT_HEADER_LEN"
+    );
+}
+
+/// Number of payload bytes carried by a fragment of `size` bytes.
+const fn chunk_len(size: usize) -> usize {
+    size - FRAGMENT_HEADER_LEN
+}
+
+/// Number of fragments of `size` bytes needed to carry `payload_len` bytes.
+pub const fn fragment_count(size: usize, payload_len: usize) -> usize {
+    payload_len.div_ceil(chunk_len(size))
+}
+
+/// Header of a radio fragment.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FragmentHeader {
+    /// Identifier of the transfer, shared by all the fragments of a payload
+    pub transfer_id: u8,
+    /// Position of the fragment in the transfer
+    pub index: u8,
+    /// Number of fragments of the transfer
+    pub count: u8,
+    /// Size of the whole payload
+    pub length: u16,
+}
+
+impl FragmentHeader {
+    /// Check if both fragments belong to the same transfer.
+    fn is_same_transfer(&self, other: &FragmentHeader) -> bool {
+        self.transfer_id == other.transfer_id
+            && self.count == other.count
+            && self.length == other.length
+    }
+
+    /// Parse the header from the beginning of a fragment.
+    pub fn parse(bytes: &[u8]) -> Result<Self, SegmentError> {
+        if bytes.len() < FRAGMENT_HEADER_LEN {
+            return Err(SegmentError::Truncated);
+        }
+
+        Ok(Self {
+            transfer_id: bytes[TRANSFER_BYTE],
+            index: bytes[INDEX_BYTE],
+            count: bytes[COUNT_BYTE],
+            length: u16::from_be_bytes([bytes[LENGTH_BYTE], bytes[LENGTH_BYTE + 1]]),
+        })
+    }
+
+    fn encode(&self, buffer: &mut [u8]) {
+        buffer[TRANSFER_BYTE] = self.transfer_id;
+        buffer[INDEX_BYTE] = self.index;
+        buffer[COUNT_BYTE] = self.count;
+        buffer[LENGTH_BYTE..FRAGMENT_HEADER_LEN].copy_from_slice(&self.length.to_be_bytes());
+    }
+}
+
+/// Radio fragment of at most `SIZE` bytes.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Fragment<const SIZE: usize> {
+    data: [u8; SIZE],
+    length: usize,
+}
+
+impl<const SIZE: usize> Fragment<SIZE> {
+    /// Fragment bytes, header included.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data[..self.length]
+    }
+
+    /// Fragment padded with zeros to `SIZE` bytes, for links with fixed packet length.
+    pub fn as_packet(&self) -> &[u8; SIZE] {
+        &self.data
+    }
+}
+
+/// Split a payload of up to SFP_DATA_LEN_MAX bytes into fragments of `SIZE` bytes, e.g. 64
+/// for the CC1101 FIFO. `SIZE` must be larger than FRAGMENT_HEADER_LEN, checked at compile
+/// time.
+pub struct Segmenter<'a, const SIZE: usize> {
+    transfer_id: u8,
+    payload: &'a [u8],
+    index: usize,
+    count: usize,
+}
+
+impl<'a, const SIZE: usize> Segmenter<'a, SIZE> {
+    const SIZE_CHECK: () = check_size(SIZE);
+
+    pub fn new(transfer_id: u8, payload: &'a [u8]) -> Result<Self, SegmentError> {
+        let () = Self::SIZE_CHECK;
+        let count = fragment_count(SIZE, payload.len());
+        if !(SFP_DATA_LEN_MIN..=SFP_DATA_LEN_MAX).contains(&payload.len())
+            || count > FRAGMENT_COUNT_MAX
+        {
+            return Err(SegmentError::InvalidLength);
+        }
+
+        Ok(Self {
+            transfer_id,
+            payload,
+            index: 0,
+            count,
+        })
+    }
+
+    /// Number of fragments of the transfer.
+    pub fn fragment_count(&self) -> usize {
+        self.count
+    }
+}
+
+impl<'a, const SIZE: usize> Iterator for Segmenter<'a, SIZE> {
+    type Item = Fragment<SIZE>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.count {
+            return None;
+        }
+
+        let start = self.index * chunk_len(SIZE);
+        let end = (start + chunk_len(SIZE)).min(self.payload.len());
+        let header = FragmentHeader {
+            transfer_id: self.transfer_id,
+            index: self.index as u8,
+            count: self.count as u8,
+            length: self.payload.len() as u16,
+        };
+
+        let mut fragment = Fragment {
+            data: [0; SIZE],
+            length: FRAGMENT_HEADER_LEN + end - start,
+        };
+        header.encode(&mut fragment.data);
+        fragment.data[FRAGMENT_HEADER_LEN..fragment.length]
+            .copy_from_slice(&self.payload[start..end]);
+
+        self.index += 1;
+        Some(fragment)
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum ReassemblyEvent<'a> {
+    /// All the fragments of a transfer were received
+    Complete(&'a [u8]),
+    /// Transfer was abandoned, because of a timeout or because a new transfer started
+    Incomplete {
+        transfer_id: u8,
+        received: usize,
+        count: usize,
+    },
+}
+
+#[derive(Copy, Clone)]
+struct Transfer {
+    header: FragmentHeader,
+    received: [u32; 8],
+    received_count: usize,
+    last_fragment_at: u64,
+}
+
+impl Transfer {
+    fn is_received(&self, index: u8) -> bool {
+        self.received[index as usize / 32] & (1 << (index % 32)) != 0
+    }
+
+    fn set_received(&mut self, index: u8) {
+        self.received[index as usize / 32] |= 1 << (index % 32);
+        self.received_count += 1;
+    }
+}
+
+/// Reassembly of the fragments of `SIZE` bytes produced by a `Segmenter`. Fragments may
+/// arrive out of order or duplicated, also after the end of their transfer; a transfer is
+/// abandoned when no fragment was received for `timeout_ms` milliseconds, driven by the
+/// caller-supplied time.
+pub struct Reassembler<const SIZE: usize> {
+    timeout_ms: u64,
+    transfer: Option<Transfer>,
+    completed: Option<FragmentHeader>,
+    buffer: [u8; SFP_DATA_LEN_MAX],
+}
+
+impl<const SIZE: usize> Reassembler<SIZE> {
+    const SIZE_CHECK: () = check_size(SIZE);
+
+    pub const fn new(timeout_ms: u64) -> Self {
+        let () = Self::SIZE_CHECK;
+        Self {
+            timeout_ms,
+            transfer: None,
+            completed: None,
+            buffer: [0; SFP_DATA_LEN_MAX],
+        }
+    }
+
+    /// Check if a transfer is in progress.
+    pub fn is_busy(&self) -> bool {
+        self.transfer.is_some()
+    }
+
+    /// Process a received fragment. Packets padded to a fixed length are accepted.
+    pub fn push<F>(
+        &mut self,
+        fragment: &[u8],
+        now: u64,
+        mut on_event: F,
+    ) -> Result<(), SegmentError>
+    where
+        F: FnMut(ReassemblyEvent<'_>),
+    {
+        let header = FragmentHeader::parse(fragment)?;
+        let length = header.length as usize;
+
+        if !(SFP_DATA_LEN_MIN..=SFP_DATA_LEN_MAX).contains(&length)
+            || header.count as usize != fragment_count(SIZE, length)
+            || header.index >= header.count
+        {
+            return Err(SegmentError::InvalidHeader);
+        }
+
+        let start = header.index as usize * chunk_len(SIZE);
+        let end = (start + chunk_len(SIZE)).min(length);
+        if fragment.len() < FRAGMENT_HEADER_LEN + end - start {
+            return Err(SegmentError::Truncated);
+        }
+
+        self.poll(now, &mut on_event);
+
+        let same_transfer = self
+            .transfer
+            .is_some_and(|transfer| transfer.header.is_same_transfer(&header));
+        if !same_transfer
+            && self
+                .completed
+                .is_some_and(|completed| completed.is_same_transfer(&header))
+        {
+            // Late duplicate of the last completed transfer
+            return Ok(());
+        }
+        if !same_transfer {
+            self.abandon(&mut on_event);
+            self.transfer = Some(Transfer {
+                header,
+                received: [0; 8],
+                received_count: 0,
+                last_fragment_at: now,
+            });
+        }
+
+        let Some(transfer) = self.transfer.as_mut() else {
+            return Ok(());
+        };
+        transfer.last_fragment_at = now;
+
+        if transfer.is_received(header.index) {
+            // Duplicate
+            return Ok(());
+        }
+
+        transfer.set_received(header.index);
+        self.buffer[start..end]
+            .copy_from_slice(&fragment[FRAGMENT_HEADER_LEN..FRAGMENT_HEADER_LEN + end - start]);
+
+        if transfer.received_count == header.count as usize {
+            self.transfer = None;
+            self.completed = Some(header);
+            on_event(ReassemblyEvent::Complete(&self.buffer[..length]));
+        }
+
+        Ok(())
+    }
+
+    /// Abandon the transfer in progress if it timed out.
+    pub fn poll<F>(&mut self, now: u64, mut on_event: F)
+    where
+        F: FnMut(ReassemblyEvent<'_>),
+    {
+        if let Some(transfer) = self.transfer {
+            if now.wrapping_sub(transfer.last_fragment_at) >= self.timeout_ms {
+                self.abandon(&mut on_event);
+            }
+        }
+    }
+
+    /// Abandon the transfer in progress, if any, and forget the last completed one.
+    pub fn reset(&mut self) {
+        self.transfer = None;
+        self.completed = None;
+    }
+
+    fn abandon<F>(&mut self, on_event: &mut F)
+    where
+        F: FnMut(ReassemblyEvent<'_>),
+    {
+        if let Some(transfer) = self.transfer.take() {
+            on_event(ReassemblyEvent::Incomplete {
+                transfer_id: transfer.header.transfer_id,
+                received: transfer.received_count,
+                count: transfer.header.count as usize,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: usize = 64;
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Events {
+        complete: Vec<Vec<u8>>,
+        incomplete: Vec<(u8, usize, usize)>,
+    }
+
+    impl Events {
+        fn record(&mut self) -> impl FnMut(ReassemblyEvent<'_>) + '_ {
+            move |event| match event {
+                ReassemblyEvent::Complete(data) => self.complete.push(data.to_vec()),
+                ReassemblyEvent::Incomplete {
+                    transfer_id,
+                    received,
+                    count,
+                } => self.incomplete.push((transfer_id, received, count)),
+            }
+        }
+    }
+
+    fn payload(length: usize) -> Vec<u8> {
+        (0..length).map(|i| (i * 7) as u8).collect()
+    }
+
+    fn fragments(transfer_id: u8, payload: &[u8]) -> Vec<Fragment<SIZE>> {
+        Segmenter::<SIZE>::new(transfer_id, payload)
+            .unwrap()
+            .collect()
+    }
+
+    #[test]
+    fn largest_payload_round_trip() {
+        let payload = payload(SFP_DATA_LEN_MAX);
+        let fragments = fragments(1, &payload);
+        assert_eq!(fragments.len(), 18);
+        assert!(fragments.iter().all(|f| f.as_bytes().len() <= SIZE));
+
+        let mut reassembler = Reassembler::<SIZE>::new(1000);
+        let mut events = Events::default();
+        for fragment in &fragments {
+            reassembler
+                .push(fragment.as_packet(), 0, events.record())
+                .unwrap();
+        }
+
+        assert_eq!(events.complete, [payload]);
+        assert!(!reassembler.is_busy());
+    }
+
+    #[test]
+    fn out_of_order_and_duplicate_fragments() {
+        let payload = payload(300);
+        let fragments = fragments(2, &payload);
+
+        let mut reassembler = Reassembler::<SIZE>::new(1000);
+        let mut events = Events::default();
+        for fragment in fragments.iter().rev().chain(fragments.iter().skip(2)) {
+            reassembler
+                .push(fragment.as_bytes(), 0, events.record())
+                .unwrap();
+        }
+
+        assert_eq!(
+            events,
+            Events {
+                complete: vec![payload],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn incomplete_transfer_is_reported() {
+        let fragments = fragments(3, &payload(200));
+        let mut reassembler = Reassembler::<SIZE>::new(500);
+        let mut events = Events::default();
+
+        reassembler
+            .push(fragments[0].as_bytes(), 0, events.record())
+            .unwrap();
+        reassembler
+            .push(fragments[2].as_bytes(), 100, events.record())
+            .unwrap();
+        reassembler.poll(599, events.record());
+        assert!(reassembler.is_busy());
+        reassembler.poll(600, events.record());
+
+        assert_eq!(events.incomplete, [(3, 2, 4)]);
+        assert!(!reassembler.is_busy());
+    }
+
+    #[test]
+    fn new_transfer_abandons_previous_one() {
+        let first = fragments(4, &payload(100));
+        let second = fragments(5, b"ping");
+        let mut reassembler = Reassembler::<SIZE>::new(500);
+        let mut events = Events::default();
+
+        reassembler
+            .push(first[0].as_bytes(), 0, events.record())
+            .unwrap();
+        reassembler
+            .push(second[0].as_bytes(), 10, events.record())
+            .unwrap();
+
+        assert_eq!(
+            events,
+            Events {
+                complete: vec![b"ping".to_vec()],
+                incomplete: vec![(4, 1, 2)],
+            }
+        );
+    }
+
+    #[test]
+    fn late_duplicates_of_a_completed_transfer_are_dropped() {
+        let single = fragments(6, b"ping");
+        let multiple = fragments(7, &payload(100));
+        let next = fragments(8, b"pong");
+        let mut reassembler = Reassembler::<SIZE>::new(500);
+        let mut events = Events::default();
+
+        for fragment in single.iter().chain(&single) {
+            reassembler
+                .push(fragment.as_bytes(), 0, events.record())
+                .unwrap();
+        }
+        for fragment in multiple.iter().chain(&multiple[..1]) {
+            reassembler
+                .push(fragment.as_bytes(), 10, events.record())
+                .unwrap();
+        }
+        assert!(!reassembler.is_busy());
+        reassembler
+            .push(next[0].as_bytes(), 20, events.record())
+            .unwrap();
+
+        assert_eq!(
+            events,
+            Events {
+                complete: vec![b"ping".to_vec(), payload(100), b"pong".to_vec()],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_fragments_are_rejected() {
+        let mut reassembler = Reassembler::<SIZE>::new(500);
+        let mut events = Events::default();
+
+        assert_eq!(
+            reassembler.push(&[0, 0, 1], 0, events.record()),
+            Err(SegmentError::Truncated)
+        );
+        // Index beyond count
+        assert_eq!(
+            reassembler.push(&[0, 1, 1, 0, 4, 1, 2, 3, 4], 0, events.record()),
+            Err(SegmentError::InvalidHeader)
+        );
+        // Count not matching the length
+        assert_eq!(
+            reassembler.push(&[0, 0, 2, 0, 4, 1, 2, 3, 4], 0, events.record()),
+            Err(SegmentError::InvalidHeader)
+        );
+        // Data shorter than announced
+        assert_eq!(
+            reassembler.push(&[0, 0, 1, 0, 4, 1, 2], 0, events.record()),
+            Err(SegmentError::Truncated)
+        );
+        assert_eq!(events, Events::default());
+    }
+
+    #[test]
+    fn segmenter_checks_payload_length() {
+        assert!(Segmenter::<SIZE>::new(0, &[]).is_err());
+        assert!(Segmenter::<SIZE>::new(0, &[0; SFP_DATA_LEN_MAX + 1]).is_err());
+        // 1024 bytes in 3-byte chunks need more than 255 fragments
+        assert!(Segmenter::<8>::new(0, &[0; SFP_DATA_LEN_MAX]).is_err());
+        assert_eq!(
+            Segmenter::<SIZE>::new(0, &[0; 59])
+                .unwrap()
+                .fragment_count(),
+            1
+        );
+    }
+}
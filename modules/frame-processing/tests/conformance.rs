@@ -0,0 +1,125 @@
+//! SFP conformance tests, driven by the test vectors shared with the tools scripts.
+
+use frame_processing::{
+    config::{SfpConfig, SfpCrc},
+    decoder::{SfpDecoder, SfpEvent},
+    encoder::EncodeError,
+    frame::pack_frame_with,
+};
+
+const TEST_VECTORS: &str = include_str!("../../../tools/sfp_test_vectors.txt");
+
+#[derive(Debug, PartialEq)]
+enum Expected {
+    Frame,
+    CrcError,
+    LengthError,
+}
+
+struct TestVector {
+    config: SfpConfig,
+    expected: Expected,
+    frame: Vec<u8>,
+}
+
+fn parse_hex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+fn test_vectors() -> Vec<TestVector> {
+    TEST_VECTORS
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let crc = match fields[0] {
+                "ccitt" => SfpCrc::Crc16Ccitt,
+                "usb" => SfpCrc::Crc16Usb,
+                other => panic!("Unknown CRC type: {}", other),
+            };
+            let expected = match fields[1] {
+                "frame" => Expected::Frame,
+                "crc_error" => Expected::CrcError,
+                "length_error" => Expected::LengthError,
+                other => panic!("Unknown expected result: {}", other),
+            };
+
+            TestVector {
+                config: SfpConfig {
+                    crc,
+                    ..Default::default()
+                },
+                expected,
+                frame: parse_hex(fields[2]),
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn decoder_matches_test_vectors() {
+    for vector in test_vectors() {
+        let mut decoder = SfpDecoder::new(vector.config);
+        let mut result = None;
+
+        decoder.feed(&vector.frame, |event| {
+            if result.is_some() {
+                return;
+            }
+            result = match event {
+                SfpEvent::Frame(data) => {
+                    assert_eq!(data, &vector.frame[4..vector.frame.len() - 2]);
+                    Some(Expected::Frame)
+                }
+                SfpEvent::CrcError => Some(Expected::CrcError),
+                SfpEvent::LengthOutOfRange => Some(Expected::LengthError),
+                _ => None,
+            };
+        });
+
+        assert_eq!(result, Some(vector.expected), "frame {:02x?}", vector.frame);
+    }
+}
+
+#[test]
+fn encoder_matches_test_vectors() {
+    for vector in test_vectors() {
+        if vector.expected != Expected::Frame {
+            continue;
+        }
+
+        let mut buffer = [0; 1024];
+        let data = &vector.frame[4..vector.frame.len() - 2];
+        let length = pack_frame_with(&vector.config, data, &mut buffer).unwrap();
+
+        assert_eq!(&buffer[..length], &vector.frame[..]);
+    }
+}
+
+#[test]
+fn decoder_rejects_length_above_configured_maximum() {
+    let config = SfpConfig {
+        max_len: 4,
+        ..Default::default()
+    };
+    let mut buffer = [0; 1024];
+    assert_eq!(
+        pack_frame_with(&config, b"hello", &mut buffer),
+        Err(EncodeError::InvalidLength)
+    );
+    let length = pack_frame_with(&SfpConfig::default(), b"hello", &mut buffer).unwrap();
+
+    let mut decoder = SfpDecoder::new(config);
+    let mut rejected = false;
+    decoder.feed(&buffer[..length], |event| {
+        if event == SfpEvent::LengthOutOfRange {
+            rejected = true;
+        }
+    });
+
+    assert!(rejected);
+}
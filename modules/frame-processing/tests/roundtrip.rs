@@ -0,0 +1,159 @@
+//! Property-based round-trip tests of the frame packing and decoding.
+
+use frame_processing::{
+    config::SfpConfig,
+    decoder::{SfpDecoder, SfpEvent},
+    frame::{pack_frame, SFP_FRAME_MARKER},
+    framing::{FrameDecoder, FrameEncoder, Framing},
+};
+use proptest::prelude::*;
+
+// Largest payload which fits, together with header and CRC, in the pack_frame buffer
+const PAYLOAD_LEN_MAX: usize = 1018;
+
+#[derive(Debug, Default, PartialEq)]
+struct Decoded {
+    frames: Vec<Vec<u8>>,
+    crc_errors: usize,
+    length_errors: usize,
+    passthrough: Vec<u8>,
+}
+
+fn pack(data: &[u8]) -> Vec<u8> {
+    let mut buffer = [0; 1024];
+    let frame_len = pack_frame(data, &mut buffer).unwrap();
+    buffer[..frame_len].to_vec()
+}
+
+fn decode(stream: &[u8]) -> Decoded {
+    let mut decoder = SfpDecoder::new(SfpConfig::default());
+    let mut decoded = Decoded::default();
+
+    decoder.feed(stream, |event| match event {
+        SfpEvent::Frame(data) => decoded.frames.push(data.to_vec()),
+        SfpEvent::CrcError => decoded.crc_errors += 1,
+        SfpEvent::LengthOutOfRange => decoded.length_errors += 1,
+        SfpEvent::NonFrameByte(byte) => decoded.passthrough.push(byte),
+        other => panic!("Unexpected event: {:?}", other),
+    });
+
+    decoded
+}
+
+fn cobs_pack(data: &[u8]) -> Vec<u8> {
+    let encoder = Framing::Cobs.encoder(SfpConfig::default());
+    let mut buffer = vec![0; encoder.max_frame_len(data.len())];
+    let frame_len = encoder.encode(data, &mut buffer).unwrap();
+    buffer.truncate(frame_len);
+    buffer
+}
+
+fn cobs_decode(stream: &[u8]) -> Decoded {
+    let mut decoder = Framing::Cobs.decoder(SfpConfig::default());
+    let mut decoded = Decoded::default();
+
+    decoder.feed(stream, |event| match event {
+        SfpEvent::Frame(data) => decoded.frames.push(data.to_vec()),
+        SfpEvent::CrcError | SfpEvent::EncodingError => decoded.crc_errors += 1,
+        SfpEvent::LengthOutOfRange => decoded.length_errors += 1,
+        other => panic!("Unexpected event: {:?}", other),
+    });
+
+    decoded
+}
+
+fn payload() -> impl Strategy<Value = Vec<u8>> {
+    prop::collection::vec(any::<u8>(), 1..=PAYLOAD_LEN_MAX)
+}
+
+proptest! {
+    #[test]
+    fn packed_frame_is_decoded(data in payload()) {
+        let decoded = decode(&pack(&data));
+
+        prop_assert_eq!(decoded, Decoded { frames: vec![data], ..Default::default() });
+    }
+
+    #[test]
+    fn consecutive_frames_are_decoded(frames in prop::collection::vec(payload(), 1..8)) {
+        let stream: Vec<u8> = frames.iter().flat_map(|data| pack(data)).collect();
+        let decoded = decode(&stream);
+
+        prop_assert_eq!(decoded, Decoded { frames, ..Default::default() });
+    }
+
+    #[test]
+    fn frame_is_decoded_after_noise(
+        noise in prop::collection::vec(any::<u8>().prop_filter("not a marker", |b| *b != SFP_FRAME_MARKER), 0..64),
+        data in payload(),
+    ) {
+        let mut stream = noise.clone();
+        stream.extend(pack(&data));
+        let decoded = decode(&stream);
+
+        prop_assert_eq!(decoded, Decoded { frames: vec![data], passthrough: noise, ..Default::default() });
+    }
+
+    #[test]
+    fn corrupted_bit_is_detected(data in payload(), bit in any::<prop::sample::Index>()) {
+        let mut stream = pack(&data);
+
+        // Flip one bit of the Data or CRC fields
+        let bit = 32 + bit.index((stream.len() - 4) * 8);
+        stream[bit / 8] ^= 1 << (bit % 8);
+        let decoded = decode(&stream);
+
+        prop_assert_eq!(decoded, Decoded { crc_errors: 1, ..Default::default() });
+    }
+
+    #[test]
+    fn corrupted_burst_is_detected(
+        data in payload(),
+        start in any::<prop::sample::Index>(),
+        pattern in 1u16..,
+    ) {
+        let mut stream = pack(&data);
+
+        // Apply a burst error of up to 16 bits to the Data or CRC fields
+        let bit = 32 + start.index((stream.len() - 4) * 8 - 15);
+        for offset in 0..16 {
+            if pattern & (1 << offset) != 0 {
+                let position = bit + offset;
+                stream[position / 8] ^= 0x80 >> (position % 8);
+            }
+        }
+        let decoded = decode(&stream);
+
+        prop_assert_eq!(decoded, Decoded { crc_errors: 1, ..Default::default() });
+    }
+
+    #[test]
+    fn truncated_frame_is_not_decoded(data in payload(), cut in any::<prop::sample::Index>()) {
+        let stream = pack(&data);
+        let decoded = decode(&stream[..cut.index(stream.len())]);
+
+        prop_assert_eq!(decoded, Decoded::default());
+    }
+
+    #[test]
+    fn cobs_frames_are_decoded(frames in prop::collection::vec(payload(), 1..8)) {
+        let stream: Vec<u8> = frames.iter().flat_map(|data| cobs_pack(data)).collect();
+        let decoded = cobs_decode(&stream);
+
+        prop_assert_eq!(decoded, Decoded { frames, ..Default::default() });
+    }
+
+    #[test]
+    fn cobs_corrupted_bit_is_detected(data in payload(), bit in any::<prop::sample::Index>()) {
+        let mut stream = cobs_pack(&data);
+
+        // Flip one bit of any byte but the delimiter, without creating a new delimiter
+        let bit = bit.index((stream.len() - 1) * 8);
+        stream[bit / 8] ^= 1 << (bit % 8);
+        prop_assume!(stream[bit / 8] != 0);
+        let decoded = cobs_decode(&stream);
+
+        prop_assert_eq!(decoded.frames.len(), 0);
+        prop_assert!(decoded.crc_errors + decoded.length_errors >= 1);
+    }
+}
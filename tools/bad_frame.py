@@ -5,11 +5,11 @@ import help_serial
 parser = argparse.ArgumentParser(description='A tool to establish serial link with NUCLEO-F767ZI board')
 parser.add_argument('-p', '--port', type=str, required=True, help='Serial COM Port')
 parser.add_argument('-b', '--baudrate', type=int, default=115200, help='Baudrate')
+parser.add_argument('-c', '--crc', type=str, default='ccitt', choices=help_serial.CRC_TYPES.keys(), help='SFP CRC type')
 args = parser.parse_args()
 
-my_serial = help_serial.SerialLink(args.port, args.baudrate)
-my_frame = 'aaaa0002aabbc861'
-byte_data = bytes.fromhex(my_frame)
+my_serial = help_serial.SerialLink(args.port, args.baudrate, args.crc)
+byte_data = help_serial.load_test_vectors(args.crc, 'crc_error')[0]
 
 my_serial.send_frame(byte_data)
 my_serial.listen_frame()
@@ -1,5 +1,6 @@
 import serial
 import argparse
+import os
 import time
 import crcmod.predefined
 
@@ -19,17 +20,39 @@ SIZE_OFFSET = 2
 MESSAGE_OFFSET = 4
 
 
-crc16 = crcmod.predefined.Crc('crc-16-usb')
+# SFP CRC types, as named in sfp_test_vectors.txt
+CRC_TYPES = {
+    'ccitt': 'crc-ccitt-false',
+    'usb': 'crc-16-usb',
+}
+
+TEST_VECTORS_FILE = os.path.join(os.path.dirname(os.path.abspath(__file__)), 'sfp_test_vectors.txt')
+
+
+def load_test_vectors(crc, expected):
+    """Return the frames from sfp_test_vectors.txt matching the CRC type and expected result"""
+    frames = []
+    with open(TEST_VECTORS_FILE) as vectors:
+        for line in vectors:
+            line = line.strip()
+            if not line or line.startswith('#'):
+                continue
+            vector_crc, vector_expected, frame = line.split()
+            if vector_crc == crc and vector_expected == expected:
+                frames.append(bytes.fromhex(frame))
+    return frames
 
 
 class SerialLink:
-    def __init__(self, port, baudrate) -> None:
+    def __init__(self, port, baudrate, crc='ccitt') -> None:
 
         self.serial = serial.Serial(port, baudrate)
+        self.crc16 = crcmod.predefined.mkPredefinedCrcFun(CRC_TYPES[crc])
 
         print("Started serial link:")
         print(f"{TAB}Port:     {port}")
         print(f"{TAB}Baudrate: {baudrate}")
+        print(f"{TAB}CRC:      {crc}")
         print(f"{SEP}")
 
 
@@ -69,8 +92,7 @@ class SerialLink:
                             frame_crc = int.from_bytes(buffer[-2:], byteorder='big')
 
                             #Calculate CRC (calculated on size and data field from the frame)
-                            crc16.update(buffer[SIZE_OFFSET:-2])
-                            checksum = crc16.crcValue
+                            checksum = self.crc16(bytes(buffer[SIZE_OFFSET:-2]))
 
                             if frame_crc == checksum:
                                 print(f"Received frame:\nLength: {data_len}\nUseful data: {frame_data.hex()}\nCRC: {hex(frame_crc)}\n\n")